@@ -0,0 +1,31 @@
+use sch_rs::interpreter::eval::{Engine, Evalator};
+use std::time::{Duration, Instant};
+
+// * the program defining the procedure and the call which is timed
+const BENCHMARKS: &[(&str, &str, &str)] = &[
+    ("fib", include_str!("../bench/fib.sch"), "(fib 25)"),
+    ("tak", include_str!("../bench/tak.sch"), "(tak 18 12 6)"),
+    ("nqueens", include_str!("../bench/nqueens.sch"), "(queens 8)"),
+];
+
+fn run(engine: Engine, name: &str, program: &str, call: &str) -> (String, Duration) {
+    let mut evalator = Evalator::new();
+    evalator.set_engine(engine);
+    evalator.eval_source(name, program).unwrap();
+    let start = Instant::now();
+    let v = evalator.eval_source("<bench>", call).unwrap();
+    (format!("{:?}", v), start.elapsed())
+}
+
+/**
+ * * cargo bench: time every benchmark on the tree-walker and on the vm
+ */
+fn main() {
+    println!("{:<10} {:>8} {:>14} {:>14} {:>8}", "benchmark", "value", "tree", "vm", "speedup");
+    for (name, program, call) in BENCHMARKS {
+        let (expect, tree) = run(Engine::Tree, name, program, call);
+        let (value, vm) = run(Engine::Vm, name, program, call);
+        assert_eq!(value, expect, "{} has a different value on the vm", name);
+        println!("{:<10} {:>8} {:>14?} {:>14?} {:>7.1}x", name, value, tree, vm, tree.as_secs_f64() / vm.as_secs_f64());
+    }
+}
@@ -0,0 +1,769 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use crate::repl::Input;
+
+// * the history file, kept in the home directory of the user
+const HISTORY_FILE: &str = ".sch_rs_history";
+const HISTORY_SIZE: usize = 1000;
+
+/**
+ * * the terminal side of the editor: raw mode through termios
+ * ! only on linux for the architectures whose termios layout is the one below
+ */
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod term {
+    use std::io::{self, IsTerminal};
+
+    /**
+     * * struct termios of glibc and musl on linux for x86_64 and aarch64
+     */
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Termios {
+        c_iflag: u32,
+        c_oflag: u32,
+        c_cflag: u32,
+        c_lflag: u32,
+        _c_line: u8,
+        c_cc: [u8; 32],
+        _c_ispeed: u32,
+        _c_ospeed: u32,
+    }
+
+    extern "C" {
+        fn tcgetattr(fd: i32, termios: *mut Termios) -> i32;
+        fn tcsetattr(fd: i32, action: i32, termios: *const Termios) -> i32;
+    }
+
+    const BRKINT: u32 = 0o2;
+    const INPCK: u32 = 0o20;
+    const ISTRIP: u32 = 0o40;
+    const ICRNL: u32 = 0o400;
+    const IXON: u32 = 0o2000;
+    const CS8: u32 = 0o60;
+    const ISIG: u32 = 0o1;
+    const ICANON: u32 = 0o2;
+    const ECHO: u32 = 0o10;
+    const IEXTEN: u32 = 0o100000;
+    const VTIME: usize = 5;
+    const VMIN: usize = 6;
+    const TCSADRAIN: i32 = 1;
+
+    pub fn is_terminal() -> bool {
+        io::stdin().is_terminal() && io::stdout().is_terminal()
+    }
+
+    /**
+     * * stdin in raw mode: no echo, no line buffering, no signals from the keyboard
+     * ! the original mode is restored when it is dropped
+     */
+    pub struct RawMode {
+        original: Termios,
+    }
+
+    impl RawMode {
+        pub fn enable() -> io::Result<RawMode> {
+            let mut original = Termios {
+                c_iflag: 0,
+                c_oflag: 0,
+                c_cflag: 0,
+                c_lflag: 0,
+                _c_line: 0,
+                c_cc: [0; 32],
+                _c_ispeed: 0,
+                _c_ospeed: 0,
+            };
+            if unsafe { tcgetattr(0, &mut original) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let mut raw = original;
+            raw.c_iflag &= !(BRKINT | ICRNL | INPCK | ISTRIP | IXON);
+            raw.c_cflag |= CS8;
+            raw.c_lflag &= !(ECHO | ICANON | IEXTEN | ISIG);
+            raw.c_cc[VMIN] = 1;
+            raw.c_cc[VTIME] = 0;
+            if unsafe { tcsetattr(0, TCSADRAIN, &raw) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(RawMode { original })
+        }
+    }
+
+    impl Drop for RawMode {
+        fn drop(&mut self) {
+            unsafe {
+                tcsetattr(0, TCSADRAIN, &self.original);
+            }
+        }
+    }
+}
+
+#[cfg(not(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64"))))]
+mod term {
+    use std::io;
+
+    pub fn is_terminal() -> bool {
+        false
+    }
+
+    pub struct RawMode;
+
+    impl RawMode {
+        pub fn enable() -> io::Result<RawMode> {
+            Err(io::Error::other("raw mode is only supported on linux for x86_64 and aarch64"))
+        }
+    }
+}
+
+/**
+ * * whether stdin and stdout are both a terminal the editor can drive
+ */
+pub fn is_terminal() -> bool {
+    term::is_terminal()
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Key {
+    Char(char),
+    Ctrl(char),
+    Meta(char),
+    Enter,
+    Tab,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Unknown,
+}
+
+fn next_byte(bytes: &mut dyn Iterator<Item = io::Result<u8>>) -> io::Result<Option<u8>> {
+    bytes.next().transpose()
+}
+
+/**
+ * * decode the next key pressed, None at the end of input
+ */
+fn read_key(bytes: &mut dyn Iterator<Item = io::Result<u8>>) -> io::Result<Option<Key>> {
+    let b = match next_byte(bytes)? {
+        Some(b) => b,
+        None => return Ok(None),
+    };
+    let key = match b {
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        0x7f | 0x08 => Key::Backspace,
+        0x1b => read_escape(bytes)?,
+        0x01..=0x1a => Key::Ctrl((b'a' + b - 1) as char),
+        0x00..=0x1f => Key::Unknown,
+        0x20..=0x7e => Key::Char(b as char),
+        _ => read_utf8(b, bytes)?,
+    };
+    Ok(Some(key))
+}
+
+/**
+ * * the keys sent as ESC sequences: arrows, home, end, delete and the meta keys
+ */
+fn read_escape(bytes: &mut dyn Iterator<Item = io::Result<u8>>) -> io::Result<Key> {
+    let key = match next_byte(bytes)? {
+        Some(b'[') => {
+            let mut param = 0;
+            loop {
+                match next_byte(bytes)? {
+                    Some(d @ b'0'..=b'9') => param = param * 10 + (d - b'0') as u32,
+                    Some(b';') => param = 0,
+                    Some(b'A') => break Key::Up,
+                    Some(b'B') => break Key::Down,
+                    Some(b'C') => break Key::Right,
+                    Some(b'D') => break Key::Left,
+                    Some(b'H') => break Key::Home,
+                    Some(b'F') => break Key::End,
+                    Some(b'~') => break match param {
+                        1 | 7 => Key::Home,
+                        4 | 8 => Key::End,
+                        3 => Key::Delete,
+                        _ => Key::Unknown,
+                    },
+                    _ => break Key::Unknown,
+                }
+            }
+        },
+        Some(b'O') => match next_byte(bytes)? {
+            Some(b'A') => Key::Up,
+            Some(b'B') => Key::Down,
+            Some(b'C') => Key::Right,
+            Some(b'D') => Key::Left,
+            Some(b'H') => Key::Home,
+            Some(b'F') => Key::End,
+            _ => Key::Unknown,
+        },
+        Some(0x7f) | Some(0x08) => Key::Meta('\u{7f}'),
+        Some(b) if b.is_ascii_graphic() => Key::Meta(b as char),
+        _ => Key::Unknown,
+    };
+    Ok(key)
+}
+
+fn read_utf8(first: u8, bytes: &mut dyn Iterator<Item = io::Result<u8>>) -> io::Result<Key> {
+    let len = match first {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => return Ok(Key::Unknown),
+    };
+    let mut buf = vec![first];
+    for _ in 1..len {
+        match next_byte(bytes)? {
+            Some(b) => buf.push(b),
+            None => break,
+        }
+    }
+    Ok(match std::str::from_utf8(&buf).ok().and_then(|s| s.chars().next()) {
+        Some(c) => Key::Char(c),
+        None => Key::Unknown,
+    })
+}
+
+// * what ends a word for the word motions and kills
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || "()[]\"';`,".contains(c)
+}
+
+/**
+ * * the line being edited and the cursor in it, counted in chars
+ * * the text of the last kill is kept for yanking it back
+ */
+#[derive(Default)]
+struct LineBuffer {
+    chars: Vec<char>,
+    cursor: usize,
+    killed: String,
+}
+
+impl LineBuffer {
+    fn text(&self) -> String {
+        self.chars.iter().collect()
+    }
+
+    fn before_cursor(&self) -> String {
+        self.chars[..self.cursor].iter().collect()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+
+    // * replace the whole line, the cursor goes to its end
+    fn set(&mut self, text: &str) {
+        self.chars = text.chars().collect();
+        self.cursor = self.chars.len();
+    }
+
+    fn insert(&mut self, c: char) {
+        self.chars.insert(self.cursor, c);
+        self.cursor += 1;
+    }
+
+    fn insert_str(&mut self, s: &str) {
+        s.chars().for_each(|c| self.insert(c));
+    }
+
+    fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.chars.remove(self.cursor);
+        }
+    }
+
+    fn delete(&mut self) {
+        if self.cursor < self.chars.len() {
+            self.chars.remove(self.cursor);
+        }
+    }
+
+    fn left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    fn right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.chars.len());
+    }
+
+    fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    fn end(&mut self) {
+        self.cursor = self.chars.len();
+    }
+
+    // * the start of the word before the cursor
+    fn word_start(&self) -> usize {
+        let mut i = self.cursor;
+        while i > 0 && is_delimiter(self.chars[i - 1]) {
+            i -= 1;
+        }
+        while i > 0 && !is_delimiter(self.chars[i - 1]) {
+            i -= 1;
+        }
+        i
+    }
+
+    // * the end of the word after the cursor
+    fn word_end(&self) -> usize {
+        let mut i = self.cursor;
+        while i < self.chars.len() && is_delimiter(self.chars[i]) {
+            i += 1;
+        }
+        while i < self.chars.len() && !is_delimiter(self.chars[i]) {
+            i += 1;
+        }
+        i
+    }
+
+    fn word_left(&mut self) {
+        self.cursor = self.word_start();
+    }
+
+    fn word_right(&mut self) {
+        self.cursor = self.word_end();
+    }
+
+    // * remove the chars from start to end into the kill buffer, the cursor goes to start
+    fn kill(&mut self, start: usize, end: usize) {
+        if start < end {
+            self.killed = self.chars.drain(start..end).collect();
+            self.cursor = start;
+        }
+    }
+
+    fn kill_end(&mut self) {
+        self.kill(self.cursor, self.chars.len());
+    }
+
+    fn kill_start(&mut self) {
+        self.kill(0, self.cursor);
+    }
+
+    fn kill_word_back(&mut self) {
+        self.kill(self.word_start(), self.cursor);
+    }
+
+    fn kill_word_forward(&mut self) {
+        let end = self.word_end();
+        self.kill(self.cursor, end);
+    }
+
+    fn yank(&mut self) {
+        let killed = self.killed.clone();
+        self.insert_str(&killed);
+    }
+
+    /**
+     * * swap the chars around the cursor and move past them, at the end of line the last two are swapped
+     */
+    fn transpose(&mut self) {
+        if self.chars.len() < 2 || self.cursor == 0 {
+            return;
+        }
+        let at = self.cursor.min(self.chars.len() - 1);
+        self.chars.swap(at - 1, at);
+        self.cursor = at + 1;
+    }
+}
+
+/**
+ * * the inputs entered so far, with the ones of earlier sessions read back from the history file
+ */
+pub struct History {
+    entries: Vec<String>,
+    path: Option<PathBuf>,
+}
+
+impl History {
+    /**
+     * * the history stored in the file at path, if there is one
+     * ! a file grown past twice the size kept is rewritten with only the latest entries
+     */
+    pub fn load(path: Option<PathBuf>) -> History {
+        let text = path.as_ref().and_then(|p| fs::read_to_string(p).ok()).unwrap_or_default();
+        let mut entries: Vec<String> = text.lines().filter(|l| !l.is_empty()).map(String::from).collect();
+        if entries.len() > HISTORY_SIZE {
+            let all = entries.len();
+            entries.drain(..all - HISTORY_SIZE);
+            if all > 2 * HISTORY_SIZE {
+                if let Some(p) = &path {
+                    let _ = fs::write(p, entries.iter().map(|e| format!("{}\n", e)).collect::<String>());
+                }
+            }
+        }
+        History { entries, path }
+    }
+
+    /**
+     * * remember an input, one entry is one line so the lines of a multi-line input are joined
+     * ! the history file is only a convenience, failing to write it is not reported
+     */
+    pub fn add(&mut self, entry: &str) {
+        let entry = entry.trim().lines().map(str::trim).collect::<Vec<_>>().join(" ");
+        if entry.is_empty() || self.entries.last() == Some(&entry) {
+            return;
+        }
+        if let Some(p) = &self.path {
+            if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(p) {
+                let _ = writeln!(file, "{}", entry);
+            }
+        }
+        self.entries.push(entry);
+        if self.entries.len() > HISTORY_SIZE {
+            self.entries.remove(0);
+        }
+    }
+
+    /**
+     * * the index of the latest entry before `before` containing query
+     */
+    fn search(&self, query: &str, before: usize) -> Option<usize> {
+        (0..before.min(self.entries.len())).rev().find(|&i| self.entries[i].contains(query))
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE))
+}
+
+/**
+ * * the completions of the text before the cursor: the char index where the completed word starts and the candidates
+ */
+pub type Completer<'a> = &'a dyn Fn(&str) -> (usize, Vec<String>);
+
+/**
+ * * a line editor for the terminal with emacs keys, history and tab completion
+ * * C-a C-e C-b C-f M-b M-f move, C-d C-h C-k C-u C-w M-d delete, C-y yanks the last kill back,
+ * * C-t transposes, C-p C-n go through the history, C-r searches it backwards and C-l clears the screen
+ * ! C-d on an empty line is the end of input, C-c abandons the input being read
+ */
+pub struct Editor {
+    history: History,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Editor {
+    pub fn new() -> Editor {
+        Editor {
+            history: History::load(history_path()),
+        }
+    }
+
+    /**
+     * * read one line on the terminal, None at the end of input
+     */
+    pub fn read_line(&mut self, prompt: &str, out: &mut dyn Write, complete: Completer) -> io::Result<Option<String>> {
+        let _raw = term::RawMode::enable()?;
+        let stdin = io::stdin();
+        let mut bytes = stdin.lock().bytes();
+        self.edit(&mut bytes, prompt, out, complete)
+    }
+
+    fn edit(&mut self, bytes: &mut dyn Iterator<Item = io::Result<u8>>, prompt: &str, out: &mut dyn Write, complete: Completer) -> io::Result<Option<String>> {
+        let mut line = LineBuffer::default();
+        // * the place in the history, the line being written is the one after the last entry
+        let mut index = self.history.entries.len();
+        let mut pending = String::new();
+        let mut next = None;
+
+        render(out, prompt, &line)?;
+        loop {
+            let key = match next.take() {
+                Some(key) => key,
+                None => match read_key(bytes)? {
+                    Some(key) => key,
+                    None => return Ok(None),
+                },
+            };
+            match key {
+                Key::Enter => {
+                    write!(out, "\r\n")?;
+                    return Ok(Some(line.text()));
+                },
+                Key::Ctrl('d') if line.is_empty() => {
+                    write!(out, "\r\n")?;
+                    return Ok(None);
+                },
+                Key::Ctrl('c') => {
+                    write!(out, "^C\r\n")?;
+                    return Err(io::Error::new(io::ErrorKind::Interrupted, "input abandoned"));
+                },
+                Key::Char(c) => line.insert(c),
+                Key::Backspace => line.backspace(),
+                Key::Delete | Key::Ctrl('d') => line.delete(),
+                Key::Left | Key::Ctrl('b') => line.left(),
+                Key::Right | Key::Ctrl('f') => line.right(),
+                Key::Home | Key::Ctrl('a') => line.home(),
+                Key::End | Key::Ctrl('e') => line.end(),
+                Key::Meta('b') => line.word_left(),
+                Key::Meta('f') => line.word_right(),
+                Key::Ctrl('k') => line.kill_end(),
+                Key::Ctrl('u') => line.kill_start(),
+                Key::Ctrl('w') | Key::Meta('\u{7f}') => line.kill_word_back(),
+                Key::Meta('d') => line.kill_word_forward(),
+                Key::Ctrl('y') => line.yank(),
+                Key::Ctrl('t') => line.transpose(),
+                Key::Ctrl('l') => write!(out, "\x1b[H\x1b[2J")?,
+                Key::Up | Key::Ctrl('p') if index > 0 => {
+                    if index == self.history.entries.len() {
+                        pending = line.text();
+                    }
+                    index -= 1;
+                    line.set(&self.history.entries[index]);
+                },
+                Key::Down | Key::Ctrl('n') if index < self.history.entries.len() => {
+                    index += 1;
+                    match self.history.entries.get(index) {
+                        Some(entry) => line.set(entry),
+                        None => line.set(&pending),
+                    }
+                },
+                Key::Ctrl('r') => {
+                    let (found, key) = self.reverse_search(bytes, out)?;
+                    if let Some(text) = found {
+                        line.set(&text);
+                    }
+                    next = key;
+                },
+                Key::Tab => complete_word(out, &mut line, complete)?,
+                _ => {},
+            }
+            render(out, prompt, &line)?;
+        }
+    }
+
+    /**
+     * * search the history for the text typed, C-r again goes to an older match
+     * * returns the match accepted, None when the search is cancelled with C-g,
+     * * and the key which ended the search so the editor can act on it
+     */
+    fn reverse_search(&self, bytes: &mut dyn Iterator<Item = io::Result<u8>>, out: &mut dyn Write) -> io::Result<(Option<String>, Option<Key>)> {
+        let mut query = String::new();
+        let mut found: Option<usize> = None;
+        let latest = self.history.entries.len();
+
+        loop {
+            let matched = found.map_or("", |i| self.history.entries[i].as_str());
+            write!(out, "\r(reverse-i-search)`{}': {}\x1b[K", query, matched)?;
+            out.flush()?;
+
+            let key = match read_key(bytes)? {
+                Some(key) => key,
+                None => return Ok((None, None)),
+            };
+            match key {
+                Key::Char(c) => {
+                    query.push(c);
+                    found = self.history.search(&query, found.map_or(latest, |i| i + 1));
+                },
+                Key::Backspace => {
+                    query.pop();
+                    found = match query.is_empty() {
+                        true => None,
+                        false => self.history.search(&query, latest),
+                    };
+                },
+                Key::Ctrl('r') => {
+                    if let Some(i) = found {
+                        found = self.history.search(&query, i).or(found);
+                    }
+                },
+                Key::Ctrl('g') => return Ok((None, None)),
+                key => return Ok((found.map(|i| self.history.entries[i].clone()), Some(key))),
+            }
+        }
+    }
+}
+
+/**
+ * * redraw the prompt and the line, and put the cursor back in place
+ */
+fn render(out: &mut dyn Write, prompt: &str, line: &LineBuffer) -> io::Result<()> {
+    write!(out, "\r{}{}\x1b[K\r", prompt, line.text())?;
+    let column = prompt.chars().count() + line.cursor;
+    if column > 0 {
+        write!(out, "\x1b[{}C", column)?;
+    }
+    out.flush()
+}
+
+/**
+ * * complete the word before the cursor as far as all the candidates agree, listing them when that is no further
+ */
+fn complete_word(out: &mut dyn Write, line: &mut LineBuffer, complete: Completer) -> io::Result<()> {
+    let before = line.before_cursor();
+    let (start, candidates) = complete(&before);
+    let typed = before.chars().count().saturating_sub(start);
+
+    let common = match candidates.split_first() {
+        Some((first, rest)) => rest.iter().fold(first.clone(), |acc, c| {
+            acc.chars().zip(c.chars()).take_while(|(a, b)| a == b).map(|(a, _)| a).collect()
+        }),
+        None => return write!(out, "\x07"),
+    };
+
+    let rest: String = common.chars().skip(typed).collect();
+    if !rest.is_empty() {
+        line.insert_str(&rest);
+    } else if candidates.len() > 1 {
+        write!(out, "\r\n{}\r\n", candidates.join("  "))?;
+    }
+    Ok(())
+}
+
+impl Input for Editor {
+    fn read_line(&mut self, prompt: &str, out: &mut dyn Write, buf: &mut String, complete: Completer) -> io::Result<usize> {
+        match Editor::read_line(self, prompt, out, complete)? {
+            Some(line) => {
+                buf.push_str(&line);
+                buf.push('\n');
+                Ok(line.len() + 1)
+            },
+            None => Ok(0),
+        }
+    }
+
+    fn add_history(&mut self, entry: &str) {
+        self.history.add(entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn keys(input: &[u8]) -> Vec<Key> {
+        let mut bytes = input.iter().map(|b| Ok(*b));
+        let mut keys = Vec::new();
+        while let Some(key) = read_key(&mut bytes).unwrap() {
+            keys.push(key);
+        }
+        keys
+    }
+
+    fn editor(entries: &[&str]) -> Editor {
+        Editor {
+            history: History {
+                entries: entries.iter().map(|e| e.to_string()).collect(),
+                path: None,
+            },
+        }
+    }
+
+    fn no_completion(_: &str) -> (usize, Vec<String>) {
+        (0, Vec::new())
+    }
+
+    fn edit_with(editor: &mut Editor, input: &str, complete: Completer) -> Option<String> {
+        let mut bytes = input.bytes().map(Ok);
+        editor.edit(&mut bytes, "> ", &mut Vec::new(), complete).unwrap()
+    }
+
+    #[test]
+    fn editor_keys() {
+        assert_eq!(keys(b"a\x01\r\t\x7f"), vec![Key::Char('a'), Key::Ctrl('a'), Key::Enter, Key::Tab, Key::Backspace]);
+        assert_eq!(keys(b"\x1b[A\x1b[D\x1bOH\x1b[3~\x1b[4~"), vec![Key::Up, Key::Left, Key::Home, Key::Delete, Key::End]);
+        assert_eq!(keys(b"\x1bb\x1b\x7f"), vec![Key::Meta('b'), Key::Meta('\u{7f}')]);
+        assert_eq!(keys("λ→".as_bytes()), vec![Key::Char('λ'), Key::Char('→')]);
+    }
+
+    #[test]
+    fn editor_line_editing() {
+        let mut line = LineBuffer::default();
+        line.insert_str("(car lst)");
+        line.word_left();
+        line.kill_end();
+        assert_eq!((line.text(), line.cursor), ("(car ".to_string(), 5));
+        line.insert_str("xs)");
+        line.home();
+        line.right();
+        line.kill_word_forward();
+        line.insert_str("cdr");
+        assert_eq!(line.text(), "(cdr xs)");
+        line.end();
+        line.kill_word_back();
+        line.yank();
+        line.yank();
+        assert_eq!(line.text(), "(cdr xs)xs)");
+        line.set("ab");
+        line.transpose();
+        line.backspace();
+        line.left();
+        line.delete();
+        assert!(line.is_empty());
+    }
+
+    #[test]
+    fn editor_edit_session() {
+        let mut e = editor(&["(define x 1)", "(+ x 2)"]);
+        assert_eq!(edit_with(&mut e, "(car 1)\x1b[D\x7f2\r", &no_completion), Some("(car 2)".to_string()));
+        assert_eq!(edit_with(&mut e, "\x02\x01x\x05y\r", &no_completion), Some("xy".to_string()));
+        assert_eq!(edit_with(&mut e, "\x10\x10\x10\x0e\r", &no_completion), Some("(+ x 2)".to_string()));
+        assert_eq!(edit_with(&mut e, "abc\x10\x0e\r", &no_completion), Some("abc".to_string()));
+        assert_eq!(edit_with(&mut e, "a\x04\x04\x7f\x04", &no_completion), None);
+        assert_eq!(edit_with(&mut e, "abc", &no_completion), None);
+        let mut bytes = "ab\x03".bytes().map(Ok);
+        let err = e.edit(&mut bytes, "> ", &mut Vec::new(), &no_completion).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+    }
+
+    #[test]
+    fn editor_reverse_search() {
+        let mut e = editor(&["(define x 1)", "(display x)", "(define y 2)", "(+ x y)"]);
+        assert_eq!(edit_with(&mut e, "\x12def\r", &no_completion), Some("(define y 2)".to_string()));
+        assert_eq!(edit_with(&mut e, "\x12def\x12\r", &no_completion), Some("(define x 1)".to_string()));
+        assert_eq!(edit_with(&mut e, "\x12dis\x05 1\r", &no_completion), Some("(display x) 1".to_string()));
+        assert_eq!(edit_with(&mut e, "old\x12dis\x07\r", &no_completion), Some("old".to_string()));
+        assert_eq!(edit_with(&mut e, "\x12zzz\r", &no_completion), Some("".to_string()));
+    }
+
+    #[test]
+    fn editor_completion() {
+        let names = ["car", "cdr", "cons", "call/cc"];
+        let complete = |before: &str| {
+            let start = before.rfind(is_delimiter).map_or(0, |i| i + 1);
+            let word = &before[start..];
+            (start, names.iter().filter(|n| n.starts_with(word)).map(|n| n.to_string()).collect())
+        };
+        let mut e = editor(&[]);
+        assert_eq!(edit_with(&mut e, "(co\t 1)\r", &complete), Some("(cons 1)".to_string()));
+        assert_eq!(edit_with(&mut e, "(ca\tl\t\r", &complete), Some("(call/cc".to_string()));
+        assert_eq!(edit_with(&mut e, "(c\t\r", &complete), Some("(c".to_string()));
+        assert_eq!(edit_with(&mut e, "(x\t\r", &complete), Some("(x".to_string()));
+    }
+
+    #[test]
+    fn editor_history_file() {
+        let path = env::temp_dir().join(format!("sch_rs_history_{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut history = History::load(Some(path.clone()));
+        history.add("(define x\n  1)\n");
+        history.add("(define x 1)");
+        history.add("   ");
+        history.add("x");
+        assert_eq!(History::load(Some(path.clone())).entries, vec!["(define x 1)", "x"]);
+        assert_eq!(history.search("x", 2), Some(1));
+        assert_eq!(history.search("define", 1), Some(0));
+        assert_eq!(history.search("y", 2), None);
+        fs::remove_file(&path).unwrap();
+    }
+}
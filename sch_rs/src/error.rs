@@ -0,0 +1,156 @@
+use crate::interpreter::{eval::RuntimeError, lex::SyntaxError, parser::ParseError};
+use std::{fmt, io};
+
+/**
+ * * every way running a program can fail, from reading the file to evaluating it
+ * * each stage returns its own error, which `?` turns into this one
+ */
+pub enum Error {
+    Syntax(SyntaxError),
+    Parse(ParseError),
+    Runtime(RuntimeError),
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Syntax(e) => write!(f, "{}", e),
+            Error::Parse(e) => write!(f, "{}", e),
+            Error::Runtime(e) => write!(f, "{}", e),
+            Error::Io(e) => write!(f, "I/O Error: {}", e),
+        }
+    }
+}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Syntax(e) => Some(e),
+            Error::Parse(e) => Some(e),
+            Error::Runtime(e) => Some(e),
+            Error::Io(e) => Some(e),
+        }
+    }
+}
+
+impl From<SyntaxError> for Error {
+    fn from(e: SyntaxError) -> Error {
+        Error::Syntax(e)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Error {
+        Error::Parse(e)
+    }
+}
+
+impl From<RuntimeError> for Error {
+    fn from(e: RuntimeError) -> Error {
+        Error::Runtime(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::interpreter::eval::{Evalator, Value};
+
+    fn run(input: &str) -> Result<String, Error> {
+        Ok(format!("{:?}", Evalator::new().eval_source("test.sch", input)?))
+    }
+
+    #[test]
+    fn error_kinds() {
+        assert!(matches!(run("\"open"), Err(Error::Syntax(_))));
+        assert!(matches!(run("(a b"), Err(Error::Parse(_))));
+        assert!(matches!(run("(car 1)"), Err(Error::Runtime(_))));
+        let e: Error = io::Error::new(io::ErrorKind::NotFound, "no such file").into();
+        assert_eq!(format!("{}", e), "I/O Error: no such file");
+        assert!(std::error::Error::source(&e).is_some());
+    }
+
+    #[test]
+    fn error_malformed_programs_do_not_panic() {
+        let programs = [
+            "(", ")", "'", "`", ",", ",@", "(.)", "(. 1)", "(1 .)", "(1 . 2 3)", "#(1 . 2)", "#", "#\\", "#q", "\"\\q\"", "1/0", "12abc",
+            "()", "(1 2)", "(\"f\")", "(let x 1)", "(let)", "(let ((x)) x)", "(let ((1 2)) 1)", "(let (x) x)",
+            "(lambda)", "(lambda x)", "(lambda (1) 1)", "((lambda (x) x))", "((lambda (x) x) 1 2)",
+            "(define)", "(define x)", "(define 1 2)", "(define (1) 2)", "(define (f 1) 2)", "(if)", "(if 1)", "(if 1 2 3 4)",
+            "(set!)", "(set! x)", "(set! 1 2)", "(set! undefined 1)", "(quote)", "(quote 1 2)", "(quasiquote)", "`(,@1)", "`(1 ,@2 3)",
+            "(unquote 1)", ",x", "(define-syntax)", "(define-syntax foo 1)", "(define-syntax foo (syntax-rules))", "(define-syntax foo (syntax-rules () (1 2)))",
+            "(define-syntax foo (syntax-rules () ((_ x ...) (x)))) (foo 1 2)", "(define-syntax foo (syntax-rules () ((_ x) (x ...)))) (foo 1)",
+            "(let-syntax)", "(let-syntax ((a 1)) a)", "(syntax-rules)", "else", "(else)", "(=> 1)",
+            "(car)", "(car 1 2)", "(cdr '())", "(cons 1)", "(set-car! 1 2)", "(length '(1 . 2))", "(list-tail '(1) 5)", "(list-tail '(1) -1)",
+            "(vector-ref)", "(vector-ref #(1) 1)", "(vector-ref #(1) 'a)", "(make-vector -1)", "(make-vector 'a)", "(vector-fill! #(1) 0 2 1)", "(vector-map)",
+            "(string-ref \"\" 0)", "(substring \"abc\" 2 1)", "(make-string -1)", "(string-append 1)", "(list->string '(1))", "(string->list \"abc\" 4)",
+            "(char->integer 1)", "(integer->char -1)", "(integer->char 55296)",
+            "(+ 'a 1)", "(/ 1 0)", "(/ 0)", "(/ 1.5 0)", "(exact 1e400)", "(exact (/ 0. 0.))", "(- )", "(* 'a)",
+            "(apply)", "(apply +)", "(apply + 1)", "(apply 1 '())", "(eval)", "(eval '(car 1))",
+            "(call/cc)", "(call/cc 1)", "(call/cc (lambda () 1))", "((call/cc (lambda (k) k)) 1 2)", "(dynamic-wind 1 2 3)", "(dynamic-wind (lambda () 1) (lambda (x) x) (lambda () 1))",
+            "(raise)", "(raise 1)", "(error)", "(error 1)", "(error-object-message 1)", "(with-exception-handler 1 2)", "(with-exception-handler (lambda () 1) (lambda () (raise 1)))",
+            "(guard)", "(guard 1)", "(guard (e))", "(guard (1) 1)", "(guard (e (1 => )) (raise 1))", "(guard (e ()) (raise 1))", "(guard (e (else)) (raise 1))",
+        ];
+        for program in programs.iter() {
+            // * either outcome is fine, only a panic fails the test
+            let _ = run(program);
+        }
+    }
+
+    #[test]
+    fn error_large_data_does_not_overflow() {
+        // * long lists are dropped, compared and printed without recursing down their spine
+        let items: Vec<Value> = (0..200000).map(Value::from).collect();
+        assert!(Value::list(items.clone()) == Value::list(items));
+        assert_eq!(run("(length (vector->list (make-vector 200000 'x)))").ok(), Some("200000".to_string()));
+        assert!(run("(define xs (vector->list (make-vector 200000 'x))) (set! xs 0) xs").is_ok());
+
+        // * data nested through the car is printed, compared and dropped without recursing either
+        let mut evalator = Evalator::new();
+        let nested = evalator.eval_source("test.sch", "(define (nest n acc) (if (= n 0) acc (nest (- n 1) (list acc)))) (define a (nest 100000 \"x\")) a").unwrap();
+        assert!(format!("{:?}", nested) == format!("{}\"x\"{}", "(".repeat(100000), ")".repeat(100000)));
+        assert!(nested.to_string() == format!("{}x{}", "(".repeat(100000), ")".repeat(100000)));
+        let compared = evalator.eval_source("test.sch", "(list (equal? a (nest 100000 \"x\")) (equal? a (nest 100000 \"y\")) (equal? (vector a) (vector (nest 99999 '(\"x\")))))");
+        assert_eq!(format!("{:?}", compared.unwrap()), "(#true #false #true)");
+
+        // * vectors nested in vectors as well
+        let nested = evalator.eval_source("test.sch", "(define (nest-vector n acc) (if (= n 0) acc (nest-vector (- n 1) (vector acc)))) (define v (nest-vector 100000 1)) v").unwrap();
+        assert!(nested.to_string() == format!("{}1{}", "#(".repeat(100000), ")".repeat(100000)));
+        let compared = evalator.eval_source("test.sch", "(list (equal? v (nest-vector 100000 1)) (equal? v (nest-vector 100000 2)))");
+        assert_eq!(format!("{:?}", compared.unwrap()), "(#true #false)");
+        drop(nested);
+        drop(evalator);
+
+        // * nesting is limited by the parser, everything up to the limit goes through every pass
+        let nested = format!("{}1{}", "(+ 1 ".repeat(190), ")".repeat(190));
+        assert_eq!(run(&nested).ok(), Some("191".to_string()));
+        let quoted = format!("'{}{}", "(".repeat(190), ")".repeat(190));
+        assert!(run(&quoted).is_ok());
+        assert!(matches!(run(&"(".repeat(100000)), Err(Error::Parse(_))));
+        // * data built at run time has no reader, eval checks its nesting instead
+        let mut evalator = Evalator::new();
+        evalator.eval_source("test.sch", "(define (nest n f acc) (if (= n 0) acc (nest (- n 1) f (f acc))))").unwrap();
+        for program in ["(eval (list 'quote (nest 100000 list 1)))", "(eval (nest 100000 (lambda (acc) (list 'car acc)) ''(1)))"] {
+            let err = evalator.eval_source("test.sch", program).unwrap_err().to_string();
+            assert!(err.contains("nesting deeper than"), "{}", err);
+        }
+        let long = "(define xs (vector->list (make-vector 200000 1))) (list (length (eval (list 'quote xs))) (length (eval (list 'quasiquote xs))) (length (eval (cons 'list xs))))";
+        assert_eq!(format!("{:?}", evalator.eval_source("test.sch", long).unwrap()), "(200000 200000 200000)");
+        let circular = "(define xs (list 1 2)) (set-cdr! (cdr xs) xs) (eval (list 'quasiquote xs))";
+        assert!(evalator.eval_source("test.sch", circular).is_err());
+    }
+}
@@ -0,0 +1,499 @@
+use super::eval::{self, Params, RuntimeError, Value};
+use super::expand::Expander;
+use super::gc::Marker;
+use super::list::list_arg;
+use super::span::Span;
+use super::symbol::Symbol;
+use std::rc::Rc;
+
+/**
+ * * an expanded form resolved for the tree-walker, the keywords are dispatched and the variables looked up once
+ * ! a local variable is addressed by (depth, slot) like on the vm: the frame depth levels out and the slot in it
+ */
+pub enum Code {
+    Const(Value),
+    Variable(Variable),
+    // * (if test then else)
+    If(Rc<Code>, Rc<Code>, Rc<Code>),
+    Define(Variable, Rc<Code>),
+    Set(Variable, Rc<Code>),
+    Lambda(Rc<Lambda>),
+    Let(Rc<Let>),
+    Guard(Rc<Guard>),
+    // * the operator followed by the operands
+    Combination(Body),
+    // * a form read from the source, errors while it is evaluated are reported there
+    Located(Rc<Span>, Rc<Code>),
+    // * a malformed form fails only once it is evaluated, like before it was resolved
+    Malformed(String),
+}
+
+/**
+ * * a sequence of code, the last one is evaluated in tail position
+ */
+pub type Body = Rc<[Rc<Code>]>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Variable {
+    Local(usize, usize),
+    Global(Symbol),
+}
+
+/**
+ * * a lambda resolved in the frames around it
+ */
+pub struct Lambda {
+    pub(super) params: Params,
+    // * the slots of the frame a call makes: the parameters, then the internal definitions
+    pub(super) names: Rc<Vec<Symbol>>,
+    pub(super) body: Body,
+    // * where the body was read
+    source: Option<Span>,
+}
+
+impl Lambda {
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    pub fn source(&self) -> Option<&Span> {
+        self.source.as_ref()
+    }
+}
+
+/**
+ * * (let ((name init) ...) body...) the slots of its frame are the names, then the definitions of the body
+ */
+pub struct Let {
+    pub(super) inits: Vec<Rc<Code>>,
+    pub(super) names: Rc<Vec<Symbol>>,
+    pub(super) body: Body,
+}
+
+/**
+ * * (guard (var clause ...) body...) the clauses run in a frame holding the condition,
+ * * the body in a frame of its definitions
+ */
+pub struct Guard {
+    pub(super) var: Rc<Vec<Symbol>>,
+    pub(super) clauses: Vec<Clause>,
+    pub(super) names: Rc<Vec<Symbol>>,
+    pub(super) body: Body,
+}
+
+pub enum Clause {
+    // * (test expr ...), without expressions the value of the test is the value of the guard
+    Test(Rc<Code>, Body),
+    // * (test => receiver)
+    Arrow(Rc<Code>, Rc<Code>),
+    Else(Body),
+}
+
+impl Code {
+    pub(super) fn trace(&self, m: &mut Marker) {
+        match self {
+            Code::Const(v) => m.value(v),
+            Code::Variable(_) | Code::Malformed(_) => {},
+            Code::If(test, then, otherwise) => {
+                m.code(test);
+                m.code(then);
+                m.code(otherwise);
+            },
+            Code::Define(_, expr) | Code::Set(_, expr) | Code::Located(_, expr) => m.code(expr),
+            Code::Lambda(lambda) => m.body(&lambda.body),
+            Code::Let(l) => l.trace(m),
+            Code::Guard(g) => g.trace(m),
+            Code::Combination(items) => m.body(items),
+        }
+    }
+}
+
+impl Let {
+    pub(super) fn trace(&self, m: &mut Marker) {
+        for init in &self.inits {
+            m.code(init);
+        }
+        m.body(&self.body);
+    }
+}
+
+impl Guard {
+    pub(super) fn trace(&self, m: &mut Marker) {
+        for clause in &self.clauses {
+            match clause {
+                Clause::Test(test, body) => {
+                    m.code(test);
+                    m.body(body);
+                },
+                Clause::Arrow(test, receiver) => {
+                    m.code(test);
+                    m.code(receiver);
+                },
+                Clause::Else(body) => m.body(body),
+            }
+        }
+        m.body(&self.body);
+    }
+}
+
+/**
+ * * resolve an expanded toplevel form, forms without expander like those of eval in a bare machine work as well
+ */
+pub fn address(form: &Value) -> Rc<Code> {
+    Addresser { scopes: Vec::new() }.expr(form)
+}
+
+// * the names (define name expr) and (define (name params...) body...) bind in the frame of a body
+pub(super) fn body_defines(body: &[Value]) -> Vec<Symbol> {
+    body.iter().filter_map(|form| match form.to_vec()?.as_slice() {
+        [Value::Symbol(define), Value::Symbol(name), _, ..] if define == "define" => Some(*name),
+        [Value::Symbol(define), Value::Pair(sig), _, ..] if define == "define" => match sig.car() {
+            Value::Symbol(name) => Some(name),
+            _ => None,
+        },
+        _ => None,
+    }).collect()
+}
+
+// * the parameters or let variables of one frame, a name bound twice is an error
+fn check_distinct(names: &[Symbol]) -> Result<(), RuntimeError> {
+    for (i, name) in names.iter().enumerate() {
+        if names[..i].contains(name) {
+            runtime_error!("The identifier is already defined!: {:?}", (Expander::source_name(&name.to_string())));
+        }
+    }
+    Ok(())
+}
+
+/**
+ * * special forms receive their operands unresolved, they are recognized by their keyword
+ * * before the operator would be resolved, so they can neither be shadowed nor passed around as values
+ */
+type SpecialForm = fn(&mut Addresser, &[Value]) -> Result<Rc<Code>, RuntimeError>;
+
+/**
+ * * the special form table, every keyword here has its own evaluation rule
+ */
+const SPECIAL_FORMS: &[(&str, SpecialForm)] = &[
+    ("define", special_define),
+    ("let", special_let),
+    ("lambda", special_lambda),
+    ("if", special_if),
+    ("set!", special_set),
+    ("quote", special_quote),
+    ("quasiquote", special_quasiquote),
+    ("unquote", special_unquote),
+    ("unquote-splicing", special_unquote),
+    ("guard", special_guard),
+];
+
+fn special_form(name: &str) -> Option<SpecialForm> {
+    SPECIAL_FORMS.iter().find(|(n, _)| *n == name).map(|(_, form)| *form)
+}
+
+pub(crate) fn special_form_names() -> impl Iterator<Item = &'static str> {
+    SPECIAL_FORMS.iter().map(|(n, _)| *n)
+}
+
+struct Addresser {
+    // * the slot names of the frames around the code, the innermost last
+    scopes: Vec<Rc<Vec<Symbol>>>,
+}
+
+impl Addresser {
+    fn resolve(&self, name: Symbol) -> Variable {
+        let local = self.scopes.iter().rev().enumerate().find_map(|(depth, names)| {
+            names.iter().position(|n| *n == name).map(|slot| (depth, slot))
+        });
+        match local {
+            Some((depth, slot)) => Variable::Local(depth, slot),
+            None => Variable::Global(name),
+        }
+    }
+
+    fn expr(&mut self, expr: &Value) -> Rc<Code> {
+        match expr {
+            Value::Symbol(name) => Rc::new(Code::Variable(self.resolve(*name))),
+            Value::Nil => Rc::new(Code::Malformed("can not evaluate an empty combination".to_string())),
+            Value::Pair(_) => {
+                let code = self.form(expr).unwrap_or_else(|e| Rc::new(Code::Malformed(e.message().to_string())));
+                match expr.span() {
+                    Some(span) => Rc::new(Code::Located(span, code)),
+                    None => code,
+                }
+            },
+            _ => Rc::new(Code::Const(expr.clone())),
+        }
+    }
+
+    fn form(&mut self, form: &Value) -> Result<Rc<Code>, RuntimeError> {
+        let items = match form.to_vec() {
+            Some(items) => items,
+            None => runtime_error!("can not evaluate an improper list: {:?}", form),
+        };
+
+        if let Value::Symbol(s) = &items[0] {
+            if let Some(special) = special_form(s) {
+                return special(self, &items[1..]);
+            }
+        }
+        Ok(Rc::new(Code::Combination(self.body(&items))))
+    }
+
+    fn body(&mut self, forms: &[Value]) -> Body {
+        forms.iter().map(|form| self.expr(form)).collect()
+    }
+
+    /**
+     * * resolve a body in a frame with the given slots, a frame without slots is never made
+     */
+    fn frame(&mut self, names: &Rc<Vec<Symbol>>, forms: &[Value]) -> Body {
+        if names.is_empty() {
+            return self.body(forms);
+        }
+        self.scopes.push(names.clone());
+        let body = self.body(forms);
+        self.scopes.pop();
+        body
+    }
+
+    fn lambda(&mut self, params: Params, forms: &[Value]) -> Result<Rc<Lambda>, RuntimeError> {
+        let mut names: Vec<Symbol> = params.names().collect();
+        check_distinct(&names)?;
+        names.extend(body_defines(forms));
+        let names = Rc::new(names);
+        let body = self.frame(&names, forms);
+
+        let spans: Vec<_> = forms.iter().filter_map(Value::span).collect();
+        let source = match (spans.first(), spans.last()) {
+            (Some(first), Some(last)) => Some(first.to(last)),
+            _ => None,
+        };
+        Ok(Rc::new(Lambda { params, names, body, source }))
+    }
+
+    /**
+     * * a definition in a body has a slot in the frame of the body, one at the top level is global
+     */
+    fn definition(&self, name: Symbol) -> Result<Variable, RuntimeError> {
+        match self.scopes.last() {
+            None => Ok(Variable::Global(name)),
+            Some(names) => match names.iter().position(|n| *n == name) {
+                Some(slot) => Ok(Variable::Local(0, slot)),
+                None => runtime_error!("bad syntax: define is only allowed at the top level or at the beginning of a body"),
+            },
+        }
+    }
+
+    fn clause(&mut self, clause: &Value) -> Result<Clause, RuntimeError> {
+        match list_arg("guard clause", clause)?.as_slice() {
+            [Value::Symbol(s), body @ ..] if s == "else" => Ok(Clause::Else(self.body(body))),
+            [test, Value::Symbol(arrow), receiver] if arrow == "=>" => Ok(Clause::Arrow(self.expr(test), self.expr(receiver))),
+            [test, body @ ..] => Ok(Clause::Test(self.expr(test), self.body(body))),
+            [] => runtime_error!("guard expects clauses of the form (test expr ...) but got ()"),
+        }
+    }
+}
+
+/**
+ * * (if pred v1 v2)
+ * * both branches are in tail position
+ */
+fn special_if(a: &mut Addresser, args: &[Value]) -> Result<Rc<Code>, RuntimeError> {
+    match args {
+        [test, then, otherwise] => Ok(Rc::new(Code::If(a.expr(test), a.expr(then), a.expr(otherwise)))),
+        _ => runtime_error!("expect 1 predicate and 2 branches but got: {:?}", args)
+    }
+}
+
+/**
+ * * (set! name value) assign to the innermost existing binding of name
+ */
+fn special_set(a: &mut Addresser, args: &[Value]) -> Result<Rc<Code>, RuntimeError> {
+    match args {
+        [Value::Symbol(name), expr] => Ok(Rc::new(Code::Set(a.resolve(*name), a.expr(expr)))),
+        _ => runtime_error!("set! expects a variable and a value but got: {:?}", args),
+    }
+}
+
+/**
+ * * (quote datum) produce the datum without evaluating it
+ */
+fn special_quote(_a: &mut Addresser, args: &[Value]) -> Result<Rc<Code>, RuntimeError> {
+    match args {
+        [datum] => Ok(Rc::new(Code::Const(datum.clone()))),
+        _ => runtime_error!("quote expects exactly one datum but got: {:?}", args),
+    }
+}
+
+/**
+ * * (quasiquote template) build the template, evaluating the unquoted parts
+ * * the template is rewritten into calls of the list natives once, when the form is resolved
+ */
+fn special_quasiquote(a: &mut Addresser, args: &[Value]) -> Result<Rc<Code>, RuntimeError> {
+    match args {
+        [template] => Ok(a.expr(&eval::quasiquote(template)?)),
+        _ => runtime_error!("quasiquote expects exactly one template but got: {:?}", args),
+    }
+}
+
+fn special_unquote(_a: &mut Addresser, args: &[Value]) -> Result<Rc<Code>, RuntimeError> {
+    runtime_error!("unquote outside of quasiquote: {:?}", args)
+}
+
+/**
+ * * (guard (var clause ...) body ...)
+ * * evaluate body, a condition raised in it is bound to var and tried against the clauses
+ * * like cond: (test expr ...), (test => receiver) or (else expr ...)
+ */
+fn special_guard(a: &mut Addresser, args: &[Value]) -> Result<Rc<Code>, RuntimeError> {
+    let (var, clauses) = match args.first().and_then(Value::to_vec) {
+        Some(spec) => match spec.split_first() {
+            Some((Value::Symbol(var), clauses)) => (*var, clauses.to_vec()),
+            _ => runtime_error!("guard expects (variable clause ...) but got: {:?}", spec),
+        },
+        None => runtime_error!("guard expects (variable clause ...) and a body but got: {:?}", args),
+    };
+
+    let var = Rc::new(vec![var]);
+    a.scopes.push(var.clone());
+    let clauses = clauses.iter().map(|clause| a.clause(clause)).collect::<Result<Vec<Clause>, RuntimeError>>();
+    a.scopes.pop();
+
+    let names = Rc::new(body_defines(&args[1..]));
+    let body = a.frame(&names, &args[1..]);
+    Ok(Rc::new(Code::Guard(Rc::new(Guard { var, clauses: clauses?, names, body }))))
+}
+
+/**
+ * * (lambda (xs ...) body) produce a procedure, (lambda (xs ... . rest) body) and (lambda args body) take any more arguments
+ */
+fn special_lambda(a: &mut Addresser, args: &[Value]) -> Result<Rc<Code>, RuntimeError> {
+    let params = match args.first() {
+        Some(spec) => Params::parse(spec)?,
+        _ => runtime_error!("Must provide parameter lists in function parameter: {:?}", args),
+    };
+    Ok(Rc::new(Code::Lambda(a.lambda(params, &args[1..])?)))
+}
+
+/**
+ * * (let ([n1 v1] ...) body)
+ */
+fn special_let(a: &mut Addresser, args: &[Value]) -> Result<Rc<Code>, RuntimeError> {
+    let mut names = Vec::new();
+    let mut inits = Vec::new();
+    match args.first().and_then(Value::to_vec) {
+        Some(assigns) => {
+            for assign in assigns {
+                match assign.to_vec() {
+                    Some(nv_pair) if nv_pair.len() == 2 => {
+                        match &nv_pair[0] {
+                            Value::Symbol(ref s) => {
+                                names.push(*s);
+                                inits.push(a.expr(&nv_pair[1]));
+                            },
+                            _ => runtime_error!("invalid let syntax: {:?}", nv_pair),
+                        }
+                    },
+                    _ => runtime_error!("invalid let define list: {:?}", assign),
+                }
+            }
+        },
+        _ => runtime_error!("let-define requires but got: {:?}", args),
+    };
+
+    check_distinct(&names)?;
+    names.extend(body_defines(&args[1..]));
+    let names = Rc::new(names);
+    let body = a.frame(&names, &args[1..]);
+    Ok(Rc::new(Code::Let(Rc::new(Let { inits, names, body }))))
+}
+
+/*
+ * * (define name value)\(define (p_name params) body)
+ * args must be a vec with length greater than 2
+ */
+fn special_define(a: &mut Addresser, args: &[Value]) -> Result<Rc<Code>, RuntimeError> {
+    if args.len() < 2 {
+        runtime_error!("invalid define: {:?}", args);
+    }
+
+    match &args[0] {
+        Value::Symbol(n) => Ok(Rc::new(Code::Define(a.definition(*n)?, a.expr(&args[1])))),
+        Value::Pair(p) => match p.car() {
+            Value::Symbol(n) => {
+                let params = Params::parse(&p.cdr())?;
+                let lambda = a.lambda(params, &args[1..])?;
+                Ok(Rc::new(Code::Define(a.definition(n)?, Rc::new(Code::Lambda(lambda)))))
+            },
+            _ => runtime_error!("must supply a symbol as define name: {:?}", (args[0])),
+        },
+        _ => runtime_error!("invalid define: {:?}", args),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::interpreter::{eval::Evalator, lex::lexer, parser::Parser};
+
+    // * the last form of input, resolved after expansion
+    fn address_str(input: &str) -> Rc<Code> {
+        let nodes = Parser::parse(&lexer::lex(input).unwrap()).unwrap();
+        address(Evalator::new().expand(&nodes).unwrap().last().unwrap())
+    }
+
+    // * the code under the location of a form
+    fn unlocated(code: &Rc<Code>) -> &Rc<Code> {
+        match &**code {
+            Code::Located(_, inner) => inner,
+            _ => code,
+        }
+    }
+
+    #[test]
+    fn address_locals() {
+        let code = address_str("(lambda (x) (let ((y x)) (define z y) (lambda () (set! x z) car)))");
+        let outer = match &**unlocated(&code) {
+            Code::Lambda(outer) => outer.clone(),
+            _ => panic!("expect a lambda"),
+        };
+        assert_eq!(outer.names.len(), 1);
+        let l = match &**unlocated(&outer.body[0]) {
+            Code::Let(l) => l.clone(),
+            _ => panic!("expect a let"),
+        };
+        // * the init of y is outside of the let, its body defines z in the frame of the let
+        assert!(matches!(&*l.inits[0], Code::Variable(Variable::Local(0, 0))));
+        assert_eq!(l.names.len(), 2);
+        assert!(matches!(&**unlocated(&l.body[0]), Code::Define(Variable::Local(0, 1), _)));
+        let inner = match &**unlocated(&l.body[1]) {
+            Code::Lambda(inner) => inner.clone(),
+            _ => panic!("expect a lambda"),
+        };
+        // * a lambda without parameters makes no frame: z is one frame out, x two and car is global
+        assert!(inner.names.is_empty());
+        match &**unlocated(&inner.body[0]) {
+            Code::Set(var, value) => {
+                assert_eq!(*var, Variable::Local(1, 0));
+                assert!(matches!(&**value, Code::Variable(Variable::Local(0, 1))));
+            },
+            _ => panic!("expect a set!"),
+        }
+        assert!(matches!(&*inner.body[1], Code::Variable(Variable::Global(name)) if name == "car"));
+    }
+
+    #[test]
+    fn address_malformed() {
+        // * a malformed form fails where it is evaluated, the forms around it are resolved
+        let code = address(&Value::list(vec![Value::Symbol(Symbol::intern("if")), Value::from(1)]));
+        assert!(matches!(&*code, Code::Malformed(msg) if msg.starts_with("expect 1 predicate")));
+        let x = Value::Symbol(Symbol::intern("x"));
+        let duplicate = Value::list(vec![Value::Symbol(Symbol::intern("lambda")), Value::list(vec![x.clone(), x.clone()]), x]);
+        match &*address(&Value::list(vec![duplicate, Value::from(1), Value::from(2)])) {
+            Code::Combination(items) => assert!(matches!(&*items[0], Code::Malformed(msg) if msg.contains("already defined"))),
+            _ => panic!("expect a combination"),
+        }
+    }
+}
@@ -0,0 +1,400 @@
+use super::eval::{Env, Function, RuntimeError, Value, ValueOperation};
+use super::number::{Number, NumberResult};
+use super::strings::check_arity;
+use std::cmp::Ordering;
+
+/**
+ * * number natives, registered into the root env by Env::new_root
+ */
+pub fn define_natives(env: &mut Env) {
+    let natives: &[(&str, ValueOperation)] = &[
+        ("+", native_add),
+        ("*", native_times),
+        ("-", native_minus),
+        ("/", native_divide),
+        ("=", native_equal),
+        ("<", native_less),
+        (">", native_greater),
+        ("<=", native_less_equal),
+        (">=", native_greater_equal),
+        ("quotient", native_quotient),
+        ("remainder", native_remainder),
+        ("modulo", native_modulo),
+        ("abs", native_abs),
+        ("min", native_min),
+        ("max", native_max),
+        ("gcd", native_gcd),
+        ("lcm", native_lcm),
+        ("expt", native_expt),
+        ("exact-integer-sqrt", native_exact_integer_sqrt),
+        ("zero?", native_is_zero),
+        ("positive?", native_is_positive),
+        ("negative?", native_is_negative),
+        ("even?", native_is_even),
+        ("odd?", native_is_odd),
+        ("exact", native_exact),
+        ("inexact", native_inexact),
+        ("exact?", native_is_exact),
+        ("inexact?", native_is_inexact),
+        ("number?", native_is_number),
+        ("number->string", native_number_to_string),
+        ("string->number", native_string_to_number),
+    ];
+
+    for (name, op) in natives {
+        env.define(name, &Value::Procedure(Function::Native(*op))).unwrap();
+    }
+}
+
+/**
+ * * check that every argument is a number
+ */
+fn number_args(name: &str, args: &[Value]) -> Result<Vec<Number>, RuntimeError> {
+    args.iter().map(|v| {
+        match v {
+            Value::Number(n) => Ok(n.clone()),
+            _ => runtime_error!("invalid arguments for {}: {:?}", name, v),
+        }
+    }).collect()
+}
+
+fn number_result(res: NumberResult) -> Result<Value, RuntimeError> {
+    res.map(Value::Number).map_err(RuntimeError::new)
+}
+
+/**
+ * * a general arithmatic native function for arithmatic operation
+ * * a single argument is combined with the identity first, so (- x) is 0 - x and (/ x) is 1 / x
+ * ! args must be all Value::Number, otherwise an runtime error is reported
+ */
+fn native_arithmatic(name: &str, args: &[Value], identity: Number, f: fn(&Number, &Number) -> NumberResult) -> Result<Value, RuntimeError> {
+    let args = number_args(name, args)?;
+
+    //  ! we want to do arithmatic with arg[0] as initial and go over the vec
+    //  ! we need to advance the iterator one step so that we do that
+    //  ! the old way is directly call fold with args[0] as initial and that will compute args[0] twice
+    let mut args_it = args.iter();
+    let first = match (args_it.next(), args.len()) {
+        (Some(n), 1) => f(&identity, n),
+        (Some(n), _) => Ok(n.clone()),
+        (None, _) => Ok(identity),
+    };
+    let res = first.and_then(|first| args_it.try_fold(first, |acc, x| {
+        f(&acc, x)
+    }));
+
+    number_result(res)
+
+}
+
+fn native_add(args: &[Value]) -> Result<Value, RuntimeError> {
+    native_arithmatic("+", args, Number::Integer(0), Number::add)
+}
+
+fn native_times(args: &[Value]) -> Result<Value, RuntimeError> {
+    native_arithmatic("*", args, Number::Integer(1), Number::mul)
+}
+
+fn native_minus(args: &[Value]) -> Result<Value, RuntimeError> {
+    if args.is_empty() {
+        runtime_error!("- expects at least one argument");
+    }
+    native_arithmatic("-", args, Number::Integer(0), Number::sub)
+}
+
+fn native_divide(args: &[Value]) -> Result<Value, RuntimeError> {
+    if args.is_empty() {
+        runtime_error!("/ expects at least one argument");
+    }
+    native_arithmatic("/", args, Number::Integer(1), Number::div)
+}
+
+/**
+ * * (< x y z ...) whether every number compares to the next one as holds says
+ * ! a comparison with NaN never holds
+ */
+fn native_compare(name: &str, args: &[Value], holds: fn(Ordering) -> bool) -> Result<Value, RuntimeError> {
+    let args = number_args(name, args)?;
+    if args.len() < 2 {
+        runtime_error!("{} expects at least two arguments but got: {:?}", name, args);
+    }
+    Ok(Value::Boolean(args.windows(2).all(|pair| pair[0].compare(&pair[1]).is_some_and(holds))))
+}
+
+fn native_equal(args: &[Value]) -> Result<Value, RuntimeError> {
+    native_compare("=", args, Ordering::is_eq)
+}
+
+fn native_less(args: &[Value]) -> Result<Value, RuntimeError> {
+    native_compare("<", args, Ordering::is_lt)
+}
+
+fn native_greater(args: &[Value]) -> Result<Value, RuntimeError> {
+    native_compare(">", args, Ordering::is_gt)
+}
+
+fn native_less_equal(args: &[Value]) -> Result<Value, RuntimeError> {
+    native_compare("<=", args, Ordering::is_le)
+}
+
+fn native_greater_equal(args: &[Value]) -> Result<Value, RuntimeError> {
+    native_compare(">=", args, Ordering::is_ge)
+}
+
+/**
+ * * a native taking exactly one number
+ */
+fn single_number(name: &str, args: &[Value]) -> Result<Number, RuntimeError> {
+    match number_args(name, args)?.as_slice() {
+        [n] => Ok(n.clone()),
+        _ => runtime_error!("{} expects exactly one argument but got: {:?}", name, args),
+    }
+}
+
+fn native_exact(args: &[Value]) -> Result<Value, RuntimeError> {
+    number_result(single_number("exact", args)?.to_exact())
+}
+
+fn native_inexact(args: &[Value]) -> Result<Value, RuntimeError> {
+    Ok(Value::Number(single_number("inexact", args)?.to_inexact()))
+}
+
+fn native_is_exact(args: &[Value]) -> Result<Value, RuntimeError> {
+    Ok(Value::Boolean(single_number("exact?", args)?.is_exact()))
+}
+
+fn native_is_inexact(args: &[Value]) -> Result<Value, RuntimeError> {
+    Ok(Value::Boolean(!single_number("inexact?", args)?.is_exact()))
+}
+
+fn native_is_number(args: &[Value]) -> Result<Value, RuntimeError> {
+    match args {
+        [v] => Ok(Value::Boolean(matches!(v, Value::Number(_)))),
+        _ => runtime_error!("number? expects exactly one argument but got: {:?}", args),
+    }
+}
+
+
+/**
+ * * check that every argument is an integer, 4 and 4.0 both are
+ */
+fn integer_args(name: &str, args: &[Value]) -> Result<Vec<Number>, RuntimeError> {
+    args.iter().map(|v| {
+        match v {
+            Value::Number(n) if n.is_integer() => Ok(n.clone()),
+            _ => runtime_error!("{} expects an integer but got: {:?}", name, v),
+        }
+    }).collect()
+}
+
+/**
+ * * (quotient n1 n2) (remainder n1 n2) (modulo n1 n2)
+ */
+fn integer_division(name: &str, args: &[Value], f: fn(&Number, &Number) -> NumberResult) -> Result<Value, RuntimeError> {
+    match integer_args(name, args)?.as_slice() {
+        [a, b] => number_result(f(a, b)),
+        _ => runtime_error!("{} expects exactly two arguments but got: {:?}", name, args),
+    }
+}
+
+fn native_quotient(args: &[Value]) -> Result<Value, RuntimeError> {
+    integer_division("quotient", args, Number::quotient)
+}
+
+fn native_remainder(args: &[Value]) -> Result<Value, RuntimeError> {
+    integer_division("remainder", args, Number::remainder)
+}
+
+fn native_modulo(args: &[Value]) -> Result<Value, RuntimeError> {
+    integer_division("modulo", args, Number::modulo)
+}
+
+fn native_abs(args: &[Value]) -> Result<Value, RuntimeError> {
+    Ok(Value::Number(single_number("abs", args)?.abs()))
+}
+
+/**
+ * * (max x y ...) (min x y ...) the number which compares to all others as wanted
+ * ! one inexact argument makes the result inexact, even when another number is picked
+ */
+fn extremum(name: &str, args: &[Value], wanted: Ordering) -> Result<Value, RuntimeError> {
+    let args = number_args(name, args)?;
+    let mut res = match args.first() {
+        Some(n) => n.clone(),
+        None => runtime_error!("{} expects at least one argument", name),
+    };
+    for n in &args[1..] {
+        match n.compare(&res) {
+            Some(o) if o == wanted => res = n.clone(),
+            Some(_) => {},
+            None => res = Number::Real(f64::NAN),
+        }
+    }
+    match args.iter().all(Number::is_exact) {
+        true => Ok(Value::Number(res)),
+        false => Ok(Value::Number(res.to_inexact())),
+    }
+}
+
+fn native_min(args: &[Value]) -> Result<Value, RuntimeError> {
+    extremum("min", args, Ordering::Less)
+}
+
+fn native_max(args: &[Value]) -> Result<Value, RuntimeError> {
+    extremum("max", args, Ordering::Greater)
+}
+
+// * (gcd) is 0 and (lcm) is 1, the identities of the folds
+fn native_gcd(args: &[Value]) -> Result<Value, RuntimeError> {
+    integer_args("gcd", args)?;
+    native_arithmatic("gcd", args, Number::Integer(0), Number::gcd)
+}
+
+fn native_lcm(args: &[Value]) -> Result<Value, RuntimeError> {
+    integer_args("lcm", args)?;
+    native_arithmatic("lcm", args, Number::Integer(1), Number::lcm)
+}
+
+fn native_expt(args: &[Value]) -> Result<Value, RuntimeError> {
+    match number_args("expt", args)?.as_slice() {
+        [base, exponent] => number_result(base.expt(exponent)),
+        _ => runtime_error!("expt expects exactly two arguments but got: {:?}", args),
+    }
+}
+
+/**
+ * * (exact-integer-sqrt k) the list (s r) where s * s + r is k and (s + 1) * (s + 1) is greater
+ * ! r7rs returns two values, there are no multiple values yet so both come in a list
+ */
+fn native_exact_integer_sqrt(args: &[Value]) -> Result<Value, RuntimeError> {
+    match single_number("exact-integer-sqrt", args)?.exact_integer_sqrt() {
+        Some((s, r)) => Ok(Value::list(vec![Value::Number(s), Value::Number(r)])),
+        None => runtime_error!("exact-integer-sqrt expects a non-negative exact integer but got: {:?}", args),
+    }
+}
+
+fn native_is_zero(args: &[Value]) -> Result<Value, RuntimeError> {
+    Ok(Value::Boolean(single_number("zero?", args)?.is_zero()))
+}
+
+fn native_is_positive(args: &[Value]) -> Result<Value, RuntimeError> {
+    Ok(Value::Boolean(single_number("positive?", args)?.is_positive()))
+}
+
+fn native_is_negative(args: &[Value]) -> Result<Value, RuntimeError> {
+    Ok(Value::Boolean(single_number("negative?", args)?.is_negative()))
+}
+
+fn single_integer(name: &str, args: &[Value]) -> Result<Number, RuntimeError> {
+    match integer_args(name, args)?.as_slice() {
+        [n] => Ok(n.clone()),
+        _ => runtime_error!("{} expects exactly one argument but got: {:?}", name, args),
+    }
+}
+
+fn native_is_even(args: &[Value]) -> Result<Value, RuntimeError> {
+    Ok(Value::Boolean(single_integer("even?", args)?.is_even()))
+}
+
+fn native_is_odd(args: &[Value]) -> Result<Value, RuntimeError> {
+    Ok(Value::Boolean(!single_integer("odd?", args)?.is_even()))
+}
+
+// * the optional radix of number->string and string->number
+fn radix_arg(name: &str, args: &[Value]) -> Result<u32, RuntimeError> {
+    match args.get(1) {
+        None => Ok(10),
+        Some(Value::Number(Number::Integer(r))) if [2, 8, 10, 16].contains(r) => Ok(*r as u32),
+        Some(v) => runtime_error!("{} expects a radix of 2, 8, 10 or 16 but got: {:?}", name, v),
+    }
+}
+
+/**
+ * * (number->string z [radix]) the digits string->number reads back as z
+ */
+fn native_number_to_string(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("number->string", args, 1, 2)?;
+    let radix = radix_arg("number->string", args)?;
+    let n = single_number("number->string", &args[..1])?;
+    match n.to_string_radix(radix) {
+        Some(s) => Ok(Value::String(s.into())),
+        None => runtime_error!("number->string can only write an inexact number in radix 10 but got: {:?}", args),
+    }
+}
+
+/**
+ * * (string->number string [radix]) #f when the string is not a number
+ */
+fn native_string_to_number(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("string->number", args, 1, 2)?;
+    let radix = radix_arg("string->number", args)?;
+    match &args[0] {
+        Value::String(s) => Ok(Number::parse_radix(s, radix).map_or(Value::Boolean(false), Value::Number)),
+        v => runtime_error!("string->number expects a string but got: {:?}", v),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::interpreter::tests::{eval_str, eval_write};
+
+    #[test]
+    fn arithmetic_integer_division() {
+        assert_eq!(eval_write("(list (quotient 17 5) (remainder 17 5) (modulo 17 5))"), "(3 2 2)");
+        assert_eq!(eval_write("(list (quotient -17 5) (remainder -17 5) (modulo -17 5) (modulo 17 -5))"), "(-3 -2 3 -3)");
+        assert_eq!(eval_write("(list (quotient 17.0 5) (modulo -7 2.0))"), "(3.0 1.0)");
+        assert_eq!(eval_write("(quotient 100000000000000000000 3)"), "33333333333333333333");
+        assert!(eval_str("(quotient 1 0)").unwrap_err().message().contains("division by zero"));
+        assert!(eval_str("(modulo 1.5 1)").unwrap_err().message().contains("modulo expects an integer"));
+        assert!(eval_str("(remainder 1)").is_err());
+    }
+
+    #[test]
+    fn arithmetic_min_max_abs() {
+        assert_eq!(eval_write("(list (max 1 3 2) (min 1 3 2) (max 1 2.0) (min 1/2 1/3) (abs -7) (abs -1/2))"), "(3 1 2.0 1/3 7 1/2)");
+        assert_eq!(eval_write("(min 1 2.0)"), "1.0");
+        assert!(eval_str("(max)").is_err());
+        assert!(eval_str("(abs 'x)").is_err());
+    }
+
+    #[test]
+    fn arithmetic_gcd_lcm_expt() {
+        assert_eq!(eval_write("(list (gcd 32 -36) (gcd) (gcd 5) (lcm 32 -36) (lcm) (lcm 32.0 -36))"), "(4 0 5 288 1 288.0)");
+        assert_eq!(eval_write("(list (expt 2 10) (expt 2 -1) (expt 2.0 3) (expt 0 0) (expt 4 1/2))"), "(1024 1/2 8.0 1 2.0)");
+        assert_eq!(eval_write("(list (exact-integer-sqrt 17) (exact-integer-sqrt 0))"), "((4 1) (0 0))");
+        assert!(eval_str("(expt 0 -1)").unwrap_err().message().contains("division by zero"));
+        assert_eq!(eval_write("(list (expt 1 100000000000000000000) (expt 0 100000000000000000000) (expt -1 100000000000000000001))"), "(1 0 -1)");
+        assert!(eval_str("(expt 2 100000000000000000000)").unwrap_err().message().contains("too large"));
+        assert!(eval_str("(exact-integer-sqrt -1)").is_err());
+        assert!(eval_str("(exact-integer-sqrt 4.0)").is_err());
+        assert!(eval_str("(gcd 1/2 1)").is_err());
+    }
+
+    #[test]
+    fn arithmetic_predicates() {
+        assert_eq!(eval_write("(list (zero? 0) (zero? 0.0) (zero? 1/2) (positive? 1/2) (positive? 0) (negative? -0.5) (negative? 0))"), "(#true #true #false #true #false #true #false)");
+        assert_eq!(eval_write("(list (even? 0) (even? -3) (odd? 7) (odd? 6.0) (even? 100000000000000000000))"), "(#true #false #true #false #true)");
+        assert!(eval_str("(even? 1.5)").is_err());
+        assert!(eval_str("(zero? 'a)").is_err());
+        assert!(eval_str("(positive? 1 2)").is_err());
+    }
+
+    #[test]
+    fn arithmetic_chained_comparisons() {
+        assert_eq!(eval_write("(list (< 1 2 3) (< 1 3 2) (<= 1 1 2) (= 1 1.0 1) (> 3 2 2) (>= 3 2 2))"), "(#true #false #true #true #false #true)");
+        assert!(eval_str("(< 1 'a)").is_err());
+        assert!(eval_str("(+ 1 \"2\")").unwrap_err().message().contains("invalid arguments for +"));
+        assert!(eval_str("(/ 1 0)").unwrap_err().message().contains("division by zero"));
+    }
+
+    #[test]
+    fn arithmetic_number_strings() {
+        assert_eq!(eval_write("(list (number->string 255) (number->string 255 16) (number->string -5 2) (number->string 1/3 2) (number->string 1.5))"), "(\"255\" \"ff\" \"-101\" \"1/11\" \"1.5\")");
+        assert_eq!(eval_write("(list (string->number \"42\") (string->number \"ff\" 16) (string->number \"#xff\") (string->number \"1e2\") (string->number \"abc\"))"), "(42 255 255 100.0 #false)");
+        assert_eq!(eval_write("(string->number (number->string 100000000000000000000 8) 8)"), "100000000000000000000");
+        assert!(eval_str("(number->string 1.5 2)").is_err());
+        assert!(eval_str("(number->string 1 3)").is_err());
+        assert!(eval_str("(string->number 1)").is_err());
+    }
+}
@@ -0,0 +1,484 @@
+use std::fmt;
+use std::cmp::Ordering;
+
+/**
+ * * arbitrary precision signed integer
+ * * the magnitude is stored as little endian base 2^32 limbs
+ * ! the magnitude never has leading zero limbs and zero is never negative,
+ * ! so the derived equality is numeric equality
+ */
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct BigInt {
+    negative: bool,
+    mag: Vec<u32>,
+}
+
+const BASE: u64 = 1 << 32;
+
+fn trim(mag: &mut Vec<u32>) {
+    while let Some(&0) = mag.last() {
+        mag.pop();
+    }
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    if a.len() != b.len() {
+        return a.len().cmp(&b.len());
+    }
+    for (x, y) in a.iter().rev().zip(b.iter().rev()) {
+        if x != y {
+            return x.cmp(y);
+        }
+    }
+    Ordering::Equal
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut res = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0u64;
+    for (i, &x) in long.iter().enumerate() {
+        let s = x as u64 + *short.get(i).unwrap_or(&0) as u64 + carry;
+        res.push(s as u32);
+        carry = s >> 32;
+    }
+    if carry > 0 {
+        res.push(carry as u32);
+    }
+    res
+}
+
+// ! requires |a| >= |b|
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut res = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &x) in a.iter().enumerate() {
+        let mut d = x as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = 0;
+        if d < 0 {
+            d += BASE as i64;
+            borrow = 1;
+        }
+        res.push(d as u32);
+    }
+    trim(&mut res);
+    res
+}
+
+fn mul_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+    let mut res = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let t = x as u64 * y as u64 + res[i + j] as u64 + carry;
+            res[i + j] = t as u32;
+            carry = t >> 32;
+        }
+        res[i + b.len()] = carry as u32;
+    }
+    trim(&mut res);
+    res
+}
+
+fn divrem_small(a: &[u32], d: u32) -> (Vec<u32>, u32) {
+    let mut q = vec![0u32; a.len()];
+    let mut rem = 0u64;
+    for i in (0..a.len()).rev() {
+        let cur = (rem << 32) | a[i] as u64;
+        q[i] = (cur / d as u64) as u32;
+        rem = cur % d as u64;
+    }
+    trim(&mut q);
+    (q, rem as u32)
+}
+
+fn shl_mag(a: &[u32], bits: u32) -> Vec<u32> {
+    let limbs = (bits / 32) as usize;
+    let bits = bits % 32;
+    let mut res = vec![0u32; limbs];
+    let mut carry = 0u32;
+    for &x in a {
+        if bits == 0 {
+            res.push(x);
+        } else {
+            res.push((x << bits) | carry);
+            carry = x >> (32 - bits);
+        }
+    }
+    if carry > 0 {
+        res.push(carry);
+    }
+    trim(&mut res);
+    res
+}
+
+fn shr_bits(a: &[u32], bits: u32) -> Vec<u32> {
+    if bits == 0 {
+        return a.to_vec();
+    }
+    let mut res = vec![0u32; a.len()];
+    for i in 0..a.len() {
+        let high = if i + 1 < a.len() { a[i + 1] << (32 - bits) } else { 0 };
+        res[i] = (a[i] >> bits) | high;
+    }
+    trim(&mut res);
+    res
+}
+
+/**
+ * * long division of magnitudes, knuth's algorithm D
+ * ! the divisor must not be zero
+ */
+fn divrem_mag(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if cmp_mag(a, b) == Ordering::Less {
+        return (Vec::new(), a.to_vec());
+    }
+    if b.len() == 1 {
+        let (q, r) = divrem_small(a, b[0]);
+        let mut r = vec![r];
+        trim(&mut r);
+        return (q, r);
+    }
+
+    // * normalize so the top limb of the divisor has its high bit set
+    let shift = b[b.len() - 1].leading_zeros();
+    let v = shl_mag(b, shift);
+    let mut u = shl_mag(a, shift);
+    u.resize(a.len() + 1, 0);
+    let n = v.len();
+    let m = u.len() - n - 1;
+    let mut q = vec![0u32; m + 1];
+
+    for j in (0..=m).rev() {
+        let num = ((u[j + n] as u64) << 32) | u[j + n - 1] as u64;
+        let mut qhat = num / v[n - 1] as u64;
+        let mut rhat = num % v[n - 1] as u64;
+        while qhat >= BASE || qhat * v[n - 2] as u64 > ((rhat << 32) | u[j + n - 2] as u64) {
+            qhat -= 1;
+            rhat += v[n - 1] as u64;
+            if rhat >= BASE {
+                break;
+            }
+        }
+
+        // * multiply and subtract qhat * v from the current window of u
+        let mut borrow = 0i64;
+        let mut carry = 0u64;
+        for i in 0..n {
+            let p = qhat * v[i] as u64 + carry;
+            carry = p >> 32;
+            let t = u[i + j] as i64 - borrow - (p & 0xffff_ffff) as i64;
+            u[i + j] = t as u32;
+            borrow = if t < 0 { 1 } else { 0 };
+        }
+        let t = u[j + n] as i64 - borrow - carry as i64;
+        u[j + n] = t as u32;
+
+        // * qhat was one too large, add the divisor back
+        if t < 0 {
+            qhat -= 1;
+            let mut carry = 0u64;
+            for i in 0..n {
+                let s = u[i + j] as u64 + v[i] as u64 + carry;
+                u[i + j] = s as u32;
+                carry = s >> 32;
+            }
+            u[j + n] = u[j + n].wrapping_add(carry as u32);
+        }
+        q[j] = qhat as u32;
+    }
+
+    trim(&mut q);
+    u.truncate(n);
+    trim(&mut u);
+    (q, shr_bits(&u, shift))
+}
+
+impl BigInt {
+
+    fn from_parts(negative: bool, mut mag: Vec<u32>) -> BigInt {
+        trim(&mut mag);
+        BigInt { negative: negative && !mag.is_empty(), mag }
+    }
+
+    pub fn zero() -> BigInt {
+        BigInt::default()
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mag.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn is_even(&self) -> bool {
+        self.mag.first().is_none_or(|l| l % 2 == 0)
+    }
+
+    pub fn abs(&self) -> BigInt {
+        BigInt::from_parts(false, self.mag.clone())
+    }
+
+    pub fn neg(&self) -> BigInt {
+        BigInt::from_parts(!self.negative, self.mag.clone())
+    }
+
+    pub fn add(&self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::from_parts(self.negative, add_mag(&self.mag, &other.mag));
+        }
+        match cmp_mag(&self.mag, &other.mag) {
+            Ordering::Less => BigInt::from_parts(other.negative, sub_mag(&other.mag, &self.mag)),
+            _ => BigInt::from_parts(self.negative, sub_mag(&self.mag, &other.mag)),
+        }
+    }
+
+    pub fn sub(&self, other: &BigInt) -> BigInt {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &BigInt) -> BigInt {
+        BigInt::from_parts(self.negative != other.negative, mul_mag(&self.mag, &other.mag))
+    }
+
+    /**
+     * * truncating division, the remainder has the sign of the dividend
+     * * returns None on division by zero
+     */
+    pub fn divrem(&self, other: &BigInt) -> Option<(BigInt, BigInt)> {
+        if other.is_zero() {
+            return None;
+        }
+        let (q, r) = divrem_mag(&self.mag, &other.mag);
+        Some((BigInt::from_parts(self.negative != other.negative, q), BigInt::from_parts(self.negative, r)))
+    }
+
+    pub fn gcd(&self, other: &BigInt) -> BigInt {
+        let (mut a, mut b) = (self.abs(), other.abs());
+        while !b.is_zero() {
+            let (_, r) = a.divrem(&b).unwrap();
+            a = b;
+            b = r;
+        }
+        a
+    }
+
+    pub fn pow(&self, mut exp: u64) -> BigInt {
+        let mut base = self.clone();
+        let mut res = BigInt::from(1);
+        while exp > 0 {
+            if exp & 1 == 1 {
+                res = res.mul(&base);
+            }
+            exp >>= 1;
+            if exp > 0 {
+                base = base.mul(&base);
+            }
+        }
+        res
+    }
+
+    /**
+     * * the integer square root of a non-negative number, newton's iteration coming down from above
+     */
+    pub fn isqrt(&self) -> BigInt {
+        if self.is_zero() {
+            return BigInt::zero();
+        }
+        // * a number below 2^(32 * limbs) has its root below 2^(16 * limbs)
+        let two = BigInt::from(2);
+        let mut x = BigInt::from(1).shl(16 * self.mag.len() as u32);
+        loop {
+            let (q, _) = self.divrem(&x).unwrap();
+            let (y, _) = x.add(&q).divrem(&two).unwrap();
+            if y >= x {
+                return x;
+            }
+            x = y;
+        }
+    }
+
+    pub fn shl(&self, bits: u32) -> BigInt {
+        BigInt::from_parts(self.negative, shl_mag(&self.mag, bits))
+    }
+
+    /**
+     * * the digits in a radix up to 36, lower case like the #x literals
+     */
+    pub fn to_string_radix(&self, radix: u32) -> String {
+        if self.is_zero() {
+            return "0".to_string();
+        }
+        let mut digits = Vec::new();
+        let mut mag = self.mag.clone();
+        while !mag.is_empty() {
+            let (q, r) = divrem_small(&mag, radix);
+            digits.push(char::from_digit(r, radix).unwrap());
+            mag = q;
+        }
+        if self.negative {
+            digits.push('-');
+        }
+        digits.iter().rev().collect()
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.mag.len() > 2 {
+            return None;
+        }
+        let m = self.mag.iter().rev().fold(0u64, |acc, &l| (acc << 32) | l as u64);
+        match self.negative {
+            true if m <= i64::MAX as u64 + 1 => Some((m as i64).wrapping_neg()),
+            false if m <= i64::MAX as u64 => Some(m as i64),
+            _ => None,
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let m = self.mag.iter().rev().fold(0f64, |acc, &l| acc * BASE as f64 + l as f64);
+        if self.negative { -m } else { m }
+    }
+
+    /**
+     * * parse unsigned digits in the given radix, None if any digit is invalid
+     */
+    pub fn parse_radix(digits: &str, radix: u32) -> Option<BigInt> {
+        if digits.is_empty() {
+            return None;
+        }
+        let mut mag: Vec<u32> = Vec::new();
+        for c in digits.chars() {
+            let d = c.to_digit(radix)? as u64;
+            let mut carry = d;
+            for limb in mag.iter_mut() {
+                let t = *limb as u64 * radix as u64 + carry;
+                *limb = t as u32;
+                carry = t >> 32;
+            }
+            if carry > 0 {
+                mag.push(carry as u32);
+            }
+        }
+        Some(BigInt::from_parts(false, mag))
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(i: i64) -> BigInt {
+        let m = i.unsigned_abs();
+        BigInt::from_parts(i < 0, vec![m as u32, (m >> 32) as u32])
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_mag(&self.mag, &other.mag),
+            (true, true) => cmp_mag(&other.mag, &self.mag),
+        }
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+
+        // * peel off nine decimal digits at a time
+        let mut chunks = Vec::new();
+        let mut mag = self.mag.clone();
+        while !mag.is_empty() {
+            let (q, r) = divrem_small(&mag, 1_000_000_000);
+            chunks.push(r);
+            mag = q;
+        }
+
+        let mut res = String::new();
+        if self.negative {
+            res.push('-');
+        }
+        res.push_str(&chunks.pop().unwrap().to_string());
+        for chunk in chunks.iter().rev() {
+            res.push_str(&format!("{:09}", chunk));
+        }
+        write!(f, "{}", res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn big(s: &str) -> BigInt {
+        match s.strip_prefix('-') {
+            Some(digits) => BigInt::parse_radix(digits, 10).unwrap().neg(),
+            None => BigInt::parse_radix(s, 10).unwrap(),
+        }
+    }
+
+    #[test]
+    fn parse_and_display() {
+        let s = "123456789012345678901234567890";
+        assert_eq!(big(s).to_string(), s);
+        assert_eq!(big("-42").to_string(), "-42");
+        assert_eq!(BigInt::parse_radix("ffffffffffffffffff", 16).unwrap().to_string(), "4722366482869645213695");
+        assert_eq!(BigInt::from(i64::MIN).to_string(), i64::MIN.to_string());
+    }
+
+    #[test]
+    fn arithmetic() {
+        let a = big("99999999999");
+        assert_eq!(a.mul(&a).to_string(), "9999999999800000000001");
+        assert_eq!(big("-5").add(&big("3")), big("-2"));
+        assert_eq!(big("3").sub(&big("10000000000000000000000")), big("-9999999999999999999997"));
+        assert_eq!(big("2").pow(100).to_string(), "1267650600228229401496703205376");
+    }
+
+    #[test]
+    fn division() {
+        let a = big("1267650600228229401496703205376");
+        let b = big("-12345678901234567");
+        let (q, r) = a.divrem(&b).unwrap();
+        assert_eq!(q, big("-102679699542603"));
+        assert_eq!(r, big("10924694790447475"));
+        assert_eq!(q.mul(&b).add(&r), a);
+        assert!(a.divrem(&BigInt::zero()).is_none());
+        assert_eq!(big("48").gcd(&big("-180")), big("12"));
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(big("9223372036854775807").to_i64(), Some(i64::MAX));
+        assert_eq!(big("-9223372036854775808").to_i64(), Some(i64::MIN));
+        assert_eq!(big("9223372036854775808").to_i64(), None);
+        assert!(big("-3") < big("2"));
+        assert!(big("-30000000000000000000") < big("-3"));
+    }
+
+    #[test]
+    fn roots_and_radix() {
+        assert_eq!(big("0").isqrt(), big("0"));
+        assert_eq!(big("15").isqrt(), big("3"));
+        assert_eq!(big("16").isqrt(), big("4"));
+        assert_eq!(big("100000000000000000000000000000000000000").isqrt(), big("10000000000000000000"));
+        assert_eq!(big("99999999999999999999999999999999999999").isqrt(), big("9999999999999999999"));
+        assert_eq!(big("-255").to_string_radix(16), "-ff");
+        assert_eq!(big("18446744073709551616").to_string_radix(2), format!("1{}", "0".repeat(64)));
+        assert_eq!(big("0").to_string_radix(8), "0");
+    }
+}
@@ -0,0 +1,361 @@
+use super::address::{body_defines, special_form_names};
+use super::eval::{self, Params, Value};
+use super::span::Span;
+use super::symbol::Symbol;
+use std::rc::Rc;
+
+/**
+ * * one instruction of the vm, the operands of an instruction are on the stack of its activation
+ * ! a local variable is addressed by (depth, slot): the frame depth levels out and the slot in it
+ */
+pub enum Op {
+    Const(Value),
+    Local(usize, usize),
+    // * assign the value on top to a variable, which leaves the unspecified value
+    SetLocal(usize, usize),
+    // * bind a slot of the innermost frame to the value on top, which stays as the value of define
+    DefineLocal(usize),
+    Global(Symbol),
+    SetGlobal(Symbol),
+    DefineGlobal(Symbol),
+    Pop,
+    Jump(usize),
+    JumpIfFalse(usize),
+    // * a closure of the template over the frames of the running code
+    Closure(Rc<Template>),
+    // * call the procedure below the given number of arguments
+    Call(usize),
+    // * replace the running code by the call, the return after it is only reached by natives
+    TailCall(usize),
+    // * (let ...) start a frame with that many values from the stack and slots for the names
+    Enter(usize, Rc<Vec<Symbol>>),
+    Leave,
+    Return,
+}
+
+/**
+ * * compiled code: a lambda, or a toplevel form which is a lambda without parameters and frame
+ */
+pub struct Template {
+    pub(super) code: Vec<Op>,
+    // * the innermost located form of every instruction, errors are reported there
+    pub(super) spans: Vec<Option<Rc<Span>>>,
+    pub(super) params: Params,
+    // * the slots of the frame a call makes: the parameters, then the internal definitions
+    pub(super) names: Rc<Vec<Symbol>>,
+    // * where the body was read
+    pub(super) source: Option<Span>,
+}
+
+impl Template {
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    pub fn source(&self) -> Option<&Span> {
+        self.source.as_ref()
+    }
+}
+
+/**
+ * * compile an expanded toplevel form
+ * * None for a form the compiler does not handle, like guard or a malformed special form,
+ * * the tree-walker runs those and reports their errors when they are evaluated
+ */
+pub fn compile(form: &Value) -> Option<Rc<Template>> {
+    let mut compiler = Compiler::new(Vec::new(), form.span());
+    compiler.expr(form, true)?;
+    Some(Rc::new(compiler.finish(Params::default(), Vec::new(), None)))
+}
+
+struct Compiler {
+    code: Vec<Op>,
+    spans: Vec<Option<Rc<Span>>>,
+    // * the slot names of the frames around the code, the innermost last
+    scopes: Vec<Vec<Symbol>>,
+    span: Option<Rc<Span>>,
+}
+
+impl Compiler {
+    fn new(scopes: Vec<Vec<Symbol>>, span: Option<Rc<Span>>) -> Compiler {
+        Compiler { code: Vec::new(), spans: Vec::new(), scopes, span }
+    }
+
+    fn finish(self, params: Params, names: Vec<Symbol>, source: Option<Span>) -> Template {
+        Template { code: self.code, spans: self.spans, params, names: Rc::new(names), source }
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        self.code.push(op);
+        self.spans.push(self.span.clone());
+        self.code.len() - 1
+    }
+
+    // * point the jump at index to the next instruction
+    fn patch(&mut self, index: usize) {
+        let target = self.code.len();
+        match &mut self.code[index] {
+            Op::Jump(t) | Op::JumpIfFalse(t) => *t = target,
+            _ => unreachable!("only jumps are patched"),
+        }
+    }
+
+    fn resolve(&self, name: Symbol) -> Option<(usize, usize)> {
+        self.scopes.iter().rev().enumerate().find_map(|(depth, names)| {
+            names.iter().position(|n| *n == name).map(|slot| (depth, slot))
+        })
+    }
+
+    /**
+     * * compile expr, in tail position the code returns its value or tail calls
+     */
+    fn expr(&mut self, expr: &Value, tail: bool) -> Option<()> {
+        match expr {
+            Value::Pair(_) => return self.form(expr, tail),
+            Value::Nil => return None,
+            Value::Symbol(name) => {
+                let op = match self.resolve(*name) {
+                    Some((depth, slot)) => Op::Local(depth, slot),
+                    None => Op::Global(*name),
+                };
+                self.emit(op);
+            },
+            _ => {
+                self.emit(Op::Const(expr.clone()));
+            },
+        }
+        self.ret(tail);
+        Some(())
+    }
+
+    fn ret(&mut self, tail: bool) {
+        if tail {
+            self.emit(Op::Return);
+        }
+    }
+
+    fn form(&mut self, form: &Value, tail: bool) -> Option<()> {
+        let items = form.to_vec()?;
+        let outer = self.span.clone();
+        if let Some(span) = form.span() {
+            self.span = Some(span);
+        }
+
+        let res = match (&items[0], items.len()) {
+            (Value::Symbol(k), 2) if k == "quote" => {
+                self.emit(Op::Const(items[1].clone()));
+                self.ret(tail);
+                Some(())
+            },
+            (Value::Symbol(k), 2) if k == "quasiquote" => match eval::quasiquote(&items[1]) {
+                Ok(expr) => self.expr(&expr, tail),
+                Err(_) => None,
+            },
+            (Value::Symbol(k), 4) if k == "if" => self.branch(&items[1], &items[2], &items[3], tail),
+            (Value::Symbol(k), 3) if k == "set!" => self.set(&items[1], &items[2], tail),
+            (Value::Symbol(k), 3) if k == "define" => self.define(&items[1], &items[2], tail),
+            (Value::Symbol(k), n) if k == "lambda" && n >= 2 => {
+                let template = self.lambda(&items[1], &items[2..])?;
+                self.emit(Op::Closure(template));
+                self.ret(tail);
+                Some(())
+            },
+            (Value::Symbol(k), n) if k == "let" && n >= 2 => self.local(&items[1], &items[2..], tail),
+            (Value::Symbol(k), _) if special_form_names().any(|name| *k == name) => None,
+            _ => self.combination(&items, tail),
+        };
+
+        self.span = outer;
+        res
+    }
+
+    fn branch(&mut self, test: &Value, then: &Value, otherwise: &Value, tail: bool) -> Option<()> {
+        self.expr(test, false)?;
+        let to_otherwise = self.emit(Op::JumpIfFalse(0));
+        self.expr(then, tail)?;
+        // * a branch in tail position has already returned
+        let to_end = match tail {
+            true => None,
+            false => Some(self.emit(Op::Jump(0))),
+        };
+        self.patch(to_otherwise);
+        self.expr(otherwise, tail)?;
+        if let Some(to_end) = to_end {
+            self.patch(to_end);
+        }
+        Some(())
+    }
+
+    fn set(&mut self, name: &Value, expr: &Value, tail: bool) -> Option<()> {
+        let name = match name {
+            Value::Symbol(name) => *name,
+            _ => return None,
+        };
+        self.expr(expr, false)?;
+        let op = match self.resolve(name) {
+            Some((depth, slot)) => Op::SetLocal(depth, slot),
+            None => Op::SetGlobal(name),
+        };
+        self.emit(op);
+        self.ret(tail);
+        Some(())
+    }
+
+    /**
+     * * a definition in a body has a slot in the frame of the body, one at the top level is global
+     */
+    fn define(&mut self, name: &Value, expr: &Value, tail: bool) -> Option<()> {
+        let name = match name {
+            Value::Symbol(name) => *name,
+            _ => return None,
+        };
+        self.expr(expr, false)?;
+        let op = match self.scopes.last() {
+            Some(names) => Op::DefineLocal(names.iter().position(|n| *n == name)?),
+            None => Op::DefineGlobal(name),
+        };
+        self.emit(op);
+        self.ret(tail);
+        Some(())
+    }
+
+    fn lambda(&mut self, params: &Value, body: &[Value]) -> Option<Rc<Template>> {
+        let params = Params::parse(params).ok()?;
+        let mut names: Vec<Symbol> = params.names().collect();
+        names.extend(body_defines(body));
+
+        // * like a let without bindings, a call without slots makes no frame
+        let mut scopes = self.scopes.clone();
+        if !names.is_empty() {
+            scopes.push(names.clone());
+        }
+        let mut compiler = Compiler::new(scopes, None);
+        compiler.body(body, true)?;
+
+        let spans: Vec<_> = body.iter().filter_map(Value::span).collect();
+        let source = match (spans.first(), spans.last()) {
+            (Some(first), Some(last)) => Some(first.to(last)),
+            _ => None,
+        };
+        Some(Rc::new(compiler.finish(params, names, source)))
+    }
+
+    /**
+     * * (let ((name init) ...) body...) the inits are evaluated in the enclosing frame,
+     * * the body in a new frame holding them and the definitions of the body
+     */
+    fn local(&mut self, bindings: &Value, body: &[Value], tail: bool) -> Option<()> {
+        let mut names = Vec::new();
+        for binding in bindings.to_vec()? {
+            match binding.to_vec()?.as_slice() {
+                [Value::Symbol(name), init] => {
+                    self.expr(init, false)?;
+                    names.push(*name);
+                },
+                _ => return None,
+            }
+        }
+        let count = names.len();
+        names.extend(body_defines(body));
+
+        // * a frame without slots could never be addressed
+        if names.is_empty() {
+            return self.body(body, tail);
+        }
+        self.emit(Op::Enter(count, Rc::new(names.clone())));
+        self.scopes.push(names);
+        let res = self.body(body, tail);
+        self.scopes.pop();
+        if !tail {
+            self.emit(Op::Leave);
+        }
+        res
+    }
+
+    fn body(&mut self, body: &[Value], tail: bool) -> Option<()> {
+        match body.split_last() {
+            Some((last, init)) => {
+                for form in init {
+                    self.expr(form, false)?;
+                    self.emit(Op::Pop);
+                }
+                self.expr(last, tail)
+            },
+            None => {
+                self.emit(Op::Const(Value::Unit));
+                self.ret(tail);
+                Some(())
+            },
+        }
+    }
+
+    fn combination(&mut self, items: &[Value], tail: bool) -> Option<()> {
+        for item in items {
+            self.expr(item, false)?;
+        }
+        let argc = items.len() - 1;
+        // * the call is located at the combination, not at its last operand
+        let span = self.span.clone();
+        self.code.push(match tail {
+            true => Op::TailCall(argc),
+            false => Op::Call(argc),
+        });
+        self.spans.push(span);
+        // * a native called in tail position leaves its value and returns here
+        self.ret(tail);
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::interpreter::{eval::Evalator, lex::lexer, parser::Parser};
+
+    // * the last form of input, compiled after expansion
+    fn compile_str(input: &str) -> Option<Rc<Template>> {
+        let nodes = Parser::parse(&lexer::lex(input).unwrap()).unwrap();
+        compile(Evalator::new().expand(&nodes).unwrap().last().unwrap())
+    }
+
+    #[test]
+    fn compile_addressing() {
+        let t = compile_str("(lambda (x) (let ((y x)) (define z y) (lambda () (set! x z))))").unwrap();
+        let outer = match &t.code[0] {
+            Op::Closure(outer) => outer.clone(),
+            _ => panic!("expect a closure"),
+        };
+        assert_eq!(outer.params().required().len(), 1);
+        assert!(matches!(outer.code[1], Op::Enter(1, ref names) if names.len() == 2));
+        let inner = outer.code.iter().find_map(|op| match op {
+            Op::Closure(inner) => Some(inner.clone()),
+            _ => None,
+        }).unwrap();
+        // * z is in the let frame right outside the closure, x one frame further out
+        assert!(matches!(inner.code[0], Op::Local(0, 1)));
+        assert!(matches!(inner.code[1], Op::SetLocal(1, 0)));
+        assert!(matches!(inner.code[2], Op::Return));
+    }
+
+    #[test]
+    fn compile_tail_calls() {
+        let t = compile_str("(define (f n) (if (g n) (f n) (+ (f n) 1)))").unwrap();
+        let f = match &t.code[0] {
+            Op::Closure(f) => f.clone(),
+            _ => panic!("expect a closure"),
+        };
+        assert_eq!(f.code.iter().filter(|op| matches!(op, Op::TailCall(1))).count(), 1);
+        assert_eq!(f.code.iter().filter(|op| matches!(op, Op::TailCall(2))).count(), 1);
+        assert_eq!(f.code.iter().filter(|op| matches!(op, Op::Call(_))).count(), 2);
+        assert!(matches!(t.code.last(), Some(Op::Return)));
+    }
+
+    #[test]
+    fn compile_unsupported() {
+        assert!(compile_str("(guard (e (#t 1)) (raise 2))").is_none());
+        assert!(compile_str("(if 1)").is_none());
+        assert!(compile_str("(lambda () (guard (e (#t 1)) 2))").is_none());
+        assert!(compile_str("`(1 ,@(list 2))").is_some());
+    }
+}
@@ -1,5 +1,22 @@
-use super::parser::Node;
-use std::{cell::{RefCell}, collections::HashMap, rc::Rc};
+use super::parser::{Node, Parser};
+use super::number::Number;
+use super::lex::{lexer, CHAR_NAMES};
+use super::arithmetic;
+use super::strings;
+use super::list::{self, Pair, list_arg};
+use super::vector::{self, Vector};
+use super::system;
+use super::library::Libraries;
+use super::exception::{self, ErrorObject};
+use super::expand::Expander;
+use super::compile::{self, Template};
+use super::address::{self, Body, Clause, Code, Guard, Lambda, Let, Variable};
+use super::vm::{Activation, Locals};
+use super::gc::{self, EnvRef, LocalsRef, Marker};
+use super::symbol::{Symbol, SymbolMap, SymbolSet};
+use super::span::{Source, Span};
+use crate::Error;
+use std::{cell::{RefCell}, collections::HashSet, mem, rc::Rc};
 use std::fmt;
 
 
@@ -7,316 +24,1324 @@ use std::fmt;
 
 pub struct RuntimeError {
     msg: String,
+    // * the object raised by scheme code if it was never handled
+    raised: Option<Value>,
+    // * the innermost form being evaluated when the error happened
+    span: Option<Rc<Span>>,
 }
 
 impl fmt::Display for RuntimeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Runtime Error: {}", self.msg)
+        match &self.span {
+            Some(span) => write!(f, "{}: Runtime Error: {}\n{}", span, self.msg, span.excerpt()),
+            None => write!(f, "Runtime Error: {}", self.msg),
+        }
     }
 }
 
 impl fmt::Debug for RuntimeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Runtime Error: {}", self.msg)
+        write!(f, "{}", self)
     }
 }
 
-macro_rules! runtime_error {
-    ($($arg:tt),*) => (
-        return Err(RuntimeError { msg: format!($($arg),*)})
-    )
+impl std::error::Error for RuntimeError {}
+
+impl RuntimeError {
+    pub(crate) fn new(msg: String) -> RuntimeError {
+        RuntimeError { msg, raised: None, span: None }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.msg
+    }
+
+    pub fn span(&self) -> Option<&Span> {
+        self.span.as_deref()
+    }
+
+    // * locate an error which does not know its position yet
+    pub(crate) fn at(mut self, span: Option<Rc<Span>>) -> RuntimeError {
+        if self.span.is_none() {
+            self.span = span;
+        }
+        self
+    }
+
+    /**
+     * * an object raised by raise or error which no handler took care of
+     */
+    pub(crate) fn raised(obj: Value) -> RuntimeError {
+        let msg = match &obj {
+            Value::Error(e) => format!("{}", e),
+            _ => format!("uncaught exception: {:?}", obj),
+        };
+        RuntimeError { msg, raised: Some(obj), span: None }
+    }
+
+    /**
+     * * the condition scheme handlers see for this error,
+     * * built-in faults become error objects carrying the message
+     */
+    pub(crate) fn into_condition(self) -> Value {
+        match self.raised {
+            Some(obj) => obj,
+            None => Value::Error(Rc::new(ErrorObject::new(self.msg, Value::Nil))),
+        }
+    }
 }
 
 
-#[derive(PartialEq, Clone)]
+#[derive(Clone)]
 pub enum Value {
     Unit,
-    Symbol(String),
-    Integer(usize),
-    List(Vec<Value>),
+    Symbol(Symbol),
+    Number(Number),
+    Nil,
+    Pair(Rc<Pair>),
+    Vector(Rc<Vector>),
     Procedure(Function),
+    Error(Rc<ErrorObject>),
     Boolean(bool),
+    String(Rc<str>),
+    Char(char),
 }
 
-pub type ValueOperation = fn(&[Value], Rc<RefCell<Env>>) -> Result<Value, RuntimeError>;
+/**
+ * * result of one machine step: either a value handed to the frame on top of the stack,
+ * * or an expression to evaluate next with the stack as its continuation
+ * ! an expression in tail position pushes no frame, which keeps tail calls in constant space
+ */
+pub enum Tail {
+    Return(Value),
+    Eval(Rc<Code>, Scope),
+    // * compiled code to run on the vm
+    Execute(Activation),
+}
+
+/**
+ * * native procedures always receive already evaluated arguments
+ */
+pub type ValueOperation = fn(&[Value]) -> Result<Value, RuntimeError>;
+
+/**
+ * * natives which hand control back to the machine (apply, eval, call/cc ...)
+ * ! the env is the env of the call site, the arguments are still evaluated
+ */
+pub type TailOperation = fn(&[Value], EnvRef, &mut Machine) -> Result<Tail, RuntimeError>;
+
+/**
+ * * the parameters of a procedure: (a b), a rest parameter after the required ones (a b . c), or only one c
+ * * the rest parameter is bound to the list of the arguments after the required ones
+ */
+#[derive(Debug, Default, PartialEq)]
+pub struct Params {
+    required: Vec<Symbol>,
+    rest: Option<Symbol>,
+}
+
+impl Params {
+    pub(crate) fn parse(spec: &Value) -> Result<Params, RuntimeError> {
+        let (items, tail) = spec.parts();
+        let required = items.iter().map(|p| match p {
+            Value::Symbol(s) => Ok(*s),
+            _ => runtime_error!("Must provide symbol as parameter names: {:?}", p),
+        }).collect::<Result<Vec<Symbol>, RuntimeError>>()?;
+        let rest = match tail {
+            Value::Nil => None,
+            Value::Symbol(s) => Some(s),
+            _ => runtime_error!("Must provide parameter lists in function parameter: {:?}", spec),
+        };
+        Ok(Params { required, rest })
+    }
+
+    pub fn required(&self) -> &[Symbol] {
+        &self.required
+    }
+
+    pub fn rest(&self) -> Option<Symbol> {
+        self.rest
+    }
+
+    // * the variables a call binds, the rest parameter last
+    pub fn names(&self) -> impl Iterator<Item = Symbol> + '_ {
+        self.required.iter().copied().chain(self.rest)
+    }
+
+    /**
+     * * the values of the parameters for a call with args, the arguments left over become the rest list
+     */
+    pub(crate) fn arguments(&self, mut args: Vec<Value>) -> Result<Vec<Value>, RuntimeError> {
+        let n = self.required.len();
+        match self.rest {
+            None if args.len() != n => runtime_error!("expect {} arguments but got {}: {:?}", n, (args.len()), args),
+            Some(_) if args.len() < n => runtime_error!("expect at least {} arguments but got {}: {:?}", n, (args.len()), args),
+            None => Ok(args),
+            Some(_) => {
+                let rest = Value::list(args.split_off(n));
+                args.push(rest);
+                Ok(args)
+            },
+        }
+    }
+}
 
 pub enum Function {
     Native(ValueOperation),
-    Closure(Vec<String>, Vec<Value>, Rc<RefCell<Env>>),
+    TailNative(TailOperation),
+    Closure(Rc<Closure>),
+    Compiled(Rc<Compiled>),
+    Continuation(Rc<Continuation>),
+}
+
+/**
+ * * a lambda of the tree-walker, over its frames and the globals
+ */
+pub struct Closure {
+    pub(crate) lambda: Rc<Lambda>,
+    pub(crate) locals: Option<LocalsRef>,
+    pub(crate) globals: EnvRef,
 }
 
+/**
+ * * a closure compiled to bytecode, over its frames and the globals
+ */
+pub struct Compiled {
+    pub(crate) template: Rc<Template>,
+    pub(crate) locals: Option<LocalsRef>,
+    pub(crate) globals: EnvRef,
+}
+
+/**
+ * * procedures are compared by identity
+ * ! every evaluation of a lambda makes a new closure, two of them are never the same even with equal code and env
+ */
 impl PartialEq for Function{
     fn eq(&self, other: &Function) -> bool {
-        self == other
+        match (self, other) {
+            (Function::Native(a), Function::Native(b)) => *a as usize == *b as usize,
+            (Function::TailNative(a), Function::TailNative(b)) => *a as usize == *b as usize,
+            (Function::Closure(a), Function::Closure(b)) => Rc::ptr_eq(a, b),
+            (Function::Compiled(a), Function::Compiled(b)) => Rc::ptr_eq(a, b),
+            (Function::Continuation(a), Function::Continuation(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
     }
 }
 
-/** 
- * * evaluate expression, if the first item is a procedure we apply it
- * * otherwise we just process it with eval_values
+/**
+ * * where the tree-walker evaluates code: the frames of the local variables around it and the globals
  */
-fn eval_expression(vals: &[Value], env: Rc<RefCell<Env>>) -> Result<Value, RuntimeError> {
-    let p = eval_value(&vals[0], env.clone()).unwrap();
+#[derive(Clone, Copy)]
+pub struct Scope {
+    locals: Option<LocalsRef>,
+    globals: EnvRef,
+}
 
-    match &p {
-        Value::Procedure(f) => proc_apply(f.clone(), &vals[1..], env),
-        _ => runtime_error!("first entry must be procedure: {:?}", vals),
+impl Scope {
+    pub(crate) fn global(globals: EnvRef) -> Scope {
+        Scope { locals: None, globals }
+    }
+
+    fn get(self, var: Variable) -> Result<Value, RuntimeError> {
+        match var {
+            Variable::Local(depth, slot) => Locals::get(self.locals, depth, slot),
+            Variable::Global(name) => self.globals.get(name),
+        }
+    }
+
+    fn set(self, var: Variable, v: &Value) -> Result<(), RuntimeError> {
+        match var {
+            Variable::Local(depth, slot) => Locals::set(self.locals, depth, slot, v),
+            Variable::Global(name) => self.globals.set(name, v),
+        }
+    }
+
+    fn define(self, var: Variable, v: &Value) -> Result<(), RuntimeError> {
+        match var {
+            Variable::Local(_, slot) => Locals::define(self.locals, slot, v),
+            Variable::Global(name) => self.globals.define(name, v),
+        }
+    }
+
+    // * the scope with a new frame holding values, the slots after them are bound by the defines of its body
+    fn enter(self, values: Vec<Value>, names: &Rc<Vec<Symbol>>) -> Scope {
+        match names.is_empty() {
+            true => self,
+            false => Scope { locals: Some(Locals::alloc(values, names.clone(), self.locals)), globals: self.globals },
+        }
+    }
+
+    fn trace(self, m: &mut Marker) {
+        m.locals(self.locals);
+        m.env(self.globals);
     }
 }
 
-/** 
- * * (if pred v1 v2)
-*/
-fn native_if(args: &[Value], env: Rc<RefCell<Env>>) -> Result<Value, RuntimeError> {
-    match args.len() {
-        3 => {
-            match eval_value(&args[0], env.clone()) {
-                    Ok(Value::Boolean(b)) => {
-                        if !b {
-                            eval_value(&args[2], env.clone())
-                        } else {
-                            eval_value(&args[1], env.clone())
-                        }
-                    },
-                    _ => eval_value(&args[1], env.clone())
-            }
-        },
-        _ => runtime_error!("expect 1 predicate and 2 branches but got: {:?}", args)
+/**
+ * * what is left to do with the value currently being computed
+ * * the frames on the machine stack form the continuation, capturing it copies the stack
+ */
+#[derive(Clone)]
+pub(super) enum Frame {
+    // * (if test then else) waiting for the test
+    If(Rc<Code>, Rc<Code>, Scope),
+    // * the code of a combination, the values of the parts evaluated so far and where it was read
+    Combination(Body, Vec<Value>, Option<Rc<Span>>, Scope),
+    // * a body and the index of its next form
+    Body(Body, usize, Scope),
+    // * a let and the init values so far
+    Let(Rc<Let>, Vec<Value>, Scope),
+    Define(Variable, Scope),
+    Set(Variable, Scope),
+    // * a procedure applied to each argument list in turn, the results so far and how to combine them
+    Map(Value, Rc<Vec<Vec<Value>>>, Vec<Value>, fn(Vec<Value>) -> Value, EnvRef),
+    // * dynamic-wind waiting for its before thunk, its body thunk and its after thunk
+    WindBefore(Value, Value, Value, EnvRef),
+    WindBody(Rc<Winder>, EnvRef),
+    WindAfter(Value),
+    // * the after and before thunks still to run before a continuation gets the value
+    Rewind(Vec<(Value, Option<Rc<Winder>>)>, Rc<Continuation>, Value, EnvRef),
+    // * the handlers to reinstall once a with-exception-handler thunk or a handler returns
+    Handlers(Option<Rc<Handlers>>),
+    // * a handler returned from a non-continuable raise of the value
+    Raised(Value),
+    // * a guard waiting for the condition
+    Guard(Rc<Guard>, Scope),
+    // * a guard clause waiting for its test: the guard, the index of the clause and the condition
+    GuardClause(Rc<Guard>, usize, Value, Scope),
+    // * compiled code waiting for the value of a call
+    Code(Activation),
+}
+
+/**
+ * * an active dynamic-wind, the winders form a list from the innermost one outwards
+ */
+pub struct Winder {
+    before: Value,
+    after: Value,
+    parent: Option<Rc<Winder>>,
+    depth: usize,
+}
+
+fn winder_depth(w: &Option<Rc<Winder>>) -> usize {
+    w.as_ref().map_or(0, |w| w.depth)
+}
+
+fn same_winder(a: &Option<Rc<Winder>>, b: &Option<Rc<Winder>>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+        (None, None) => true,
+        _ => false,
     }
-    
 }
 
 /**
- * * (lambda (xs ...) body) produce a procedure
+ * * an exception handler: a procedure installed by with-exception-handler,
+ * * or a guard which takes the condition to its own continuation
+ */
+enum Handler {
+    Procedure(Value),
+    Guard(Rc<Continuation>),
+}
+
+/**
+ * * the installed handlers, from the innermost one outwards
  */
-fn native_lambda(args: &[Value], env: Rc<RefCell<Env>>) -> Result<Value, RuntimeError> {
-    let params = match &args[0] {
-        Value::List(ns) => {
-
-            let params: Result<Vec<String>, RuntimeError> = ns.iter().map(|nv| {
-                match nv {
-                    Value::Symbol(s) => Ok(s.to_string()),
-                    _ => runtime_error!("Must provide symbol as parameter names: {:?}", nv),
+pub struct Handlers {
+    handler: Handler,
+    parent: Option<Rc<Handlers>>,
+}
+
+/**
+ * * a captured continuation: the stack, the dynamic-wind list and the handlers at the time of capture
+ */
+pub struct Continuation {
+    stack: Vec<Frame>,
+    winders: Option<Rc<Winder>>,
+    handlers: Option<Rc<Handlers>>,
+}
+
+impl Tail {
+    pub(crate) fn trace(&self, m: &mut Marker) {
+        match self {
+            Tail::Return(v) => m.value(v),
+            Tail::Eval(code, scope) => {
+                m.code(code);
+                scope.trace(m);
+            },
+            Tail::Execute(act) => act.trace(m),
+        }
+    }
+}
+
+impl Frame {
+    fn trace(&self, m: &mut Marker) {
+        match self {
+            Frame::If(then, otherwise, scope) => {
+                m.code(then);
+                m.code(otherwise);
+                scope.trace(m);
+            },
+            Frame::Combination(items, vals, _, scope) => {
+                m.body(items);
+                m.values(vals.iter());
+                scope.trace(m);
+            },
+            Frame::Body(body, _, scope) => {
+                m.body(body);
+                scope.trace(m);
+            },
+            Frame::Let(l, vals, scope) => {
+                l.trace(m);
+                m.values(vals.iter());
+                scope.trace(m);
+            },
+            Frame::Define(_, scope) | Frame::Set(_, scope) => scope.trace(m),
+            Frame::Map(p, arg_lists, results, _, env) => {
+                m.value(p);
+                m.values(arg_lists.iter().flatten().chain(results.iter()));
+                m.env(*env);
+            },
+            Frame::WindBefore(before, thunk, after, env) => {
+                m.values([before, thunk, after]);
+                m.env(*env);
+            },
+            Frame::WindBody(w, env) => {
+                trace_winders(&Some(w.clone()), m);
+                m.env(*env);
+            },
+            Frame::WindAfter(v) | Frame::Raised(v) => m.value(v),
+            Frame::Rewind(thunks, k, v, env) => {
+                for (thunk, w) in thunks {
+                    m.value(thunk);
+                    trace_winders(w, m);
                 }
-            }).collect();
+                m.value(&Value::Procedure(Function::Continuation(k.clone())));
+                m.value(v);
+                m.env(*env);
+            },
+            Frame::Handlers(h) => trace_handlers(h, m),
+            Frame::Guard(g, scope) => {
+                g.trace(m);
+                scope.trace(m);
+            },
+            Frame::GuardClause(g, _, v, scope) => {
+                g.trace(m);
+                m.value(v);
+                scope.trace(m);
+            },
+            Frame::Code(act) => act.trace(m),
+        }
+    }
+}
 
-            params.unwrap()
+fn trace_winders(w: &Option<Rc<Winder>>, m: &mut Marker) {
+    let mut w = w;
+    while let Some(winder) = w {
+        if !m.first_visit(winder) {
+            break;
         }
-        _ => runtime_error!("Must provide parameter lists in function parameter: {:?}", args),
-    };
+        m.values([&winder.before, &winder.after]);
+        w = &winder.parent;
+    }
+}
 
-    Ok(Value::Procedure(Function::Closure(params, args[1..].to_vec(), Env::new_child(env.clone()))))
+fn trace_handlers(h: &Option<Rc<Handlers>>, m: &mut Marker) {
+    let mut h = h;
+    while let Some(handlers) = h {
+        if !m.first_visit(handlers) {
+            break;
+        }
+        match &handlers.handler {
+            Handler::Procedure(p) => m.value(p),
+            Handler::Guard(k) => m.value(&Value::Procedure(Function::Continuation(k.clone()))),
+        }
+        h = &handlers.parent;
+    }
 }
 
+impl Continuation {
+    pub(crate) fn trace(&self, m: &mut Marker) {
+        for frame in &self.stack {
+            frame.trace(m);
+        }
+        trace_winders(&self.winders, m);
+        trace_handlers(&self.handlers, m);
+    }
+}
 
 /**
- * * (apply proc arg-list)
-*/
-fn native_apply(args: &[Value], env: Rc<RefCell<Env>>) -> Result<Value, RuntimeError> {
-    match &args[0] {
-        Value::Procedure(f) => proc_apply(f.clone(), &args[1..].to_vec(), env.clone()),
-        _ => runtime_error!("expect a procedure but got {:?}", args)
+ * * the evaluator, an explicit stack machine: nothing of the scheme program lives on
+ * * the rust stack, so continuations can be captured and re-entered any number of times
+ */
+pub struct Machine {
+    pub(super) stack: Vec<Frame>,
+    winders: Option<Rc<Winder>>,
+    handlers: Option<Rc<Handlers>>,
+    // * the env the machine was started in, handlers raised outside of a call run there
+    env: EnvRef,
+    // * the innermost located form being evaluated, errors are reported there
+    pub(super) span: Option<Rc<Span>>,
+    // * the expander of the evalator running the machine, eval expands its datum with it
+    expander: Option<Rc<RefCell<Expander>>>,
+}
+
+impl Machine {
+    fn new(env: EnvRef, expander: Option<Rc<RefCell<Expander>>>) -> Machine {
+        Machine {
+            stack: Vec::new(),
+            winders: None,
+            handlers: None,
+            env,
+            span: None,
+            expander,
+        }
+    }
+
+    /**
+     * * collect the garbage of the heap, everything the machine still uses and what extra marks is kept
+     * * returns how many objects were freed
+     */
+    pub(crate) fn collect(&self, extra: impl FnOnce(&mut Marker)) -> usize {
+        gc::collect(|m| {
+            for frame in &self.stack {
+                frame.trace(m);
+            }
+            trace_winders(&self.winders, m);
+            trace_handlers(&self.handlers, m);
+            m.env(self.env);
+            extra(m);
+        })
+    }
+
+    /**
+     * * run until the stack is empty, every error is raised as a condition to the installed handlers
+     * ! the error only leaves the machine once no handler is left
+     */
+    fn run(&mut self, mut step: Tail) -> Result<Value, RuntimeError> {
+        loop {
+            // * between two steps everything the machine uses is on its stack or in the step
+            if gc::should_collect() {
+                self.collect(|m| step.trace(m));
+            }
+            let res = match step {
+                Tail::Eval(value, env) => self.eval(value, env),
+                Tail::Execute(act) => self.execute(act),
+                Tail::Return(v) => match self.stack.pop() {
+                    Some(frame) => self.resume(frame, v),
+                    None => return Ok(v),
+                },
+            };
+            step = match res {
+                Ok(tail) => tail,
+                Err(e) => {
+                    let span = self.span.clone();
+                    self.raise(e.into_condition(), false).map_err(|e| e.at(span))?
+                },
+            };
+        }
+    }
+
+    /**
+     * * evaluate resolved code, a combination evaluates the operator and all the operands
+     * * left to right and applies the procedure, the other forms each have their own rule
+     */
+    fn eval(&mut self, code: Rc<Code>, scope: Scope) -> Result<Tail, RuntimeError> {
+        match &*code {
+            Code::Const(v) => Ok(Tail::Return(v.clone())),
+            Code::Variable(var) => Ok(Tail::Return(scope.get(*var)?)),
+            Code::If(test, then, otherwise) => {
+                self.stack.push(Frame::If(then.clone(), otherwise.clone(), scope));
+                Ok(Tail::Eval(test.clone(), scope))
+            },
+            Code::Define(var, expr) => {
+                self.stack.push(Frame::Define(*var, scope));
+                Ok(Tail::Eval(expr.clone(), scope))
+            },
+            Code::Set(var, expr) => {
+                self.stack.push(Frame::Set(*var, scope));
+                Ok(Tail::Eval(expr.clone(), scope))
+            },
+            Code::Lambda(lambda) => Ok(Tail::Return(Value::Procedure(Function::Closure(Rc::new(Closure {
+                lambda: lambda.clone(),
+                locals: scope.locals,
+                globals: scope.globals,
+            }))))),
+            Code::Let(l) => match l.inits.first() {
+                Some(init) => {
+                    let init = init.clone();
+                    self.stack.push(Frame::Let(l.clone(), Vec::new(), scope));
+                    Ok(Tail::Eval(init, scope))
+                },
+                None => self.eval_body(l.body.clone(), scope.enter(Vec::new(), &l.names)),
+            },
+            Code::Guard(g) => self.guard(g.clone(), scope),
+            Code::Combination(items) => {
+                let span = self.span.clone();
+                self.combination(items.clone(), Vec::with_capacity(items.len()), span, scope)
+            },
+            Code::Located(span, inner) => {
+                self.span = Some(span.clone());
+                self.eval(inner.clone(), scope)
+            },
+            Code::Malformed(msg) => Err(RuntimeError::new(msg.clone())),
+        }
+    }
+
+    /**
+     * * hand the value v to the frame popped from the top of the stack
+     */
+    pub(super) fn resume(&mut self, frame: Frame, v: Value) -> Result<Tail, RuntimeError> {
+        match frame {
+            Frame::If(then, otherwise, scope) => match v {
+                Value::Boolean(false) => Ok(Tail::Eval(otherwise, scope)),
+                _ => Ok(Tail::Eval(then, scope)),
+            },
+            Frame::Combination(items, mut values, span, scope) => {
+                values.push(v);
+                self.combination(items, values, span, scope)
+            },
+            Frame::Body(body, next, scope) => {
+                if next + 1 < body.len() {
+                    self.stack.push(Frame::Body(body.clone(), next + 1, scope));
+                }
+                Ok(Tail::Eval(body[next].clone(), scope))
+            },
+            Frame::Let(l, mut values, scope) => {
+                values.push(v);
+                if values.len() < l.inits.len() {
+                    let next = l.inits[values.len()].clone();
+                    self.stack.push(Frame::Let(l, values, scope));
+                    return Ok(Tail::Eval(next, scope));
+                }
+                self.eval_body(l.body.clone(), scope.enter(values, &l.names))
+            },
+            Frame::Define(var, scope) => {
+                scope.define(var, &v)?;
+                Ok(Tail::Return(v))
+            },
+            Frame::Set(var, scope) => {
+                scope.set(var, &v)?;
+                Ok(Tail::Return(Value::Unit))
+            },
+            Frame::Map(p, arg_lists, mut results, finish, env) => {
+                results.push(v);
+                if results.len() < arg_lists.len() {
+                    let next = results.len();
+                    self.stack.push(Frame::Map(p.clone(), arg_lists.clone(), results, finish, env));
+                    return self.apply_value(&p, &arg_lists[next], env);
+                }
+                Ok(Tail::Return(finish(results)))
+            },
+            Frame::WindBefore(before, thunk, after, env) => {
+                let winder = Rc::new(Winder {
+                    before,
+                    after,
+                    depth: winder_depth(&self.winders) + 1,
+                    parent: self.winders.take(),
+                });
+                self.winders = Some(winder.clone());
+                self.stack.push(Frame::WindBody(winder, env));
+                self.apply_value(&thunk, &[], env)
+            },
+            Frame::WindBody(winder, env) => {
+                self.winders = winder.parent.clone();
+                self.stack.push(Frame::WindAfter(v));
+                self.apply_value(&winder.after, &[], env)
+            },
+            Frame::WindAfter(v) => Ok(Tail::Return(v)),
+            Frame::Rewind(steps, k, v, env) => self.rewind(steps, k, v, env),
+            Frame::Handlers(handlers) => {
+                self.handlers = handlers;
+                Ok(Tail::Return(v))
+            },
+            Frame::Raised(obj) => runtime_error!("exception handler returned from a non-continuable raise of {:?}", obj),
+            Frame::Guard(g, scope) => {
+                let scope = scope.enter(vec![v.clone()], &g.var);
+                self.guard_clause(g, 0, v, scope)
+            },
+            Frame::GuardClause(g, index, condition, scope) => match (v, &g.clauses[index]) {
+                (Value::Boolean(false), _) => self.guard_clause(g, index + 1, condition, scope),
+                (v, Clause::Test(_, body)) if body.is_empty() => Ok(Tail::Return(v)),
+                (v, Clause::Arrow(_, receiver)) => {
+                    // * the receiver is evaluated like the operator of a call with the value of the test
+                    let call: Body = Rc::new([receiver.clone(), Rc::new(Code::Const(v))]);
+                    self.stack.push(Frame::Combination(call, Vec::new(), self.span.clone(), scope));
+                    Ok(Tail::Eval(receiver.clone(), scope))
+                },
+                (_, Clause::Test(_, body) | Clause::Else(body)) => self.eval_body(body.clone(), scope),
+            },
+            Frame::Code(mut act) => {
+                act.push(v);
+                self.execute(act)
+            },
+        }
+    }
+
+    /**
+     * * evaluate the rest of a combination and apply it, constants and variables need no step of their own
+     */
+    fn combination(&mut self, items: Body, mut values: Vec<Value>, span: Option<Rc<Span>>, scope: Scope) -> Result<Tail, RuntimeError> {
+        while let Some(next) = items.get(values.len()) {
+            match &**next {
+                Code::Const(v) => values.push(v.clone()),
+                Code::Variable(var) => values.push(scope.get(*var)?),
+                _ => {
+                    let next = next.clone();
+                    self.stack.push(Frame::Combination(items, values, span, scope));
+                    return Ok(Tail::Eval(next, scope));
+                },
+            }
+        }
+
+        self.span = span;
+        match &values[0] {
+            Value::Procedure(f) => self.apply(f, &values[1..], scope.globals),
+            _ => runtime_error!("first entry must be procedure: {:?}", values),
+        }
+    }
+
+    /**
+     * * (p_name arg1 arg2 ...) apply a procedure to already evaluated arguments
+     * ! the body of a closure is handed back to the machine instead of being evaluated here
+     */
+    pub(super) fn apply(&mut self, func: &Function, args: &[Value], env: EnvRef) -> Result<Tail, RuntimeError> {
+        match func {
+            Function::Native(op) => Ok(Tail::Return(op(args)?)),
+            Function::TailNative(op) => op(args, env, self),
+            Function::Closure(c) => {
+                let args = c.lambda.params.arguments(args.to_vec())?;
+                let scope = Scope { locals: c.locals, globals: c.globals }.enter(args, &c.lambda.names);
+                self.eval_body(c.lambda.body.clone(), scope)
+            },
+            Function::Compiled(c) => Ok(Tail::Execute(Activation::call(&c.template, c.locals, c.globals, args.to_vec())?)),
+            Function::Continuation(k) => {
+                let v = match args {
+                    [] => Value::Unit,
+                    [v] => v.clone(),
+                    _ => runtime_error!("a continuation expects at most one value but got: {:?}", args),
+                };
+                self.throw(k.clone(), v, env)
+            },
+        }
+    }
+
+    pub(crate) fn apply_value(&mut self, p: &Value, args: &[Value], env: EnvRef) -> Result<Tail, RuntimeError> {
+        match p {
+            Value::Procedure(f) => self.apply(f, args, env),
+            _ => runtime_error!("expect a procedure but got {:?}", p),
+        }
+    }
+
+    /**
+     * * apply p to every argument list in turn and combine the results with finish
+     * * used by natives like vector-map, the calls run on the machine like any other
+     */
+    pub(crate) fn map(&mut self, p: Value, arg_lists: Vec<Vec<Value>>, finish: fn(Vec<Value>) -> Value, env: EnvRef) -> Result<Tail, RuntimeError> {
+        if arg_lists.is_empty() {
+            return Ok(Tail::Return(finish(Vec::new())));
+        }
+        let first = arg_lists[0].clone();
+        self.stack.push(Frame::Map(p.clone(), Rc::new(arg_lists), Vec::new(), finish, env));
+        self.apply_value(&p, &first, env)
+    }
+
+    /**
+     * * evaluate every body form but the last one, which is evaluated in tail position
+     */
+    fn eval_body(&mut self, body: Body, scope: Scope) -> Result<Tail, RuntimeError> {
+        match body.len() {
+            0 => Ok(Tail::Return(Value::Unit)),
+            _ => self.resume(Frame::Body(body, 0, scope), Value::Unit),
+        }
+    }
+
+    fn capture(&self) -> Value {
+        Value::Procedure(Function::Continuation(Rc::new(Continuation {
+            stack: self.stack.clone(),
+            winders: self.winders.clone(),
+            handlers: self.handlers.clone(),
+        })))
+    }
+
+    /**
+     * * hand obj to the innermost handler, which runs with the outer handlers installed
+     * * the value of a handler is returned to raise-continuable, returning to raise is an error
+     */
+    pub(crate) fn raise(&mut self, obj: Value, continuable: bool) -> Result<Tail, RuntimeError> {
+        let handlers = match self.handlers.clone() {
+            Some(h) => h,
+            None => return Err(RuntimeError::raised(obj)),
+        };
+
+        match &handlers.handler {
+            Handler::Guard(k) => self.throw(k.clone(), obj, self.env),
+            Handler::Procedure(p) => {
+                self.stack.push(Frame::Handlers(Some(handlers.clone())));
+                if !continuable {
+                    self.stack.push(Frame::Raised(obj.clone()));
+                }
+                self.handlers = handlers.parent.clone();
+                self.apply_value(p, &[obj], self.env)
+            },
+        }
+    }
+
+    /**
+     * * call thunk with handler installed in front of the current handlers
+     */
+    pub(crate) fn with_exception_handler(&mut self, handler: &Value, thunk: &Value, env: EnvRef) -> Result<Tail, RuntimeError> {
+        if !matches!(handler, Value::Procedure(_)) {
+            runtime_error!("with-exception-handler expects a procedure as handler but got: {:?}", handler);
+        }
+
+        self.stack.push(Frame::Handlers(self.handlers.clone()));
+        self.handlers = Some(Rc::new(Handlers {
+            handler: Handler::Procedure(handler.clone()),
+            parent: self.handlers.take(),
+        }));
+        self.apply_value(thunk, &[], env)
     }
 
+    /**
+     * * evaluate the body of a guard, a condition raised in it is bound to the variable and tried against the clauses
+     */
+    fn guard(&mut self, g: Rc<Guard>, scope: Scope) -> Result<Tail, RuntimeError> {
+        // * the handler goes back to the continuation of the guard, with one more frame for the clauses
+        let mut stack = self.stack.clone();
+        stack.push(Frame::Guard(g.clone(), scope));
+        let k = Rc::new(Continuation {
+            stack,
+            winders: self.winders.clone(),
+            handlers: self.handlers.clone(),
+        });
+
+        self.stack.push(Frame::Handlers(self.handlers.clone()));
+        self.handlers = Some(Rc::new(Handlers {
+            handler: Handler::Guard(k),
+            parent: self.handlers.take(),
+        }));
+        self.eval_body(g.body.clone(), scope.enter(Vec::new(), &g.names))
+    }
+
+    /**
+     * * try the guard clauses from index on, a condition no clause accepts is raised again
+     * ! the condition is re-raised in the dynamic environment of the guard, not of the original raise
+     */
+    fn guard_clause(&mut self, g: Rc<Guard>, index: usize, condition: Value, scope: Scope) -> Result<Tail, RuntimeError> {
+        let test = match g.clauses.get(index) {
+            Some(Clause::Else(body)) => return self.eval_body(body.clone(), scope),
+            Some(Clause::Test(test, _) | Clause::Arrow(test, _)) => test.clone(),
+            None => return self.raise(condition, true),
+        };
+        self.stack.push(Frame::GuardClause(g, index, condition, scope));
+        Ok(Tail::Eval(test, scope))
+    }
+
+    /**
+     * * pass v to the continuation k: leave the dynamic-winds which are not active in k
+     * * (innermost first) and enter those which are only active in k (outermost first)
+     */
+    fn throw(&mut self, k: Rc<Continuation>, v: Value, env: EnvRef) -> Result<Tail, RuntimeError> {
+        let mut afters = Vec::new();
+        let mut befores = Vec::new();
+        let (mut from, mut to) = (self.winders.clone(), k.winders.clone());
+
+        while winder_depth(&from) > winder_depth(&to) {
+            let w = from.unwrap();
+            afters.push((w.after.clone(), w.parent.clone()));
+            from = w.parent.clone();
+        }
+        while winder_depth(&to) > winder_depth(&from) {
+            let w = to.unwrap();
+            befores.push((w.before.clone(), w.parent.clone()));
+            to = w.parent.clone();
+        }
+        while !same_winder(&from, &to) {
+            let (f, t) = (from.unwrap(), to.unwrap());
+            afters.push((f.after.clone(), f.parent.clone()));
+            befores.push((t.before.clone(), t.parent.clone()));
+            from = f.parent.clone();
+            to = t.parent.clone();
+        }
+
+        // * steps are popped from the back
+        let steps = befores.into_iter().chain(afters.into_iter().rev()).collect();
+        self.rewind(steps, k, v, env)
+    }
+
+    fn rewind(&mut self, mut steps: Vec<(Value, Option<Rc<Winder>>)>, k: Rc<Continuation>, v: Value, env: EnvRef) -> Result<Tail, RuntimeError> {
+        match steps.pop() {
+            Some((thunk, winders)) => {
+                self.winders = winders;
+                self.stack.push(Frame::Rewind(steps, k, v, env));
+                self.apply_value(&thunk, &[], env)
+            },
+            None => {
+                self.stack = k.stack.clone();
+                self.winders = k.winders.clone();
+                self.handlers = k.handlers.clone();
+                Ok(Tail::Return(v))
+            },
+        }
+    }
 }
-/** 
- * *(p_name arg1 arg2 ...) evaluate the function body after all args evalualted in current env
- * ! note that we only evaluate the argument in current env when a closure is applied
- * ! native function don't require the argument evaluation, they should already be a valid value
-*/
-fn proc_apply(func: Function, apply_args: &[Value], env: Rc<RefCell<Env>>) -> Result<Value, RuntimeError> {
-    // let args = apply_args[..].to_vec();
-    // let eval_args: Vec<Value> = args.iter().map(|a| {
-    //     eval_value(a, env.clone()).unwrap()
-    // }).collect();
-    match &func {
-        Function::Native(op) => {
-            op(&apply_args[..], env)
+
+/**
+ * * the expression building a quasiquote template
+ */
+pub(super) fn quasiquote(template: &Value) -> Result<Value, RuntimeError> {
+    Ok(quasi(template, 1)?.expr())
+}
+
+/**
+ * * if the value is the list (keyword x), return x
+ */
+fn quasi_form(value: &Value, keyword: &str) -> Option<Value> {
+    match value {
+        Value::Pair(p) if p.car() == Value::Symbol(Symbol::intern(keyword)) => match p.cdr() {
+            Value::Pair(rest) if matches!(rest.cdr(), Value::Nil) => Some(rest.car()),
+            _ => None,
         },
-        Function::Closure(params, body, closure_env) => {
-            let closure_env = closure_env.clone();
-            let new_env = Env::new_child(closure_env);
-            for (param, arg) in params.iter().zip(apply_args.iter()) {
-                new_env.borrow_mut().define(param, &eval_value(&arg, env.clone()).unwrap()).unwrap();
-            }
+        _ => None,
+    }
+}
+
+/**
+ * * a part of a quasiquote template: a constant datum, or an expression building it
+ */
+enum Quasi {
+    Const(Value),
+    Expr(Value),
+}
 
-            eval_values(body, new_env)
+impl Quasi {
+    fn expr(self) -> Value {
+        match self {
+            Quasi::Const(v) => Value::list(vec![Value::Symbol(Symbol::intern("quote")), v]),
+            Quasi::Expr(e) => e,
         }
+    }
+}
 
-        _ => runtime_error!("expect procedure apply but got: {:?}", apply_args),
+// * the natives are put in operator position as values so no binding can capture them
+fn quasi_call(op: ValueOperation, args: Vec<Value>) -> Quasi {
+    let mut call = vec![Value::Procedure(Function::Native(op))];
+    call.extend(args);
+    Quasi::Expr(Value::list(call))
+}
+
+fn quasi_cons(car: Quasi, cdr: Quasi) -> Quasi {
+    match (car, cdr) {
+        (Quasi::Const(a), Quasi::Const(d)) => Quasi::Const(Value::cons(a, d)),
+        (a, d) => quasi_call(list::native_cons, vec![a.expr(), d.expr()]),
     }
-} 
+}
 
-/** 
- * * (let ([n1 v1] ...) body)
-*/
-fn native_let(args: &[Value], env: Rc<RefCell<Env>>) -> Result<Value, RuntimeError> {
-    let new_env = Env::new_child(env.clone());
-    let eval_nv: Result<(), RuntimeError> = match &args[0] {
-        Value::List(assigns) => {
-            for assign in assigns {
-                match assign {
-                    Value::List(nv_pair) => {
-                        assert!(nv_pair.len() == 2);
-                        match &nv_pair[0] {
-                            Value::Symbol(ref s) => {
-                                new_env.borrow_mut().define(s, &eval_value(&nv_pair[1], env.clone()).unwrap());
-                            },
-                            _ => runtime_error!("invalid let syntax: {:?}", nv_pair),
+fn quasi_nested(keyword: &str, inner: Quasi) -> Quasi {
+    quasi_cons(Quasi::Const(Value::Symbol(Symbol::intern(keyword))), quasi_cons(inner, Quasi::Const(Value::Nil)))
+}
+
+// * a run of elements as one list, (list e ...) unless every one of them is constant
+fn quasi_run(run: Vec<Quasi>) -> Quasi {
+    match run.iter().all(|q| matches!(q, Quasi::Const(_))) {
+        true => Quasi::Const(Value::list(run.into_iter().map(|q| match q {
+            Quasi::Const(v) => v,
+            Quasi::Expr(_) => unreachable!("every element is constant"),
+        }).collect())),
+        false => quasi_call(list::native_list, run.into_iter().map(Quasi::expr).collect()),
+    }
+}
+
+/**
+ * * rewrite a quasiquote template at the given nesting depth
+ * * every nested quasiquote goes one level deeper and every unquote one level out,
+ * * only the unquotes which reach level 0 are evaluated
+ */
+fn quasi(template: &Value, depth: usize) -> Result<Quasi, RuntimeError> {
+    if let Some(inner) = quasi_form(template, "unquote") {
+        return match depth {
+            1 => Ok(Quasi::Expr(inner)),
+            _ => Ok(quasi_nested("unquote", quasi(&inner, depth - 1)?)),
+        };
+    }
+    if let Some(inner) = quasi_form(template, "quasiquote") {
+        return Ok(quasi_nested("quasiquote", quasi(&inner, depth + 1)?));
+    }
+
+    match template {
+        Value::Pair(_) => {
+            // * walked pair by pair so that an unquote in the tail, `(1 . ,x), is found as well
+            let mut items = Vec::new();
+            let mut tail = template.clone();
+            while let Value::Pair(p) = tail.clone() {
+                if !items.is_empty() && (quasi_form(&tail, "unquote").is_some() || quasi_form(&tail, "quasiquote").is_some()) {
+                    break;
+                }
+                items.push(p.car());
+                tail = p.cdr();
+            }
+
+            // * the elements in runs between the spliced lists, all joined by one append
+            let mut parts = Vec::new();
+            let mut run = Vec::new();
+            for item in items {
+                match quasi_form(&item, "unquote-splicing") {
+                    Some(inner) if depth == 1 => {
+                        if !run.is_empty() {
+                            parts.push(quasi_run(mem::take(&mut run)));
                         }
+                        parts.push(Quasi::Expr(inner));
                     },
-                    _ => runtime_error!("invalid let define list: {:?}", assign)
+                    Some(inner) => run.push(quasi_nested("unquote-splicing", quasi(&inner, depth - 1)?)),
+                    None => run.push(quasi(&item, depth)?),
                 }
             }
-            Ok(())
+            if !run.is_empty() {
+                parts.push(quasi_run(run));
+            }
+            parts.push(quasi(&tail, depth)?);
+
+            match parts.as_slice() {
+                [Quasi::Const(items), Quasi::Const(tail)] => {
+                    let (items, _) = items.parts();
+                    Ok(Quasi::Const(Value::list_with_tail(items, tail.clone())))
+                },
+                _ => Ok(quasi_call(list::native_append, parts.into_iter().map(Quasi::expr).collect())),
+            }
         },
-        _ => runtime_error!("let-define requires but got: {:?}", args),
+        Value::Vector(items) => match quasi(&Value::list(items.borrow().clone()), depth)? {
+            Quasi::Const(_) => Ok(Quasi::Const(template.clone())),
+            items => Ok(quasi_call(vector::native_list_to_vector, vec![items.expr()])),
+        },
+        _ => Ok(Quasi::Const(template.clone())),
+    }
+}
+
+/**
+ * * (apply proc arg ... arg-list)
+ * * the last argument must be a list, which is spread after the other arguments
+*/
+fn native_apply(args: &[Value], env: EnvRef, m: &mut Machine) -> Result<Tail, RuntimeError> {
+    let (f, rest) = match args.split_first() {
+        Some((Value::Procedure(f), rest)) => (f, rest),
+        _ => runtime_error!("expect a procedure but got {:?}", args),
     };
 
-    eval_nv.unwrap();
+    let mut apply_args = rest.to_vec();
+    if let Some(tail) = apply_args.pop() {
+        apply_args.extend(list_arg("apply", &tail)?);
+    }
 
-    eval_value(&args[1], new_env)
+    m.apply(f, &apply_args, env)
+}
+
+/**
+ * * (call-with-current-continuation proc) call proc with the continuation of this call
+ */
+fn native_call_cc(args: &[Value], env: EnvRef, m: &mut Machine) -> Result<Tail, RuntimeError> {
+    match args {
+        [p] => {
+            let k = m.capture();
+            m.apply_value(p, &[k], env)
+        },
+        _ => runtime_error!("call/cc expects exactly one procedure but got: {:?}", args),
+    }
+}
+
+/**
+ * * (dynamic-wind before thunk after)
+ * * before runs whenever control enters the extent of thunk and after whenever it leaves,
+ * * be it by returning or by a continuation
+ */
+fn native_dynamic_wind(args: &[Value], env: EnvRef, m: &mut Machine) -> Result<Tail, RuntimeError> {
+    match args {
+        [before, thunk, after] => {
+            m.stack.push(Frame::WindBefore(before.clone(), thunk.clone(), after.clone(), env));
+            m.apply_value(before, &[], env)
+        },
+        _ => runtime_error!("dynamic-wind expects three thunks but got: {:?}", args),
+    }
+}
+
+fn equivalence(name: &str, args: &[Value], same: fn(&Value, &Value) -> bool) -> Result<Value, RuntimeError> {
+    match args {
+        [a, b] => Ok(Value::Boolean(same(a, b))),
+        _ => runtime_error!("{} expects exactly two arguments but got: {:?}", name, args),
+    }
+}
+
+fn native_is_eq(args: &[Value]) -> Result<Value, RuntimeError> {
+    equivalence("eq?", args, Value::is_eq)
+}
+
+fn native_is_eqv(args: &[Value]) -> Result<Value, RuntimeError> {
+    equivalence("eqv?", args, Value::is_eqv)
+}
+
+fn native_is_equal(args: &[Value]) -> Result<Value, RuntimeError> {
+    equivalence("equal?", args, Value::eq)
+}
+
+/**
+ * * (display v) (write v) (newline) print to the standard output
+ */
+fn native_display(args: &[Value]) -> Result<Value, RuntimeError> {
+    match args {
+        [v] => print!("{}", v),
+        _ => runtime_error!("display expects exactly one argument but got: {:?}", args),
+    }
+    Ok(Value::Unit)
+}
+
+fn native_write(args: &[Value]) -> Result<Value, RuntimeError> {
+    match args {
+        [v] => print!("{:?}", v),
+        _ => runtime_error!("write expects exactly one argument but got: {:?}", args),
+    }
+    Ok(Value::Unit)
+}
+
+fn native_newline(args: &[Value]) -> Result<Value, RuntimeError> {
+    if !args.is_empty() {
+        runtime_error!("newline expects no argument but got: {:?}", args);
+    }
+    println!();
+    Ok(Value::Unit)
+}
+
+/**
+ * * (eval expr) expand an already evaluated value as a toplevel form and evaluate it in the env the machine started in
+ * * the forms of a begin run in order on this machine, the last one is in tail position
+ */
+fn native_eval(args: &[Value], env: EnvRef, m: &mut Machine) -> Result<Tail, RuntimeError> {
+    let expr = match args {
+        [expr] => expr,
+        _ => runtime_error!("eval expects exactly one argument but got: {:?}", args),
+    };
+    let expander = match &m.expander {
+        Some(expander) => expander.clone(),
+        // * a machine started without an evalator only knows the core forms
+        None => return Ok(Tail::Eval(address::address(expr), Scope::global(env))),
+    };
+    let forms = expander.borrow_mut().expand_toplevel(expr)?;
+    let env = m.env;
+    m.eval_body(forms.iter().map(address::address).collect(), Scope::global(env))
+}
+
+impl Clone for Function {
+    fn clone(&self) -> Function {
+        // self.clone()
+        match self {
+            Function::Native(op) => Function::Native(*op),
+            Function::TailNative(op) => Function::TailNative(*op),
+            Function::Closure(c) => Function::Closure(c.clone()),
+            Function::Compiled(c) => Function::Compiled(c.clone()),
+            Function::Continuation(k) => Function::Continuation(k.clone()),
+        }
+    }
+}
 
+// * what is left to print, the pairs and vectors in a value are taken apart into these
+enum Piece {
+    Value(Value),
+    Text(&'static str),
+    // * the pair or vector at this address is printed completely
+    Leave(usize),
 }
 
 /**
- * * a general arithmatic native function for arithmatic operation
- * ! args must be all Value::Integer, otherwise an runtime error is reported
+ * * print v with the `write` representation or the `display` one
+ * ! pairs and vectors are printed from a work list, data nested deeply through the car does not recurse
+ * ! a pair or vector met again inside itself prints as ..., so data containing itself is printed once
  */
-fn native_arithmatic(args: &[Value], env: Rc<RefCell<Env>>, f: fn(i1: usize, i2: usize) -> usize) -> Result<Value, RuntimeError> {
-    let args: Result<Vec<usize>, RuntimeError> = args.iter().map(|x| {
-        let v = eval_value(x, env.clone()).unwrap();
-        match v {
-            Value::Integer(i) => Ok(i),
-            _ => runtime_error!("invalid arguments for add: {:?}", v),
+fn print(v: &Value, f: &mut fmt::Formatter<'_>, write: bool) -> fmt::Result {
+    let mut pending = vec![Piece::Value(v.clone())];
+    // * the pairs and vectors being printed, shared data that is no cycle still prints in full
+    let mut open = HashSet::new();
+    while let Some(piece) = pending.pop() {
+        let address = match &piece {
+            Piece::Value(Value::Pair(p)) => Some(Rc::as_ptr(p) as *const () as usize),
+            Piece::Value(Value::Vector(items)) => Some(Rc::as_ptr(items) as *const () as usize),
+            _ => None,
+        };
+        if let Some(address) = address {
+            if !open.insert(address) {
+                f.write_str("...")?;
+                continue;
+            }
+            pending.push(Piece::Leave(address));
         }
-    }).collect();
-
-    //  ! we want to do arithmatic with arg[0] as initial and go over the vec
-    //  ! we need to advance the iterator one step so that we do that
-    //  ! the old way is directly call fold with args[0] as initial and that will compute args[0] twice
-    let args = args.unwrap();
-    let mut args_it = args.iter();
-    args_it.next();
-    let res = args_it.fold(args[0], |acc, x| {
-        f(acc, *x)
-    });
-
-    Ok(Value::Integer(res))
-
+        match piece {
+            Piece::Text(text) => f.write_str(text)?,
+            Piece::Leave(address) => {
+                open.remove(&address);
+            },
+            Piece::Value(v @ Value::Pair(_)) => {
+                let (values, tail) = v.parts();
+                pending.push(Piece::Text(")"));
+                match tail {
+                    Value::Nil => {},
+                    // * a circular list, printing stops where the cycle was found
+                    Value::Pair(_) => pending.push(Piece::Text(" ...")),
+                    tail => {
+                        pending.push(Piece::Value(tail));
+                        pending.push(Piece::Text(" . "));
+                    },
+                }
+                items(&mut pending, values);
+                pending.push(Piece::Text("("));
+            },
+            Piece::Value(Value::Vector(values)) => {
+                pending.push(Piece::Text(")"));
+                items(&mut pending, values.borrow().clone());
+                pending.push(Piece::Text("#("));
+            },
+            Piece::Value(v) if write => write_atom(&v, f)?,
+            Piece::Value(v) => display_atom(&v, f)?,
+        }
+    }
+    Ok(())
 }
 
-fn native_add(args: &[Value], env: Rc<RefCell<Env>>) -> Result<Value, RuntimeError> {
-    native_arithmatic(args, env, |a, b| {
-        a + b
-    })
+// * the values separated by spaces, pushed back to front so they are printed in order
+fn items(pending: &mut Vec<Piece>, values: Vec<Value>) {
+    for (i, v) in values.into_iter().enumerate().rev() {
+        pending.push(Piece::Value(v));
+        if i > 0 {
+            pending.push(Piece::Text(" "));
+        }
+    }
 }
 
-fn native_times(args: &[Value], env: Rc<RefCell<Env>>) -> Result<Value, RuntimeError> {
-    native_arithmatic(args, env, |a, b| {
-        a * b
-    })
+fn display_atom(v: &Value, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match v {
+        Value::Unit => write!(f, "()"),
+        Value::Symbol(s) => write!(f, "{}", s),
+        Value::Number(n) => write!(f, "{}", n),
+        Value::Nil => write!(f, "()"),
+        Value::Procedure(_) => {
+            write!(f, "#procedure")
+        },
+        Value::Error(e) => write!(f, "#<error {}>", e),
+        Value::Boolean(b) => {
+            write!(f, "#{}", b)
+        },
+        Value::String(s) => write!(f, "{}", s),
+        Value::Char(c) => write!(f, "{}", c),
+        Value::Pair(_) | Value::Vector(_) => unreachable!("print takes pairs and vectors apart"),
+    }
 }
-/*
- * * (define name value)\(define (p_name params) body)
- * args must be a vec with length greater than 2
- */
-fn native_define(args: &[Value], env: Rc<RefCell<Env>>) -> Result<Value, RuntimeError> {
-    assert!(args.len() >= 2);
-
-    let (name, val) = match &args[0] {
-        Value::Symbol(n) => {
-            let val = eval_value(&args[1], env.clone()).unwrap();
-            (n, val)
-        }
-        Value::List(list) => {
-            assert!(list.len() >= 2);
-            match &list[0] {
-                Value::Symbol(n) => {
-                    let res: Result<Vec<String>, RuntimeError> = (&list[1..]).iter().map(|x| {
-                        match x {
-                            Value::Symbol(s) => Ok(s.clone()),
-                            _ => runtime_error!("unexpected argument in define: {:?}", x),
-                        }
-                    }).collect();
 
-                    let params = res.unwrap();
-                    let body = (&args[1..]).to_vec();
-                    let val = Value::Procedure(Function::Closure(params, body, env.clone()));
-                    (n, val)
-                },
-                _ => runtime_error!("must supply a symbol as define name: {:?}", list),
+// * strings and chars are written as literals, the other atoms print as for display
+fn write_atom(v: &Value, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match v {
+        Value::String(s) => {
+            let mut res = String::from("\"");
+            for c in s.chars() {
+                match c {
+                    '"' => res.push_str("\\\""),
+                    '\\' => res.push_str("\\\\"),
+                    '\n' => res.push_str("\\n"),
+                    '\t' => res.push_str("\\t"),
+                    '\r' => res.push_str("\\r"),
+                    c if c.is_control() => res.push_str(&format!("\\x{:x};", c as u32)),
+                    c => res.push(c),
+                }
             }
+            res.push('"');
+            write!(f, "{}", res)
         },
-        _ => runtime_error!("invalid define: {:?}", args),
-    };
-
-    env.borrow_mut().define(&name, &val).unwrap();
-    Ok(val)
-}
-
-impl Clone for Function {
-    fn clone(&self) -> Function {
-        // self.clone()
-        match self {
-            Function::Native(op) => Function::Native(op.clone()),
-            Function::Closure(params, body, env) => Function::Closure(params.clone(), body.clone(), env.clone()),
-        }
+        Value::Char(c) => {
+            match CHAR_NAMES.iter().find(|(_, nc)| nc == c) {
+                Some((name, _)) => write!(f, "#\\{}", name),
+                None if c.is_control() => write!(f, "#\\x{:x}", *c as u32),
+                None => write!(f, "#\\{}", c),
+            }
+        },
+        _ => display_atom(v, f),
     }
 }
 
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Value::Unit => write!(f, "()"),
-            Value::Symbol(s) => write!(f, "{}", s),
-            Value::Integer(i) => write!(f, "{}", i),
-            Value::List(values) => {
-                let strs: Vec<String> = values.iter().map(|v| {
-                    format!("{}", v)
-                }).collect();
-
-                write!(f, "({})", &strs.join(" "))
-            },
-            Value::Procedure(_) => {
-                write!(f, "#procedure")
-            },
-            Value::Boolean(b) => {
-                write!(f, "#{}", b)
-            }
-        }
+        print(self, f, false)
     }
 }
 
+/**
+ * * Display is the `display` representation, Debug is the `write` representation
+ * * which can be read back: strings are quoted and escaped, chars written as #\\ literals
+ */
 impl fmt::Debug for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Value::Symbol(s) => write!(f, "{}", s),
-            Value::Integer(i) => write!(f, "{}", i),
-            Value::List(values) => {
-                let strs: Vec<String> = values.iter().map(|v| {
-                    format!("{:?}", v)
-                }).collect();
-
-                write!(f, "({})", &strs.join(" "))
-            }
-            _ => write!(f, "{}", self)
-        }
+        print(self, f, true)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(i: i64) -> Value {
+        Value::Number(Number::Integer(i))
+    }
+}
+
+impl From<f64> for Value {
+    fn from(f: f64) -> Value {
+        Value::Number(Number::Real(f))
     }
 }
 
 impl Value {
 
+    /**
+     * * eq?: the same symbol, the same object, or the same atom
+     * * symbols compare by their id, pairs, vectors, strings and procedures by identity
+     * ! only fixnums are atoms among the numbers, big, rational and real numbers are never eq? like boxed numbers
+     */
+    pub fn is_eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Number(Number::Integer(a)), Value::Number(Number::Integer(b))) => a == b,
+            (Value::Number(_), Value::Number(_)) => false,
+            _ => self.is_eqv(other),
+        }
+    }
+
+    /**
+     * * eqv?: eq? which also compares every number by exactness and value, reals by their bits
+     * ! a string is an object, two strings with the same characters are only equal?
+     */
+    pub fn is_eqv(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Symbol(a), Value::Symbol(b)) => a == b,
+            (Value::Pair(a), Value::Pair(b)) => Rc::ptr_eq(a, b),
+            (Value::Vector(a), Value::Vector(b)) => Rc::ptr_eq(a, b),
+            (Value::Error(a), Value::Error(b)) => Rc::ptr_eq(a, b),
+            (Value::String(a), Value::String(b)) => Rc::ptr_eq(a, b),
+            (Value::Number(a), Value::Number(b)) => a.is_eqv(b),
+            (Value::Procedure(a), Value::Procedure(b)) => a == b,
+            (Value::Char(a), Value::Char(b)) => a == b,
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::Nil, Value::Nil) | (Value::Unit, Value::Unit) => true,
+            _ => false,
+        }
+    }
+
     fn from_nodes(nodes: &[Node]) -> Vec<Value> {
         nodes.iter().map(Value::from_node).collect()
     }
 
     fn from_node(node: &Node) -> Value {
         match node {
-            Node::Boolean(b) => Value::Boolean(b.clone()),
-            Node::Identifier(s) => Value::Symbol(s.clone()),
-            Node::Integer(i) => Value::Integer(i.clone()),
-            Node::List(nodes) => Value::List(Value::from_nodes(nodes)),
+            Node::Boolean(b) => Value::Boolean(*b),
+            Node::Identifier(s) => Value::Symbol(Symbol::intern(s)),
+            Node::Number(n) => Value::Number(n.clone()),
+            Node::String(s) => Value::String(s.as_str().into()),
+            Node::Char(c) => Value::Char(*c),
+            Node::List(nodes) => Value::list(Value::from_nodes(nodes)),
+            Node::Vector(nodes) => Value::Vector(Rc::new(Vector::new(Value::from_nodes(nodes)))),
+            Node::DottedList(nodes, tail) => Value::list_with_tail(Value::from_nodes(nodes), Value::from_node(tail)),
+            Node::Located(node, span) => Value::from_node(node).with_span(Some(Rc::new(span.clone()))),
         }
     }
 }
@@ -338,101 +1363,351 @@ impl Value {
 //             Node::Identifier("x".to_string())
 //         ])]
 
+/**
+ * * the variables of one scope, environments live on the gc heap and are referred to by an EnvRef
+ * ! the globals are an env, the local variables of the tree-walker and the vm are in indexed frames
+ */
 #[derive(Clone)]
 pub struct Env {
-    parent: Option<Rc<RefCell<Env>>>,
-    values: HashMap<String, Value>,
+    parent: Option<EnvRef>,
+    values: SymbolMap<Value>,
+    // * the bindings of the prelude, a definition of the same name replaces them
+    defaults: SymbolSet,
 }
 
 impl Env {
 
-
-    pub fn new_root() -> Rc<RefCell<Env>> {
+    pub fn new_root() -> EnvRef {
        let mut env =  Env {
            parent: None,
-           values: HashMap::new(),
+           values: SymbolMap::default(),
+           defaults: SymbolSet::default(),
        };
 
-       env.define("define", &Value::Procedure(Function::Native(native_define))).unwrap();
-       env.define("+", &Value::Procedure(Function::Native(native_add))).unwrap();
-       env.define("let", &Value::Procedure(Function::Native(native_let))).unwrap();
-       env.define("*", &Value::Procedure(Function::Native(native_times))).unwrap();
-       env.define("lambda", &Value::Procedure(Function::Native(native_lambda))).unwrap();
-       env.define("if", &Value::Procedure(Function::Native(native_if))).unwrap();
-       env.define("apply", &Value::Procedure(Function::Native(native_apply))).unwrap();
-       env.define("eval", &Value::Procedure(Function::Native(eval_values))).unwrap();
-       Rc::new(RefCell::new(env))
+       env.define("eq?", &Value::Procedure(Function::Native(native_is_eq))).unwrap();
+       env.define("eqv?", &Value::Procedure(Function::Native(native_is_eqv))).unwrap();
+       env.define("equal?", &Value::Procedure(Function::Native(native_is_equal))).unwrap();
+       env.define("display", &Value::Procedure(Function::Native(native_display))).unwrap();
+       env.define("write", &Value::Procedure(Function::Native(native_write))).unwrap();
+       env.define("newline", &Value::Procedure(Function::Native(native_newline))).unwrap();
+       arithmetic::define_natives(&mut env);
+       strings::define_natives(&mut env);
+       list::define_natives(&mut env);
+       vector::define_natives(&mut env);
+       exception::define_natives(&mut env);
+       system::define_natives(&mut env);
+       gc::define_natives(&mut env);
+       env.define("apply", &Value::Procedure(Function::TailNative(native_apply))).unwrap();
+       env.define("eval", &Value::Procedure(Function::TailNative(native_eval))).unwrap();
+       env.define("call-with-current-continuation", &Value::Procedure(Function::TailNative(native_call_cc))).unwrap();
+       env.define("call/cc", &Value::Procedure(Function::TailNative(native_call_cc))).unwrap();
+       env.define("dynamic-wind", &Value::Procedure(Function::TailNative(native_dynamic_wind))).unwrap();
+       EnvRef::alloc(env)
+    }
+
+    // * return the new child env with parameter as its parent
+    pub fn new_child(env: EnvRef) -> EnvRef {
+        EnvRef::alloc(Env {
+            parent: Some(env),
+            values: SymbolMap::default(),
+            defaults: SymbolSet::default(),
+        })
+    }
+
+    pub fn define(&mut self, key: &str, value: &Value) -> Result<(), RuntimeError> {
+        self.bind(Symbol::intern(key), value)
+    }
+
+    /**
+     * * a definition of a bound name assigns to it like set!, so a session can redefine a procedure
+     * * and a continuation re-entering a body runs the defines of the body again
+     * ! the names of a body are checked by the expander, a duplicate never gets here
+     */
+    fn bind(&mut self, key: Symbol, value: &Value) -> Result<(), RuntimeError> {
+        self.values.insert(key, value.clone());
+        self.defaults.remove(&key);
+        Ok(())
     }
 
-    fn get_root(env_ref: Rc<RefCell<Env>>) -> Rc<RefCell<Env>> {
-        let env = env_ref.borrow();
-        match env.parent {
-            Some(ref p) => Env::get_root(p.clone()),
-            None => env_ref.clone(),
+    pub(super) fn trace(&self, m: &mut Marker) {
+        if let Some(parent) = self.parent {
+            m.env(parent);
         }
+        m.values(self.values.values());
     }
-    // * return the new child env rc with parameter as its parent
-    pub fn new_child(env: Rc<RefCell<Env>>) -> Rc<RefCell<Env>> {
-        let new_env = Env {
-            parent: Some(env),
-            values: HashMap::new(),
-        };
+}
 
-        Rc::new(RefCell::new(new_env))
+impl EnvRef {
+    pub fn define(self, key: Symbol, value: &Value) -> Result<(), RuntimeError> {
+        self.with(|env| env.bind(key, value))?
     }
 
-    fn define_internal(&mut self, key: &String, value: &Value) -> Result<(), RuntimeError> {
-        match self.values.insert(String::from(key), value.clone()) {
-            Some(_) => runtime_error!("The identifier is already defined!: {:?}", key),
-            None => Ok(()),
+    /**
+     * * assign to the innermost env binding key
+     */
+    pub fn set(self, key: Symbol, value: &Value) -> Result<(), RuntimeError> {
+        let mut env = self;
+        loop {
+            let parent = env.with(|e| match e.values.get_mut(&key) {
+                Some(v) => {
+                    *v = value.clone();
+                    None
+                },
+                None => Some(e.parent),
+            })?;
+            env = match parent {
+                None => return Ok(()),
+                Some(Some(p)) => p,
+                Some(None) => runtime_error!("Can't set an undefined variable: {:?}", key),
+            };
         }
     }
 
-    pub fn define(&mut self, key: &str, value: &Value) -> Result<(), RuntimeError> {
-        self.define_internal(&key.to_string(), value)
+    /**
+     * * every identifier bound in this env and its parents
+     */
+    pub fn names(self) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut env = Some(self);
+        while let Some(e) = env {
+            // * a collected env binds nothing anymore
+            env = e.with(|e| {
+                names.extend(e.values.keys().map(|k| k.to_string()));
+                e.parent
+            }).unwrap_or(None);
+        }
+        names
     }
 
-    pub fn set(&mut self, key: &String, value: &Value) -> Result<(), RuntimeError> {
-        match self.values.contains_key(key) {
-            true => {
-                self.values.insert(key.clone(), value.clone());
-                Ok(())
-            },
-            false => {
-                match &self.parent {
-                    Some(p) => p.borrow_mut().set(key, value),
-                    None => runtime_error!("Can't set an undefined variable: {:?}", key)
-                }
-            }
+    pub fn get(self, identifier: Symbol) -> Result<Value, RuntimeError> {
+        let mut env = self;
+        loop {
+            let found = env.with(|e| match e.values.get(&identifier) {
+                Some(v) => Ok(v.clone()),
+                None => Err(e.parent),
+            })?;
+            env = match found {
+                Ok(v) => return Ok(v),
+                Err(Some(p)) => p,
+                Err(None) => runtime_error!("Used before define: {:?}", (Expander::source_name(&identifier.to_string()))),
+            };
         }
     }
 
-    pub fn get(&self, identifier: &String) -> Result<Value, RuntimeError> {
-        match self.values.get(identifier) {
-            Some(v) => Ok(v.clone()),
-            None => {
-                match &self.parent {
-                    Some(p) => p.borrow().get(identifier),
-                    None => runtime_error!("Used before define: {:?}", identifier),
-                }
-            },
+    /**
+     * * the variables bound in this env itself
+     */
+    pub fn bindings(self) -> Vec<(String, Value)> {
+        self.with(|env| env.values.iter().map(|(k, v)| (k.to_string(), v.clone())).collect()).unwrap_or_default()
+    }
+
+    pub fn lookup(self, name: &str) -> Option<Value> {
+        self.with(|env| env.values.get(&Symbol::intern(name)).cloned()).ok().flatten()
+    }
+
+    /**
+     * * the names still bound by the prelude, the ones no definition has replaced
+     */
+    pub fn defaults(self) -> Vec<String> {
+        self.with(|env| env.defaults.iter().map(|k| k.to_string()).collect()).unwrap_or_default()
+    }
+}
+
+/**
+ * * how the expanded forms are run: resolved and walked, or compiled to bytecode for the vm
+ * ! the vm hands the forms it can not compile to the tree-walker, both run on the same machine
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Engine {
+    Tree,
+    Vm,
+}
+
+impl Engine {
+    pub fn from_name(name: &str) -> Option<Engine> {
+        match name {
+            "tree" => Some(Engine::Tree),
+            "vm" => Some(Engine::Vm),
+            _ => None,
         }
     }
 }
 
+// * the scheme half of the standard procedures, built into the binary
+const PRELUDE: &str = include_str!("prelude.sch");
+
 pub struct Evalator {
-    root: Rc<RefCell<Env>>
+    pub(super) root: EnvRef,
+    // * shared with the machines the evalator starts, for eval
+    pub(super) expander: Rc<RefCell<Expander>>,
+    pub(super) libraries: Libraries,
+    engine: Engine,
+}
+
+impl Default for Evalator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Evalator {
+    fn drop(&mut self) {
+        gc::remove_root(self.root);
+    }
 }
 
 impl Evalator {
+    /**
+     * * an evalator with the natives and the procedures of the prelude
+     */
     pub fn new() -> Evalator {
+        let mut evalator = Evalator::bare();
+        evalator.load_prelude().expect("the prelude evaluates");
+        evalator
+    }
+
+    /**
+     * * an evalator with the natives alone, map or filter are not bound until the prelude is loaded
+     */
+    pub fn bare() -> Evalator {
+        let root = Env::new_root();
+        // * the globals stay alive between two evaluations, until the evalator is dropped
+        gc::add_root(root);
         Evalator {
-            root: Env::new_root(),
+            root,
+            expander: Rc::new(RefCell::new(Expander::new())),
+            libraries: Libraries::new(),
+            engine: Engine::Tree,
+        }
+    }
+
+    pub fn engine(&self) -> Engine {
+        self.engine
+    }
+
+    pub fn set_engine(&mut self, engine: Engine) {
+        self.engine = engine;
+    }
+
+    /**
+     * * each top level form is macro expanded and then evaluated before the next one
+     * * is expanded, so a define-syntax applies to the forms following it
+     */
+    pub fn eval(&mut self, nodes: &[Node]) -> Result<Value, RuntimeError> {
+        let mut res = Value::Unit;
+        for value in Value::from_nodes(nodes) {
+            if let Some(v) = self.eval_toplevel(&value)? {
+                res = v;
+            }
+        }
+        Ok(res)
+    }
+
+    /**
+     * * evaluate one toplevel form, None for forms which only exist at expansion time and empty begins
+     * * import and define-library are handled here, they change what the following forms are expanded against
+     */
+    pub(super) fn eval_toplevel(&mut self, value: &Value) -> Result<Option<Value>, RuntimeError> {
+        let keyword = self.expander.borrow().toplevel_keyword(value);
+        match keyword {
+            Some("import") => self.import(value).map_err(|e| e.at(value.span())).map(|_| None),
+            Some("define-library") => self.define_library(value).map_err(|e| e.at(value.span())).map(|_| None),
+            _ => {
+                // * a toplevel begin gives several forms, its value is the value of the last one
+                let mut res = None;
+                let forms = self.expander.borrow_mut().expand_toplevel(value).map_err(|e| e.at(value.span()))?;
+                for expanded in forms {
+                    res = Some(self.run(&expanded)?);
+                }
+                Ok(res)
+            },
+        }
+    }
+
+    // * run an expanded form with the engine
+    fn run(&self, expanded: &Value) -> Result<Value, RuntimeError> {
+        match self.engine {
+            Engine::Vm => match compile::compile(expanded) {
+                Some(template) => eval_compiled(template, self.root, Some(self.expander.clone())),
+                None => eval_value(expanded, self.root, Some(self.expander.clone())),
+            },
+            Engine::Tree => eval_value(expanded, self.root, Some(self.expander.clone())),
+        }
+    }
+
+    /**
+     * * the variables bound in the root env with their values, sorted by name
+     */
+    pub fn bindings(&self) -> Vec<(String, Value)> {
+        let mut bindings = self.root.bindings();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        bindings
+    }
+
+    /**
+     * * keep a value of an evaluation alive across collections until the guard is dropped
+     */
+    pub fn keep(&self, value: Value) -> gc::Kept {
+        gc::keep(value)
+    }
+
+    /**
+     * * the value of a global variable
+     */
+    pub fn lookup(&self, name: &str) -> Option<Value> {
+        self.root.lookup(name)
+    }
+
+    /**
+     * * whether name is a special form or a macro at toplevel
+     */
+    pub fn is_keyword(&self, name: &str) -> bool {
+        self.expander.borrow().keywords().iter().any(|k| k == name)
+    }
+
+    /**
+     * * the forms the evaluator would run for nodes, after macro expansion and desugaring
+     * ! like evaluating them, expanding a definition binds its name for the forms after it
+     */
+    pub fn expand(&mut self, nodes: &[Node]) -> Result<Vec<Value>, RuntimeError> {
+        let mut res = Vec::new();
+        for value in Value::from_nodes(nodes) {
+            let expanded = self.expander.borrow_mut().expand_toplevel(&value).map_err(|e| e.at(value.span()))?;
+            res.extend(expanded);
         }
+        Ok(res)
+    }
+
+    /**
+     * * the identifiers with a global meaning, variables of the root env and syntactic keywords, sorted
+     */
+    pub fn bound_names(&self) -> Vec<String> {
+        let mut names = self.root.names();
+        names.extend(self.expander.borrow().identifiers());
+        // * the globals of libraries live in the root env under their namespace, nobody can type those
+        names.retain(|name| !name.starts_with('('));
+        names.sort();
+        names.dedup();
+        names
     }
 
-    pub fn eval(&self, nodes: &[Node]) -> Result<Value, RuntimeError> {
-        eval(nodes, self.root.clone())
+    /**
+     * * lex, parse and evaluate the text of the source called name
+     */
+    pub fn eval_source(&mut self, name: &str, text: &str) -> Result<Value, Error> {
+        let tokens = lexer::lex_source(&Source::new(name, text))?;
+        let nodes = Parser::parse(&tokens)?;
+        Ok(self.eval(&nodes)?)
+    }
+
+    /**
+     * * define the list and higher order procedures of prelude.sch, run with the engine set now
+     */
+    pub fn load_prelude(&mut self) -> Result<(), Error> {
+        let natives: SymbolSet = self.root.with(|env| env.values.keys().copied().collect())?;
+        self.eval_source("<prelude>", PRELUDE)?;
+        self.root.with(|env| env.defaults = env.values.keys().filter(|k| !natives.contains(k)).copied().collect())?;
+        Ok(())
     }
 }
 
@@ -440,36 +1715,38 @@ impl Evalator {
 /*
    TODO: The public eval function to produce a value based on AST
 */
-fn eval(nodes: &[Node], env: Rc<RefCell<Env>>) -> Result<Value, RuntimeError> {
+#[cfg(test)]
+fn eval(nodes: &[Node], env: EnvRef) -> Result<Value, RuntimeError> {
     let values = Value::from_nodes(nodes);
     println!("values from nodes: {:?}", values);
     eval_values(&values, env)
 }
 
-fn eval_values(values: &[Value], env: Rc<RefCell<Env>>) -> Result<Value, RuntimeError> {
+#[cfg(test)]
+fn eval_values(values: &[Value], env: EnvRef) -> Result<Value, RuntimeError> {
     let mut res = None;
     for v in values {
-        res = Some(eval_value(&v, env.clone()).unwrap());
+        res = Some(eval_value(v, env, None)?);
     }
 
-    match &res {
-        Some(val) => Ok(val.clone()),
+    match res {
+        Some(val) => Ok(val),
         None => Ok(Value::Unit)
     }
 }
 
-fn eval_value(value: &Value, env: Rc<RefCell<Env>>) -> Result<Value, RuntimeError> {
+/**
+ * * evaluate a value on a fresh machine
+ */
+fn eval_value(value: &Value, env: EnvRef, expander: Option<Rc<RefCell<Expander>>>) -> Result<Value, RuntimeError> {
+    Machine::new(env, expander).run(Tail::Eval(address::address(value), Scope::global(env)))
+}
 
-    match value {
-        Value::Unit => Ok(value.clone()),
-        Value::Integer(i) => Ok(value.clone()),
-        Value::Symbol(s) => env.borrow().get(s),
-        Value::List(vs) => {
-            eval_expression(vs, env.clone())
-        },
-        Value::Procedure(_) => Ok(value.clone()),
-        Value::Boolean(_) => Ok(value.clone()),
-    }
+/**
+ * * run a compiled toplevel form on a fresh machine
+ */
+fn eval_compiled(template: Rc<Template>, env: EnvRef, expander: Option<Rc<RefCell<Expander>>>) -> Result<Value, RuntimeError> {
+    Machine::new(env, expander).run(Tail::Execute(Activation::toplevel(template, env)))
 }
 
 #[cfg(test)]
@@ -481,25 +1758,16 @@ mod tests {
      * * define a list of variables in current level env
      * ! only for testing
      */
-
-    fn insert_into_env(env: Rc<RefCell<Env>>, vars: &Vec<(String, Value)>) -> Rc<RefCell<Env>> {
+    fn insert_into_env(env: EnvRef, vars: &[(String, Value)]) -> EnvRef {
 
         for (key, value) in vars {
-            env.borrow_mut().define(key, value);
+            env.define(Symbol::intern(key), value).unwrap();
         }
 
         env
     }
 
-    fn slice_to_node(idens: Vec<&str>) -> Vec<Node> {
-        let nodes = idens.iter().map(|s| {
-            Node::Identifier(s.to_string())
-        }).collect::<Vec<Node>>();
-
-        nodes
-    }
-
-    fn test_template(nodes: Vec<Node>, exp: Value, env: Rc<RefCell<Env>>) {
+    fn test_template(nodes: Vec<Node>, exp: Value, env: EnvRef) {
 
         match eval(&nodes, env) {
             Ok(v) => assert_eq!(v, exp),
@@ -509,28 +1777,251 @@ mod tests {
 
     #[test]
     fn eval_simple_integer() {
-        test_template(vec![Node::Integer(1)], Value::Integer(1), Env::new_root());
+        test_template(vec![Node::Number(Number::Integer(1))], Value::from(1), Env::new_root());
     }
 
     #[test]
     fn eval_simple_iden() {
-        test_template(vec![Node::Identifier("x".to_string())], Value::Integer(1), insert_into_env(Env::new_root(), &vec![("x".to_string(), Value::Integer(1))]));
+        test_template(vec![Node::Identifier("x".to_string())], Value::from(1), insert_into_env(Env::new_root(), &[("x".to_string(), Value::from(1))]));
     }
 
 
     // * (define x 2) x
     #[test]
     fn eval_define() {
-        let def_nodes = vec![Node::List(vec![Node::Identifier("define".to_string()), Node::Identifier("x".to_string()), Node::Integer(2)]), Node::Identifier("x".to_string())];
-        test_template(def_nodes, Value::Integer(2), Env::new_root());
+        let def_nodes = vec![Node::List(vec![Node::Identifier("define".to_string()), Node::Identifier("x".to_string()), Node::Number(Number::Integer(2))]), Node::Identifier("x".to_string())];
+        test_template(def_nodes, Value::from(2), Env::new_root());
     }
 
     // * (let ([x 2]) x)
     #[test]
     fn eval_let() {
-        let nodes = vec![Node::List(vec![Node::Identifier("let".to_string()), Node::List(vec![Node::List(vec![Node::Identifier("x".to_string()), Node::Integer(2)])]), Node::Identifier("x".to_string())])];
-        test_template(nodes, Value::Integer(2), Env::new_root());
+        let nodes = vec![Node::List(vec![Node::Identifier("let".to_string()), Node::List(vec![Node::List(vec![Node::Identifier("x".to_string()), Node::Number(Number::Integer(2))])]), Node::Identifier("x".to_string())])];
+        test_template(nodes, Value::from(2), Env::new_root());
+    }
+
+    fn eval_str(input: &str, env: EnvRef) -> Result<Value, RuntimeError> {
+        let tokens = lexer::lex(input).unwrap();
+        let nodes = Parser::parse(&tokens).unwrap();
+        eval(&nodes, env)
+    }
+
+    #[test]
+    fn eval_tail_call_define() {
+        let input = "(define (count n acc) (if (zero? n) acc (count (- n 1) (+ acc 1)))) (count 1000000 1)";
+        assert_eq!(eval_str(input, Env::new_root()).unwrap(), Value::from(1000001));
+    }
+
+    #[test]
+    fn eval_tail_call_lambda_let() {
+        let input = "(define count (lambda (n) (let ((m (- n 1))) (if (zero? m) #t (count m))))) (count 1000000)";
+        assert_eq!(eval_str(input, Env::new_root()).unwrap(), Value::Boolean(true));
+    }
+
+    #[test]
+    fn eval_tail_call_mutual() {
+        let input = "(define (even n) (if (zero? n) #t (odd (- n 1)))) (define (odd n) (if (zero? n) #f (even (- n 1)))) (even 1000001)";
+        assert_eq!(eval_str(input, Env::new_root()).unwrap(), Value::Boolean(false));
+    }
+
+    #[test]
+    fn eval_closure_body_sequence() {
+        let input = "(define (f x) (+ x 1) (+ x x)) (f 21)";
+        assert_eq!(eval_str(input, Env::new_root()).unwrap(), Value::from(42));
+    }
+
+    #[test]
+    fn eval_native_as_argument() {
+        let input = "(define (twice f x) (f x x)) (twice + 21)";
+        assert_eq!(eval_str(input, Env::new_root()).unwrap(), Value::from(42));
+    }
+
+    #[test]
+    fn eval_apply() {
+        assert_eq!(eval_str("(apply + 1 (list 2 3))", Env::new_root()).unwrap(), Value::from(6));
+        assert_eq!(eval_str("(apply (lambda (x y) (+ x y)) (list 1 2))", Env::new_root()).unwrap(), Value::from(3));
+    }
+
+    #[test]
+    fn eval_special_form_is_not_a_value() {
+        assert!(eval_str("(apply if (list #t 1 2))", Env::new_root()).is_err());
+        assert!(eval_str("((lambda (f) (f #t 1 2)) lambda)", Env::new_root()).is_err());
+    }
+
+    #[test]
+    fn eval_special_form_not_shadowed() {
+        let input = "(define (f if) (if #f if 2)) (f 1)";
+        assert_eq!(eval_str(input, Env::new_root()).unwrap(), Value::from(2));
+    }
+
+    #[test]
+    fn eval_lexical_addressing() {
+        // * unexpanded forms keep their names, locals shadow the globals and each other frame by frame
+        let input = "(define x 1) (define (f x) (let ((y x)) (define z 2) (lambda () (set! x (+ x y z)) x))) (define g (f 10)) (g) (list (g) x)";
+        assert_eq!(eval_write(input), "(34 1)");
+        assert_eq!(eval_write("(define y 5) (define (h) (define a (lambda () y)) (define y 6) (a)) (list (h) y)"), "(6 5)");
+        assert!(eval_str("((lambda (x x) x) 1 2)", Env::new_root()).unwrap_err().message().contains("already defined"));
+    }
+
+    #[test]
+    fn eval_numeric_tower() {
+        assert_eq!(eval_str("(- 10 2 3)", Env::new_root()).unwrap(), Value::from(5));
+        assert_eq!(eval_str("(- 7)", Env::new_root()).unwrap(), Value::from(-7));
+        assert_eq!(eval_str("(/ 1 3)", Env::new_root()).unwrap(), Value::Number(Number::rational(1, 3).unwrap()));
+        assert_eq!(eval_str("(/ 4)", Env::new_root()).unwrap(), Value::Number(Number::rational(1, 4).unwrap()));
+        assert_eq!(eval_str("(+ 1/2 1/2)", Env::new_root()).unwrap(), Value::from(1));
+        assert_eq!(eval_str("(* 2 1.5)", Env::new_root()).unwrap(), Value::from(3.0));
+        assert_eq!(eval_str("(+ 1/2 0.25)", Env::new_root()).unwrap(), Value::from(0.75));
+        assert_eq!(eval_str("(exact 0.5)", Env::new_root()).unwrap(), Value::Number(Number::rational(1, 2).unwrap()));
+        assert_eq!(eval_str("(exact? (/ 6 3))", Env::new_root()).unwrap(), Value::Boolean(true));
+        assert_eq!(eval_str("(+)", Env::new_root()).unwrap(), Value::from(0));
+    }
+
+    #[test]
+    fn eval_bignum() {
+        assert_eq!(eval_str("(* 99999999999 99999999999)", Env::new_root()).unwrap().to_string(), "9999999999800000000001");
+        let input = "(define (fact n) (if (zero? n) 1 (* n (fact (- n 1))))) (fact 30)";
+        assert_eq!(eval_str(input, Env::new_root()).unwrap().to_string(), "265252859812191058636308480000000");
+        let input = "(- (+ 9223372036854775807 1) 1)";
+        assert_eq!(eval_str(input, Env::new_root()).unwrap(), Value::from(i64::MAX));
+        let input = "(/ 100000000000000000000 300000000000000000000)";
+        assert_eq!(eval_str(input, Env::new_root()).unwrap(), Value::Number(Number::rational(1, 3).unwrap()));
+    }
+
+    #[test]
+    fn eval_arithmetic_errors() {
+        assert!(eval_str("(/ 1 0)", Env::new_root()).is_err());
+        assert!(eval_str("(+ 1 #t)", Env::new_root()).is_err());
+        assert!(eval_str("(-)", Env::new_root()).is_err());
+    }
+
+    fn eval_write(input: &str) -> String {
+        format!("{:?}", eval_str(input, Env::new_root()).unwrap())
+    }
+
+    #[test]
+    fn eval_quote() {
+        assert_eq!(eval_write("'a"), "a");
+        assert_eq!(eval_write("'(1 \"two\" #\\3 (four))"), "(1 \"two\" #\\3 (four))");
+        assert_eq!(eval_write("(quote (+ 1 2))"), "(+ 1 2)");
+        assert_eq!(eval_write("''a"), "(quote a)");
+    }
+
+    #[test]
+    fn eval_quasiquote() {
+        assert_eq!(eval_write("`(1 ,(+ 1 1) ,@(list 3 4) 5)"), "(1 2 3 4 5)");
+        assert_eq!(eval_write("(define x 'y) `(x ,x (,x))"), "(x y (y))");
+        assert_eq!(eval_write("`(a `(b ,(c ,(+ 1 2))))"), "(a (quasiquote (b (unquote (c 3)))))");
+        assert_eq!(eval_write("`(a `(b ,@(c ,@(list 1 2))))"), "(a (quasiquote (b (unquote-splicing (c 1 2)))))");
+        assert_eq!(eval_write("`(1 . ,(+ 1 1))"), "(1 . 2)");
+        assert!(eval_str(",x", Env::new_root()).is_err());
+        assert!(eval_str("`(,@1)", Env::new_root()).is_err());
+    }
+
+    #[test]
+    fn eval_set() {
+        assert_eq!(eval_str("(define x 1) (set! x (+ x 1)) x", Env::new_root()).unwrap(), Value::from(2));
+        let input = "(define (counter) (let ((n 0)) (lambda () (set! n (+ n 1)) n))) (define c (counter)) (c) (c)";
+        assert_eq!(eval_str(input, Env::new_root()).unwrap(), Value::from(2));
+        assert!(eval_str("(set! undefined 1)", Env::new_root()).is_err());
+        assert_eq!(eval_write("(define x 2) `#(1 ,x ,@(list 3 4))"), "#(1 2 3 4)");
+    }
+
+    #[test]
+    fn eval_closure_arity() {
+        assert!(eval_str("((lambda (x y) x) 1)", Env::new_root()).is_err());
     }
 
+    #[test]
+    fn eval_call_cc_escape() {
+        assert_eq!(eval_str("(+ 1 (call/cc (lambda (k) (+ 10 (k 2)))))", Env::new_root()).unwrap(), Value::from(3));
+        assert_eq!(eval_str("(call-with-current-continuation (lambda (k) 5))", Env::new_root()).unwrap(), Value::from(5));
+        let input = "(define (find-zero xs) (call/cc (lambda (return) (vector-map (lambda (x) (if (zero? x) (return x) x)) xs) 'none))) (find-zero #(3 0 1))";
+        assert_eq!(eval_str(input, Env::new_root()).unwrap(), Value::from(0));
+    }
+
+    #[test]
+    fn eval_call_cc_reentry() {
+        // * the same continuation is resumed three times, each time rebinding n
+        let input = "(define k #f) (define seen '()) \
+            (define (run) (let ((n (call/cc (lambda (c) (set! k c) 2)))) (set! seen (cons n seen)) (if (zero? n) (reverse seen) (k (- n 1))))) \
+            (run)";
+        assert_eq!(format!("{:?}", eval_str(input, Env::new_root()).unwrap()), "(2 1 0)");
+
+        // * the define of a body runs again on re-entry and assigns to the name it made
+        let input = "(define kk #f) (define n 0) \
+            (define (f) (define x (call/cc (lambda (k) (set! kk k) 1))) (set! n (+ n 1)) (if (< n 3) (kk (+ x 1)) (list n x))) \
+            (f)";
+        assert_eq!(crate::interpreter::tests::eval_write(input), "(3 3)");
+    }
+
+    #[test]
+    fn eval_dynamic_wind() {
+        let trace = "(define trace '()) (define (note x) (lambda () (set! trace (cons x trace)))) ";
+        let input = format!("{}(dynamic-wind (note 'before) (lambda () ((note 'during)) 'result) (note 'after)) (reverse trace)", trace);
+        assert_eq!(format!("{:?}", eval_str(&input, Env::new_root()).unwrap()), "(before during after)");
+
+        // * escaping runs the after thunk, re-entering runs the before thunk again
+        let input = format!("{}(define k #f) (define (run) \
+            (dynamic-wind (note 'in) (lambda () (call/cc (lambda (c) (set! k c))) 1) (note 'out)) \
+            (if (zero? (length trace)) 0 (if (= (length trace) 2) (k 0) (reverse trace)))) (run)", trace);
+        assert_eq!(format!("{:?}", eval_str(&input, Env::new_root()).unwrap()), "(in out in out)");
+
+        let input = format!("{}(call/cc (lambda (k) (dynamic-wind (note 'in) (lambda () (k 1) ((note 'skipped))) (note 'out)))) (reverse trace)", trace);
+        assert_eq!(format!("{:?}", eval_str(&input, Env::new_root()).unwrap()), "(in out)");
+    }
 
+    #[test]
+    fn eval_equivalence() {
+        let eval_write = |input: &str| format!("{:?}", eval_str(input, Env::new_root()).unwrap());
+        assert_eq!(eval_write("(list (eq? 'a 'a) (eq? 'a (string->symbol \"a\")) (eq? 'a 'b) (eq? '() '()))"), "(#true #true #false #true)");
+        assert_eq!(eval_write("(let ((p (list 1))) (list (eq? p p) (eq? p (list 1)) (equal? p (list 1))))"), "(#true #false #true)");
+        assert_eq!(eval_write("(list (eqv? 2 2) (eqv? 2 2.0) (eq? car car) (eq? #\\a #\\a) (equal? #(1 (2)) #(1 (2))))"), "(#true #false #true #true #true)");
+        assert!(eval_str("(eq? 'a)", Env::new_root()).is_err());
+        assert_eq!(Value::Symbol(Symbol::intern("lambda")), Value::Symbol(Symbol::intern("lambda")));
+
+        // * a string is an object, only equal? looks at its characters
+        assert_eq!(eval_write("(let ((s \"a\")) (list (eq? s s) (eqv? s s) (eqv? s \"a\") (equal? s \"a\") (eqv? (string #\\a) (string #\\a))))"), "(#true #true #false #true #false)");
+        assert_eq!(eval_write("(let ((v (vector 1))) (list (eqv? v v) (eqv? v (vector 1)) (equal? v (vector 1)) (eqv? (list 1) (list 1))))"), "(#true #false #true #false)");
+        // * eqv? compares every number by exactness and value, reals by their bits, eq? only fixnums
+        assert_eq!(eval_write("(list (eqv? 0.0 -0.0) (= 0.0 -0.0) (eqv? 1.5 1.5) (eq? 1.5 1.5) (eq? 7 7) (eqv? 1/2 1/2) (eq? 1/2 1/2))"), "(#false #true #true #false #true #true #false)");
+        assert_eq!(eval_write("(let ((big (* 99999999999 99999999999))) (list (eqv? big (* 99999999999 99999999999)) (eq? big big) (equal? 2 2.0)))"), "(#true #false #false)");
+        // * equal? holds whenever eqv? does, and ends on circular data
+        assert_eq!(eval_write("(let ((nan (/ 0. 0.))) (list (eqv? nan nan) (equal? nan nan) (equal? (list nan) (list nan)) (equal? car car)))"), "(#true #true #true #true)");
+        assert_eq!(eval_write("(let ((a (list 1 2)) (b (list 1 2))) (set-cdr! (cdr a) a) (set-cdr! (cdr b) b) (list (equal? a b) (equal? a (cdr b))))"), "(#true #false)");
+        assert_eq!(eval_write("(let ((v (vector 1)) (w (vector 1))) (vector-set! v 0 v) (vector-set! w 0 w) (list (equal? v w) (equal? v (vector v 2))))"), "(#true #false)");
+    }
+
+    #[test]
+    fn eval_prelude() {
+        // * the prelude needs the expander, the shared helper evaluates with a whole evalator
+        let eval_write = crate::interpreter::tests::eval_write;
+        assert_eq!(eval_write("(list (map car '((1) (2))) (filter pair? '(1 (2) 3)) (reduce + 0 '(1 2 3)) (reduce + 0 '()))"), "((1 2) ((2)) 6 0)");
+        assert_eq!(eval_write("(list (fold-left list 0 '(1 2)) (fold-right list 0 '(1 2)) (fold cons* '() '(a b) '(1 2)))"), "(((0 1) 2) (1 (2 0)) (b 2 a 1))");
+        assert_eq!(eval_write("(list (member (list 2) '(1 (2) 3)) (memq 'd '(a b)) (assoc 2.0 '((1 a) (2 b)) =) (assq 'b '((a 1) (b 2))))"), "(((2) 3) #false (2 b) (b 2))");
+        assert_eq!(eval_write("(list (iota 3) (iota 3 1) (iota 3 0 2) (list-index char? '(1 3 #\\a)) (list-index char? '(1)))"), "((0 1 2) (1 2 3) (0 2 4) 2 #false)");
+        assert_eq!(eval_write("(list (any pair? '(1 (2))) (every pair? '()) (find symbol? '(1 a)) (count < '(1 5 2) '(2 3 3)) (delete-duplicates '(1 2 1 3 2)))"), "(#true #true a 2 (1 2 3))");
+        assert_eq!(eval_write("(define x (list 1 2)) (set-cdr! (cdr x) x) (list (list? x) (list? '(1 2)) (list? '(1 . 2)) (list-ref '(a b c) 2) (last '(1 2 3)))"), "(#false #true #false c 3)");
+        assert_eq!(eval_write("(let ((r '())) (for-each (lambda (a b) (set! r (cons (+ a b) r))) '(1 2) '(10 20)) r)"), "(22 11)");
+
+        // * a toplevel definition replaces a procedure of the prelude or a native
+        assert_eq!(eval_write("(define (iota n) n) (define count 1) (list (iota 3) count)"), "(3 1)");
+        assert_eq!(eval_write("(define car 1) car"), "1");
+        assert!(Evalator::bare().eval_source("<input>", "(map car '((1)))").is_err());
+    }
+
+    #[test]
+    fn eval_error_locations() {
+        let source = crate::interpreter::span::Source::new("test.sch", "(define (f x)\n  (+ 1\n    (car x)))\n(f 1)");
+        let nodes = Parser::parse(&lexer::lex_source(&source).unwrap()).unwrap();
+        let err = Evalator::new().eval(&nodes).unwrap_err();
+        assert_eq!(format!("{}", err), format!("test.sch:3:5: Runtime Error: {}\n  |\n3 |     (car x)))\n  |     ^^^^^^^", err.message()));
+
+        // * the macro use is reported for errors in its expansion, the form itself for unbound variables
+        let nodes = Parser::parse(&lexer::lex("(define-syntax my-car (syntax-rules () ((_ x) (car x))))\n(my-car 1)\n").unwrap()).unwrap();
+        assert_eq!(Evalator::new().eval(&nodes).unwrap_err().span().map(|s| s.to_string()), Some("<input>:2:1".to_string()));
+        let nodes = Parser::parse(&lexer::lex("(list 1\n (+ 1 undefined))").unwrap()).unwrap();
+        assert_eq!(Evalator::new().eval(&nodes).unwrap_err().span().map(|s| s.to_string()), Some("<input>:2:2".to_string()));
+        assert!(Evalator::new().eval(&[Node::Identifier("undefined".to_string())]).unwrap_err().span().is_none());
+    }
 }
@@ -0,0 +1,175 @@
+use super::eval::{Env, Function, Machine, RuntimeError, Tail, TailOperation, Value, ValueOperation};
+use super::gc::EnvRef;
+use super::strings::check_arity;
+use std::{fmt, rc::Rc};
+
+/**
+ * * the object created by (error message irritant ...)
+ * * built-in faults like an unbound variable are raised as error objects without irritants
+ */
+#[derive(PartialEq)]
+pub struct ErrorObject {
+    message: String,
+    irritants: Value,
+}
+
+impl ErrorObject {
+    pub(crate) fn new(message: String, irritants: Value) -> ErrorObject {
+        ErrorObject { message, irritants }
+    }
+
+    pub(crate) fn irritants(&self) -> &Value {
+        &self.irritants
+    }
+}
+
+impl fmt::Display for ErrorObject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        for irritant in self.irritants.parts().0 {
+            write!(f, " {:?}", irritant)?;
+        }
+        Ok(())
+    }
+}
+
+/**
+ * * exception natives, registered into the root env by Env::new_root
+ */
+pub fn define_natives(env: &mut Env) {
+    let natives: &[(&str, ValueOperation)] = &[
+        ("error-object?", native_is_error_object),
+        ("error-object-message", native_error_object_message),
+        ("error-object-irritants", native_error_object_irritants),
+    ];
+
+    for (name, op) in natives {
+        env.define(name, &Value::Procedure(Function::Native(*op))).unwrap();
+    }
+
+    // * raising hands control to the handlers installed on the machine
+    let tail_natives: &[(&str, TailOperation)] = &[
+        ("error", native_error),
+        ("raise", native_raise),
+        ("raise-continuable", native_raise_continuable),
+        ("with-exception-handler", native_with_exception_handler),
+    ];
+
+    for (name, op) in tail_natives {
+        env.define(name, &Value::Procedure(Function::TailNative(*op))).unwrap();
+    }
+}
+
+fn error_object_arg<'a>(name: &str, v: &'a Value) -> Result<&'a ErrorObject, RuntimeError> {
+    match v {
+        Value::Error(e) => Ok(e),
+        _ => runtime_error!("{} expects an error object but got: {:?}", name, v),
+    }
+}
+
+/**
+ * * (error message irritant ...) raise a new error object
+ */
+fn native_error(args: &[Value], _env: EnvRef, m: &mut Machine) -> Result<Tail, RuntimeError> {
+    let message = match args.first() {
+        Some(Value::String(s)) => s.to_string(),
+        _ => runtime_error!("error expects a message string but got: {:?}", args),
+    };
+    let obj = ErrorObject::new(message, Value::list(args[1..].to_vec()));
+    m.raise(Value::Error(Rc::new(obj)), false)
+}
+
+fn native_raise(args: &[Value], _env: EnvRef, m: &mut Machine) -> Result<Tail, RuntimeError> {
+    check_arity("raise", args, 1, 1)?;
+    m.raise(args[0].clone(), false)
+}
+
+/**
+ * * (raise-continuable obj) the value of the handler becomes the value of this call
+ */
+fn native_raise_continuable(args: &[Value], _env: EnvRef, m: &mut Machine) -> Result<Tail, RuntimeError> {
+    check_arity("raise-continuable", args, 1, 1)?;
+    m.raise(args[0].clone(), true)
+}
+
+/**
+ * * (with-exception-handler handler thunk)
+ */
+fn native_with_exception_handler(args: &[Value], env: EnvRef, m: &mut Machine) -> Result<Tail, RuntimeError> {
+    check_arity("with-exception-handler", args, 2, 2)?;
+    m.with_exception_handler(&args[0], &args[1], env)
+}
+
+fn native_is_error_object(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("error-object?", args, 1, 1)?;
+    Ok(Value::Boolean(matches!(args[0], Value::Error(_))))
+}
+
+fn native_error_object_message(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("error-object-message", args, 1, 1)?;
+    Ok(Value::String(error_object_arg("error-object-message", &args[0])?.message.as_str().into()))
+}
+
+fn native_error_object_irritants(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("error-object-irritants", args, 1, 1)?;
+    Ok(error_object_arg("error-object-irritants", &args[0])?.irritants.clone())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::interpreter::tests::{eval_str, eval_write};
+
+    #[test]
+    fn exception_error_objects() {
+        assert_eq!(eval_write("(guard (e (#t (error-object-message e))) (error \"boom\" 1 2))"), "\"boom\"");
+        assert_eq!(eval_write("(guard (e ((error-object? e) (error-object-irritants e))) (error \"boom\" 1 \"two\"))"), "(1 \"two\")");
+        assert_eq!(eval_write("(guard (e ((string? e) 'string) ((error-object? e) 'error) (else e)) (raise 'sym))"), "sym");
+        let err = eval_str("(error \"boom\" 'x 2)").unwrap_err();
+        assert_eq!(err.message(), "boom x 2");
+        assert!(eval_str("(error-object-message 'x)").is_err());
+    }
+
+    #[test]
+    fn exception_guard_clauses() {
+        assert_eq!(eval_write("(guard (e ((string? e) 'string) (else (list 'other e))) (raise 1))"), "(other 1)");
+        assert_eq!(eval_write("(guard (e ((car e) => (lambda (x) (* x 2)))) (raise (list 21)))"), "42");
+        assert_eq!(eval_write("(guard (e ((pair? e))) (raise (list 1)))"), "#true");
+        // * a condition no clause accepts goes on to the outer handler
+        assert_eq!(eval_write("(guard (e (#t (list 'outer e))) (guard (e ((string? e) 'inner)) (raise 1)))"), "(outer 1)");
+        assert!(eval_str("(guard (e ((string? e) 'inner)) (raise 1))").is_err());
+        // * else is only the clause keyword while it is not shadowed
+        assert!(eval_str("(let ((else #f)) (guard (e (else 'caught)) (raise 1)))").is_err());
+        assert_eq!(eval_write("(guard (e (#f 'no)) 'body)"), "body");
+    }
+
+    #[test]
+    fn exception_handlers() {
+        assert_eq!(eval_write("(+ 1 (with-exception-handler (lambda (c) 41) (lambda () (raise-continuable 'oops))))"), "42");
+        assert_eq!(eval_write("(with-exception-handler (lambda (c) 0) (lambda () (+ 1 (raise-continuable 'oops) 2)))"), "3");
+        // * returning from a handler of raise is a secondary error, caught by the outer guard
+        assert_eq!(eval_write("(guard (e ((error-object? e) 'returned)) (with-exception-handler (lambda (c) 0) (lambda () (raise 'oops))))"), "returned");
+        assert!(eval_str("(with-exception-handler (lambda (c) 0) (lambda () (raise 'oops)))").is_err());
+        // * the handler runs with the outer handlers installed
+        let input = "(guard (e (#t (list 'outer e))) (with-exception-handler (lambda (c) (raise (list 'inner c))) (lambda () (raise 1))))";
+        assert_eq!(eval_write(input), "(outer (inner 1))");
+        assert!(eval_str("(with-exception-handler 1 (lambda () 1))").is_err());
+    }
+
+    #[test]
+    fn exception_builtin_faults() {
+        assert_eq!(eval_write("(guard (e ((error-object? e) 'caught)) (car 1))"), "caught");
+        assert_eq!(eval_write("(guard (e ((error-object? e) 'caught)) undefined-variable)"), "caught");
+        assert_eq!(eval_write("(guard (e ((error-object? e) 'caught)) ((lambda (x) x)))"), "caught");
+        assert_eq!(eval_write("(guard (e ((error-object? e) 'caught)) (1 2))"), "caught");
+        assert_eq!(eval_write("(guard (e (#t (error-object-irritants e))) (vector-ref #(1) 5))"), "()");
+    }
+
+    #[test]
+    fn exception_unwinds_dynamic_wind() {
+        let input = "(define trace '()) \
+            (guard (e (#t (reverse (cons e trace)))) \
+              (dynamic-wind (lambda () (set! trace (cons 'in trace))) (lambda () (raise 'x)) (lambda () (set! trace (cons 'out trace)))))";
+        assert_eq!(eval_write(input), "(in out x)");
+    }
+}
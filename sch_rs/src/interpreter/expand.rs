@@ -0,0 +1,1370 @@
+use super::address::special_form_names;
+use super::eval::{RuntimeError, Value};
+use super::parser::MAX_DEPTH;
+use super::symbol::Symbol;
+use super::vector::Vector;
+use std::{cell::RefCell, collections::{HashMap, HashSet, VecDeque}, rc::Rc};
+
+/**
+ * * what an identifier means at expansion time
+ * * a variable carries its runtime name: globals keep their name, locals are renamed apart
+ */
+#[derive(Clone)]
+pub(crate) enum Binding {
+    Special(&'static str),
+    Macro(Rc<Macro>),
+    Variable(String),
+}
+
+impl PartialEq for Binding {
+    fn eq(&self, other: &Binding) -> bool {
+        match (self, other) {
+            (Binding::Special(a), Binding::Special(b)) => a == b,
+            (Binding::Macro(a), Binding::Macro(b)) => Rc::ptr_eq(a, b),
+            (Binding::Variable(a), Binding::Variable(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/**
+ * * a lexical scope of the syntactic environment
+ */
+struct Scope {
+    bindings: RefCell<HashMap<String, Binding>>,
+    parent: Option<Rc<Scope>>,
+}
+
+impl Scope {
+    fn new(parent: Option<Rc<Scope>>) -> Rc<Scope> {
+        Rc::new(Scope {
+            bindings: RefCell::new(HashMap::new()),
+            parent,
+        })
+    }
+
+    fn lookup(&self, id: &str) -> Option<Binding> {
+        match self.bindings.borrow().get(id) {
+            Some(b) => Some(b.clone()),
+            None => self.parent.as_ref().and_then(|p| p.lookup(id)),
+        }
+    }
+
+    fn bind(&self, id: &str, binding: Binding) {
+        self.bindings.borrow_mut().insert(id.to_string(), binding);
+    }
+}
+
+/**
+ * * an identifier inserted by a macro template, it stands for `name` as seen from
+ * * the scope the macro was defined in unless a binding form of the expansion binds it
+ */
+struct Alias {
+    name: String,
+    scope: Rc<Scope>,
+}
+
+pub(crate) struct Macro {
+    ellipsis: Symbol,
+    literals: Vec<Symbol>,
+    rules: Vec<(Value, Value)>,
+    scope: Rc<Scope>,
+}
+
+/**
+ * * what a pattern variable matched, one value or a sequence under an ellipsis
+ */
+#[derive(Clone)]
+enum Match {
+    One(Value),
+    Many(Vec<Match>),
+}
+
+/**
+ * * right hand side of a definition, (define (f x) ...) is kept as a lambda to build
+ */
+enum Definition {
+    Expr(Value),
+    Lambda(Value, Vec<Value>),
+}
+
+/**
+ * * a parsed clause of cond or case, the test is None for the else clause
+ */
+struct Clause {
+    test: Option<Value>,
+    action: Action,
+}
+
+enum Action {
+    // * (test) the value of the test
+    Test,
+    // * (test => receiver)
+    Receiver(Value),
+    // * (test expr ...) already made one expression
+    Sequence(Value),
+}
+
+enum BodyForm {
+    Define(String, Definition),
+    Expr(Value),
+}
+
+fn symbol(s: &str) -> Value {
+    Value::Symbol(Symbol::intern(s))
+}
+
+// * the value of a form without a useful one, like a when whose test fails
+fn unspecified() -> Value {
+    Value::list(vec![symbol("let"), Value::Nil])
+}
+
+fn vector(items: Vec<Value>) -> Value {
+    Value::Vector(Rc::new(Vector::new(items)))
+}
+
+/**
+ * * check that the lists and vectors of a form nest no deeper than the reader allows
+ * ! a form built at run time for eval has no such limit, expanding it would recurse off the end of the stack
+ * ! each pair and vector is visited again only at a greater depth, so shared and circular data ends as well
+ */
+fn check_depth(form: &Value) -> Result<(), RuntimeError> {
+    let mut pending = vec![(form.clone(), 0)];
+    let mut seen: HashMap<usize, usize> = HashMap::new();
+    while let Some((v, depth)) = pending.pop() {
+        let address = match &v {
+            Value::Pair(p) => Rc::as_ptr(p) as *const () as usize,
+            Value::Vector(items) => Rc::as_ptr(items) as *const () as usize,
+            _ => continue,
+        };
+        if seen.get(&address).is_some_and(|d| *d >= depth) {
+            continue;
+        }
+        if depth >= MAX_DEPTH {
+            runtime_error!("bad syntax: nesting deeper than {} levels", MAX_DEPTH);
+        }
+        seen.insert(address, depth);
+        match v {
+            Value::Pair(p) => {
+                pending.push((p.cdr(), depth));
+                pending.push((p.car(), depth + 1));
+            },
+            Value::Vector(items) => pending.extend(items.borrow().iter().map(|v| (v.clone(), depth + 1))),
+            _ => {},
+        }
+    }
+    Ok(())
+}
+
+fn vector_items(v: &Value) -> Vec<Value> {
+    match v {
+        Value::Vector(items) => items.borrow().clone(),
+        _ => Vec::new(),
+    }
+}
+
+/**
+ * * the macro expansion pass between Parser::parse and evaluation
+ * * it expands syntax-rules macros hygienically and produces core forms only:
+ * * every local binding is renamed apart and template identifiers are resolved where the macro is defined
+ */
+pub struct Expander {
+    global: Rc<Scope>,
+    // * the library whose body is being expanded, its globals are renamed into its own namespace
+    namespace: Option<String>,
+    aliases: HashMap<String, Alias>,
+    counter: usize,
+}
+
+/**
+ * * the toplevel of a program or of a library, saved while the body of another library is expanded
+ */
+pub(crate) struct Toplevel {
+    global: Rc<Scope>,
+    namespace: Option<String>,
+}
+
+const KEYWORDS: [&str; 8] = ["define-syntax", "let-syntax", "letrec-syntax", "syntax-rules", "else", "=>", "import", "define-library"];
+
+/**
+ * * between a name and the counter in renamed identifiers, the reader never produces it
+ * * so no identifier the user writes can be mistaken for a renamed one
+ */
+pub const SEPARATOR: char = '\u{1}';
+
+// * the derived forms, the expander rewrites them into if, let, lambda and define
+const DERIVED: [&str; 8] = ["begin", "cond", "case", "and", "or", "when", "unless", "do"];
+
+// * a toplevel which only knows the special forms
+fn base_scope() -> Rc<Scope> {
+    let global = Scope::new(None);
+    for name in special_form_names().chain(KEYWORDS).chain(DERIVED) {
+        global.bind(name, Binding::Special(name));
+    }
+    global
+}
+
+impl Default for Expander {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Expander {
+    pub fn new() -> Expander {
+        Expander {
+            global: base_scope(),
+            namespace: None,
+            aliases: HashMap::new(),
+            counter: 0,
+        }
+    }
+
+    /**
+     * * the identifiers bound at toplevel, by definitions, imports and define-syntax
+     * ! identifiers renamed by a macro expansion are left out, nobody could type them
+     */
+    pub fn identifiers(&self) -> Vec<String> {
+        self.global.bindings.borrow().keys()
+            .filter(|id| !self.aliases.contains_key(id.as_str()))
+            .cloned()
+            .collect()
+    }
+
+    /**
+     * * the special forms and macros bound at toplevel
+     */
+    pub fn keywords(&self) -> Vec<String> {
+        self.identifiers().into_iter()
+            .filter(|id| !matches!(self.global.lookup(id), Some(Binding::Variable(_))))
+            .collect()
+    }
+
+    /**
+     * * the bindings every library starts from, for the built-in libraries to export
+     */
+    pub(crate) fn base_bindings() -> Vec<(String, Binding)> {
+        base_scope().bindings.borrow().iter().map(|(id, b)| (id.clone(), b.clone())).collect()
+    }
+
+    /**
+     * * start expanding the body of the library called namespace on a toplevel of its own
+     * * its definitions are renamed to `namespace.name` so they never clash with other globals
+     */
+    pub(crate) fn enter_library(&mut self, namespace: String) -> Toplevel {
+        Toplevel {
+            global: std::mem::replace(&mut self.global, base_scope()),
+            namespace: self.namespace.replace(namespace),
+        }
+    }
+
+    // * go back to the toplevel the library was entered from
+    pub(crate) fn leave_library(&mut self, saved: Toplevel) {
+        self.global = saved.global;
+        self.namespace = saved.namespace;
+    }
+
+    /**
+     * * what id means at the current toplevel, unbound identifiers are the global variables of that name
+     */
+    pub(crate) fn global_binding(&self, id: &str) -> Binding {
+        self.resolve(id, &self.global)
+    }
+
+    /**
+     * * bind an identifier imported from a library at the current toplevel
+     */
+    pub(crate) fn import(&mut self, id: &str, binding: Binding) {
+        self.global.bind(id, binding);
+    }
+
+    /**
+     * * the special form a toplevel form starts with
+     */
+    pub(crate) fn toplevel_keyword(&self, form: &Value) -> Option<&'static str> {
+        self.head_keyword(form, &self.global)
+    }
+
+    // * the runtime name of a global definition
+    fn global_name(&self, name: &str) -> String {
+        match &self.namespace {
+            Some(ns) => format!("{}.{}", ns, name),
+            None => name.to_string(),
+        }
+    }
+
+    /**
+     * * expand one top level form, definitions at the top level bind global names
+     * * a form which only exists at expansion time like define-syntax gives no forms,
+     * * a begin gives the forms in it
+     */
+    pub fn expand_toplevel(&mut self, form: &Value) -> Result<Vec<Value>, RuntimeError> {
+        check_depth(form)?;
+        let global = self.global.clone();
+        let form = self.expand_head(form.clone(), &global)?;
+
+        match self.head_keyword(&form, &global) {
+            Some("define") => {
+                let (id, def) = self.parse_define(&form)?;
+                let base = self.base_name(&id);
+                let name = self.global_name(&base);
+                global.bind(&base, Binding::Variable(name.clone()));
+                global.bind(&id, Binding::Variable(name.clone()));
+                let res = Value::list(vec![symbol("define"), symbol(&name), self.expand_definition(def, &global)?]);
+                Ok(vec![res.with_span(form.span())])
+            },
+            Some("define-syntax") => {
+                self.define_syntax(&form, &global)?;
+                Ok(Vec::new())
+            },
+            // * the forms of a toplevel begin are toplevel forms, definitions bind global names
+            Some("begin") => {
+                let mut res = Vec::new();
+                for form in &self.form_items(&form)?[1..] {
+                    res.extend(self.expand_toplevel(form)?);
+                }
+                Ok(res)
+            },
+            _ => Ok(vec![self.expand(&form, &global)?]),
+        }
+    }
+
+    fn resolve(&self, id: &str, scope: &Rc<Scope>) -> Binding {
+        match scope.lookup(id) {
+            Some(b) => b,
+            None => match self.aliases.get(id) {
+                Some(alias) => self.resolve(&alias.name, &alias.scope),
+                None => Binding::Variable(id.to_string()),
+            },
+        }
+    }
+
+    // * the identifier the user originally wrote, looking through every alias
+    fn base_name(&self, id: &str) -> String {
+        match self.aliases.get(id) {
+            Some(alias) => self.base_name(&alias.name),
+            None => id.to_string(),
+        }
+    }
+
+    /**
+     * * the name a variable renamed by fresh was written with
+     */
+    pub fn source_name(id: &str) -> &str {
+        match id.split_once(SEPARATOR) {
+            Some((name, _)) => name,
+            None => id,
+        }
+    }
+
+    fn fresh(&mut self, id: &str) -> String {
+        self.counter += 1;
+        format!("{}{}{}", self.base_name(id), SEPARATOR, self.counter)
+    }
+
+    /**
+     * * turn every alias back into the identifier the user wrote, for quoted data
+     */
+    fn strip(&self, value: &Value) -> Value {
+        match value {
+            Value::Symbol(s) => Value::Symbol(Symbol::intern(&self.base_name(s))),
+            Value::Pair(_) => {
+                let (items, tail) = value.parts();
+                let tail = match tail {
+                    // * the rest of a circular list is kept as it is
+                    Value::Pair(_) => tail,
+                    tail => self.strip(&tail),
+                };
+                Value::list_with_tail(items.iter().map(|v| self.strip(v)).collect(), tail)
+            },
+            Value::Vector(items) => vector(items.borrow().iter().map(|v| self.strip(v)).collect()),
+            _ => value.clone(),
+        }
+    }
+
+    fn head_binding(&self, form: &Value, scope: &Rc<Scope>) -> Option<Binding> {
+        match form {
+            Value::Pair(p) => match p.car() {
+                Value::Symbol(id) => Some(self.resolve(&id, scope)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn head_keyword(&self, form: &Value, scope: &Rc<Scope>) -> Option<&'static str> {
+        match self.head_binding(form, scope) {
+            Some(Binding::Special(k)) => Some(k),
+            _ => None,
+        }
+    }
+
+    fn head_macro(&self, form: &Value, scope: &Rc<Scope>) -> Option<Rc<Macro>> {
+        match self.head_binding(form, scope) {
+            Some(Binding::Macro(m)) => Some(m),
+            _ => None,
+        }
+    }
+
+    // * the elements of a form which must be a proper list
+    fn form_items(&self, form: &Value) -> Result<Vec<Value>, RuntimeError> {
+        match form.to_vec() {
+            Some(items) => Ok(items),
+            None => runtime_error!("bad syntax: expect a proper list: {:?}", (self.strip(form))),
+        }
+    }
+
+    /**
+     * * expand macro uses in head position until the form is no longer a macro use
+     */
+    fn expand_head(&mut self, mut form: Value, scope: &Rc<Scope>) -> Result<Value, RuntimeError> {
+        while let Some(m) = self.head_macro(&form, scope) {
+            form = self.transcribe(&m, &form, scope)?;
+        }
+        Ok(form)
+    }
+
+    fn expand(&mut self, form: &Value, scope: &Rc<Scope>) -> Result<Value, RuntimeError> {
+        match form {
+            Value::Symbol(id) => match self.resolve(id, scope) {
+                Binding::Variable(name) => Ok(symbol(&name)),
+                _ => runtime_error!("bad syntax: keyword {} used as an expression", (self.base_name(id))),
+            },
+            Value::Pair(_) => {
+                let items = self.form_items(form)?;
+                if let Value::Symbol(id) = &items[0] {
+                    match self.resolve(id, scope) {
+                        Binding::Special(k) => return Ok(self.expand_special(k, &items, scope)?.with_span(form.span())),
+                        Binding::Macro(m) => {
+                            let form = self.transcribe(&m, form, scope)?;
+                            return self.expand(&form, scope);
+                        },
+                        Binding::Variable(_) => {},
+                    }
+                }
+                let res = Value::list(items.iter().map(|v| self.expand(v, scope)).collect::<Result<Vec<Value>, RuntimeError>>()?);
+                Ok(res.with_span(form.span()))
+            },
+            _ => Ok(form.clone()),
+        }
+    }
+
+    fn expand_special(&mut self, keyword: &'static str, items: &[Value], scope: &Rc<Scope>) -> Result<Value, RuntimeError> {
+        match keyword {
+            "quote" => {
+                let mut res = vec![symbol("quote")];
+                res.extend(items[1..].iter().map(|v| self.strip(v)));
+                Ok(Value::list(res))
+            },
+            "quasiquote" => {
+                let mut res = vec![symbol("quasiquote")];
+                for template in &items[1..] {
+                    res.push(self.expand_quasi(template, 1, scope)?);
+                }
+                Ok(Value::list(res))
+            },
+            "lambda" => match items.get(1) {
+                Some(params) => self.expand_lambda(params, &items[2..], scope),
+                None => runtime_error!("bad syntax: lambda without parameters: {:?}", (self.strip(&Value::list(items.to_vec())))),
+            },
+            "let" => self.expand_let(items, scope),
+            "guard" => self.expand_guard(items, scope),
+            "begin" => match items.len() {
+                1 => runtime_error!("bad syntax: begin expects at least one expression"),
+                _ => self.sequence(&items[1..], scope),
+            },
+            "cond" => self.expand_cond(items, scope),
+            "case" => self.expand_case(items, scope),
+            "and" => self.expand_and(&items[1..], scope),
+            "or" => self.expand_or(&items[1..], scope),
+            "when" | "unless" => self.expand_when(keyword, items, scope),
+            "do" => self.expand_do(items, scope),
+            "let-syntax" | "letrec-syntax" => self.expand_let_syntax(keyword, items, scope),
+            "define" | "define-syntax" => runtime_error!("bad syntax: {} is only allowed at the top level or at the beginning of a body", keyword),
+            "import" | "define-library" => runtime_error!("bad syntax: {} is only allowed at the top level", keyword),
+            "syntax-rules" => runtime_error!("bad syntax: syntax-rules outside of define-syntax"),
+            "unquote" | "unquote-splicing" => runtime_error!("bad syntax: {} outside of quasiquote", keyword),
+            "else" | "=>" => runtime_error!("bad syntax: {} outside of a clause", keyword),
+            _ => {
+                let mut res = vec![symbol(keyword)];
+                for v in &items[1..] {
+                    res.push(self.expand(v, scope)?);
+                }
+                Ok(Value::list(res))
+            },
+        }
+    }
+
+    /**
+     * * walk a quasiquote template, only the parts unquoted back to level 0 are expanded
+     */
+    fn expand_quasi(&mut self, template: &Value, depth: usize, scope: &Rc<Scope>) -> Result<Value, RuntimeError> {
+        match template {
+            Value::Pair(_) => {},
+            Value::Vector(_) => {
+                let items = self.expand_quasi(&Value::list(vector_items(template)), depth, scope)?;
+                return Ok(vector(items.to_vec().unwrap_or_default()));
+            },
+            _ => return Ok(self.strip(template)),
+        };
+
+        match self.quasi_form(template, scope) {
+            Some((k @ "quasiquote", inner)) => {
+                return Ok(Value::list(vec![symbol(k), self.expand_quasi(&inner, depth + 1, scope)?]));
+            },
+            Some((k, inner)) => {
+                let inner = match depth {
+                    1 => self.expand(&inner, scope)?,
+                    _ => self.expand_quasi(&inner, depth - 1, scope)?,
+                };
+                return Ok(Value::list(vec![symbol(k), inner]));
+            },
+            None => {},
+        }
+
+        // * pair by pair, so an unquote in the tail of a dotted template is found as well
+        let mut items = Vec::new();
+        let mut tail = template.clone();
+        let mut seen = HashSet::new();
+        while let Value::Pair(p) = tail.clone() {
+            if !items.is_empty() && self.quasi_form(&tail, scope).is_some() {
+                break;
+            }
+            if !seen.insert(Rc::as_ptr(&p)) {
+                runtime_error!("bad syntax: circular quasiquote template");
+            }
+            items.push(self.expand_quasi(&p.car(), depth, scope)?);
+            tail = p.cdr();
+        }
+        Ok(Value::list_with_tail(items, self.expand_quasi(&tail, depth, scope)?))
+    }
+
+    // * the keyword and the operand of (quasiquote x), (unquote x) or (unquote-splicing x)
+    fn quasi_form(&self, form: &Value, scope: &Rc<Scope>) -> Option<(&'static str, Value)> {
+        let keyword = match self.head_keyword(form, scope)? {
+            k @ ("quasiquote" | "unquote" | "unquote-splicing") => k,
+            _ => return None,
+        };
+        match form {
+            Value::Pair(p) => match p.cdr() {
+                Value::Pair(rest) if matches!(rest.cdr(), Value::Nil) => Some((keyword, rest.car())),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn expand_lambda(&mut self, params: &Value, body: &[Value], scope: &Rc<Scope>) -> Result<Value, RuntimeError> {
+        // * (a b . rest) or a lone rest identifier collect the extra arguments
+        let (mut params, rest) = params.parts();
+        let dotted = match rest {
+            Value::Nil => false,
+            Value::Symbol(_) => { params.push(rest); true },
+            _ => runtime_error!("bad syntax: lambda parameters must be a list: {:?}", (self.strip(&rest))),
+        };
+
+        let inner = Scope::new(Some(scope.clone()));
+        let mut renamed = Vec::new();
+        for p in &params {
+            match p {
+                Value::Symbol(id) => {
+                    let name = self.fresh(id);
+                    inner.bind(id, Binding::Variable(name.clone()));
+                    renamed.push(symbol(&name));
+                },
+                _ => runtime_error!("bad syntax: lambda parameter must be an identifier: {:?}", (self.strip(p))),
+            }
+        }
+
+        let tail = if dotted { renamed.pop().unwrap() } else { Value::Nil };
+        let mut res = vec![symbol("lambda"), Value::list_with_tail(renamed, tail)];
+        res.extend(self.expand_body(body, &inner)?);
+        Ok(Value::list(res))
+    }
+
+    /**
+     * * (let ((id init) ...) body...) the inits are expanded outside, the body inside the new scope
+     */
+    fn expand_let(&mut self, items: &[Value], scope: &Rc<Scope>) -> Result<Value, RuntimeError> {
+        let bindings = match items.get(1).and_then(Value::to_vec) {
+            Some(bindings) => bindings,
+            None => runtime_error!("bad syntax: let expects a binding list: {:?}", (self.strip(&Value::list(items.to_vec())))),
+        };
+
+        let inner = Scope::new(Some(scope.clone()));
+        let mut renamed = Vec::new();
+        for binding in &bindings {
+            match binding.to_vec() {
+                Some(pair) if pair.len() == 2 => match &pair[0] {
+                    Value::Symbol(id) => {
+                        let init = self.expand(&pair[1], scope)?;
+                        let name = self.fresh(id);
+                        inner.bind(id, Binding::Variable(name.clone()));
+                        renamed.push(Value::list(vec![symbol(&name), init]));
+                    },
+                    _ => runtime_error!("bad syntax: let binds identifiers only: {:?}", (self.strip(binding))),
+                },
+                _ => runtime_error!("bad syntax: invalid let binding: {:?}", (self.strip(binding))),
+            }
+        }
+
+        let mut res = vec![symbol("let"), Value::list(renamed)];
+        res.extend(self.expand_body(&items[2..], &inner)?);
+        Ok(Value::list(res))
+    }
+
+    /**
+     * * (guard (id clause ...) body...) id is bound for the clauses only
+     */
+    fn expand_guard(&mut self, items: &[Value], scope: &Rc<Scope>) -> Result<Value, RuntimeError> {
+        let spec = match items.get(1).and_then(Value::to_vec) {
+            Some(spec) => spec,
+            None => runtime_error!("bad syntax: guard expects (variable clause ...): {:?}", (self.strip(&Value::list(items.to_vec())))),
+        };
+        let (id, clauses) = match spec.split_first() {
+            Some((Value::Symbol(id), clauses)) => (id, clauses),
+            _ => runtime_error!("bad syntax: guard expects (variable clause ...): {:?}", (self.strip(&Value::list(spec)))),
+        };
+
+        let inner = Scope::new(Some(scope.clone()));
+        let name = self.fresh(id);
+        inner.bind(id, Binding::Variable(name.clone()));
+        let mut renamed = vec![symbol(&name)];
+        for clause in clauses {
+            renamed.push(self.expand_clause(clause, &inner)?);
+        }
+
+        let mut res = vec![symbol("guard"), Value::list(renamed)];
+        res.extend(self.expand_body(&items[2..], &Scope::new(Some(scope.clone())))?);
+        Ok(Value::list(res))
+    }
+
+    /**
+     * * a clause (test expr ...), (test => receiver) or (else expr ...)
+     * * else and => are recognized by their binding, a local variable of the same name shadows them
+     */
+    fn expand_clause(&mut self, clause: &Value, scope: &Rc<Scope>) -> Result<Value, RuntimeError> {
+        let items = self.form_items(clause)?;
+        let mut res = Vec::new();
+        for (i, item) in items.iter().enumerate() {
+            let keyword = match i {
+                0 => "else",
+                _ => "=>",
+            };
+            match item {
+                Value::Symbol(id) if i < 2 && self.resolve(id, scope) == Binding::Special(keyword) => res.push(symbol(keyword)),
+                _ => res.push(self.expand(item, scope)?),
+            }
+        }
+        Ok(Value::list(res))
+    }
+
+    // * whether v is the identifier for keyword here, a local variable of the same name is not
+    fn is_keyword(&self, v: &Value, keyword: &'static str, scope: &Rc<Scope>) -> bool {
+        matches!(v, Value::Symbol(id) if self.resolve(id, scope) == Binding::Special(keyword))
+    }
+
+    /**
+     * * expressions evaluated in order, the last one in tail position
+     * * one expression stands for itself, none has an unspecified value
+     */
+    fn sequence(&mut self, forms: &[Value], scope: &Rc<Scope>) -> Result<Value, RuntimeError> {
+        let mut exprs = forms.iter().map(|f| self.expand(f, scope)).collect::<Result<Vec<Value>, RuntimeError>>()?;
+        match exprs.len() {
+            1 => Ok(exprs.pop().unwrap()),
+            _ => {
+                let mut res = vec![symbol("let"), Value::Nil];
+                res.extend(exprs);
+                Ok(Value::list(res))
+            },
+        }
+    }
+
+    // * (let ((temp value)) body) where body is made with the fresh name of temp
+    fn with_temp(&mut self, value: Value, body: impl FnOnce(Value) -> Value) -> Value {
+        let temp = symbol(&self.fresh("temp"));
+        let binding = Value::list(vec![temp.clone(), value]);
+        Value::list(vec![symbol("let"), Value::list(vec![binding]), body(temp)])
+    }
+
+    fn branch(test: Value, then: Value, otherwise: Value) -> Value {
+        Value::list(vec![symbol("if"), test, then, otherwise])
+    }
+
+    /**
+     * * a clause of cond or case: (test expr ...), (test => receiver) or (else expr ...)
+     * * the test is expanded by the caller, cond and case test differently
+     */
+    fn parse_clause(&mut self, form: &str, clause: &Value, last: bool, scope: &Rc<Scope>) -> Result<Clause, RuntimeError> {
+        let items = self.form_items(clause)?;
+        let (test, body) = match items.split_first() {
+            Some(split) => split,
+            None => runtime_error!("bad syntax: empty {} clause", form),
+        };
+        let is_else = self.is_keyword(test, "else", scope);
+        if is_else && !last {
+            runtime_error!("bad syntax: else must be the last {} clause: {:?}", form, (self.strip(clause)));
+        }
+        let action = match body {
+            [arrow, receiver] if self.is_keyword(arrow, "=>", scope) => Action::Receiver(self.expand(receiver, scope)?),
+            [] if is_else => runtime_error!("bad syntax: else clause without expressions in {}", form),
+            [] => Action::Test,
+            _ => Action::Sequence(self.sequence(body, scope)?),
+        };
+        Ok(Clause { test: if is_else { None } else { Some(test.clone()) }, action })
+    }
+
+    /**
+     * * (cond clause ...) becomes nested ifs, a clause without expressions gives the value of its test
+     * * without an else clause the value is unspecified when no test holds
+     */
+    fn expand_cond(&mut self, items: &[Value], scope: &Rc<Scope>) -> Result<Value, RuntimeError> {
+        let mut clauses = Vec::new();
+        for (i, clause) in items[1..].iter().enumerate() {
+            let clause = self.parse_clause("cond", clause, i == items.len() - 2, scope)?;
+            let test = match &clause.test {
+                Some(test) => Some(self.expand(test, scope)?),
+                None => None,
+            };
+            clauses.push((test, clause.action));
+        }
+
+        let mut res = unspecified();
+        for (test, action) in clauses.into_iter().rev() {
+            res = match (test, action) {
+                (None, Action::Sequence(seq)) => seq,
+                (None, Action::Receiver(_)) => runtime_error!("bad syntax: cond has no else => clause"),
+                (Some(test), Action::Sequence(seq)) => Self::branch(test, seq, res),
+                (Some(test), Action::Test) => self.with_temp(test, |t| Self::branch(t.clone(), t, res)),
+                (Some(test), Action::Receiver(f)) => self.with_temp(test, |t| Self::branch(t.clone(), Value::list(vec![f, t]), res)),
+                (None, Action::Test) => unreachable!(),
+            };
+        }
+        Ok(res)
+    }
+
+    /**
+     * * (case key ((datum ...) expr ...) ... (else expr ...)) the data are compared to the key with eqv?
+     * * a clause may pass the key to a receiver with =>, the else clause too
+     */
+    fn expand_case(&mut self, items: &[Value], scope: &Rc<Scope>) -> Result<Value, RuntimeError> {
+        let key = match items.get(1) {
+            Some(key) => self.expand(key, scope)?,
+            None => runtime_error!("bad syntax: case expects a key"),
+        };
+        let mut clauses = Vec::new();
+        for (i, clause) in items[2..].iter().enumerate() {
+            let clause = self.parse_clause("case", clause, i == items.len() - 3, scope)?;
+            let data = match &clause.test {
+                Some(data) => match data.to_vec() {
+                    Some(data) => Some(data.iter().map(|d| self.strip(d)).collect::<Vec<Value>>()),
+                    None => runtime_error!("bad syntax: case expects a list of data but got: {:?}", (self.strip(data))),
+                },
+                None => None,
+            };
+            if matches!(clause.action, Action::Test) {
+                runtime_error!("bad syntax: case clause without expressions: {:?}", (self.strip(&items[2 + i])));
+            }
+            clauses.push((data, clause.action));
+        }
+
+        Ok(self.with_temp(key, |k| {
+            let mut res = unspecified();
+            for (data, action) in clauses.into_iter().rev() {
+                let then = match action {
+                    Action::Sequence(seq) => seq,
+                    Action::Receiver(f) => Value::list(vec![f, k.clone()]),
+                    Action::Test => unreachable!(),
+                };
+                res = match data {
+                    None => then,
+                    Some(data) => {
+                        // * (eqv? k 'd1) or (eqv? k 'd2) ... as ifs, no datum is false
+                        let test = data.into_iter().rev().fold(Value::Boolean(false), |rest, d| {
+                            let same = Value::list(vec![symbol("eqv?"), k.clone(), Value::list(vec![symbol("quote"), d])]);
+                            Self::branch(same, Value::Boolean(true), rest)
+                        });
+                        Self::branch(test, then, res)
+                    },
+                };
+            }
+            res
+        }))
+    }
+
+    /**
+     * * (and e ...) the first false value or the last value, #t without expressions
+     */
+    fn expand_and(&mut self, exprs: &[Value], scope: &Rc<Scope>) -> Result<Value, RuntimeError> {
+        match exprs.split_first() {
+            None => Ok(Value::Boolean(true)),
+            Some((last, [])) => self.expand(last, scope),
+            Some((first, rest)) => Ok(Self::branch(self.expand(first, scope)?, self.expand_and(rest, scope)?, Value::Boolean(false))),
+        }
+    }
+
+    /**
+     * * (or e ...) the first true value, #f without expressions
+     */
+    fn expand_or(&mut self, exprs: &[Value], scope: &Rc<Scope>) -> Result<Value, RuntimeError> {
+        match exprs.split_first() {
+            None => Ok(Value::Boolean(false)),
+            Some((last, [])) => self.expand(last, scope),
+            Some((first, rest)) => {
+                let first = self.expand(first, scope)?;
+                let rest = self.expand_or(rest, scope)?;
+                Ok(self.with_temp(first, |t| Self::branch(t.clone(), t, rest)))
+            },
+        }
+    }
+
+    /**
+     * * (when test expr ...) and (unless test expr ...), unspecified when the body is not run
+     */
+    fn expand_when(&mut self, keyword: &str, items: &[Value], scope: &Rc<Scope>) -> Result<Value, RuntimeError> {
+        if items.len() < 3 {
+            runtime_error!("bad syntax: {} expects a test and at least one expression", keyword);
+        }
+        let test = self.expand(&items[1], scope)?;
+        let body = self.sequence(&items[2..], scope)?;
+        match keyword {
+            "when" => Ok(Self::branch(test, body, unspecified())),
+            _ => Ok(Self::branch(test, unspecified(), body)),
+        }
+    }
+
+    /**
+     * * (do ((var init step) ...) (test expr ...) command ...) becomes a loop procedure called in tail position
+     * * a variable without a step keeps its value, without exprs the value is unspecified
+     */
+    fn expand_do(&mut self, items: &[Value], scope: &Rc<Scope>) -> Result<Value, RuntimeError> {
+        let (specs, exit) = match (items.get(1).and_then(Value::to_vec), items.get(2).and_then(Value::to_vec)) {
+            (Some(specs), Some(exit)) if !exit.is_empty() => (specs, exit),
+            _ => runtime_error!("bad syntax: do expects variables and a test clause: {:?}", (self.strip(&Value::list(items.to_vec())))),
+        };
+
+        let inner = Scope::new(Some(scope.clone()));
+        let mut vars = Vec::new();
+        for spec in &specs {
+            match spec.to_vec().as_deref() {
+                Some([Value::Symbol(id), init, step @ ..]) if step.len() <= 1 => {
+                    let init = self.expand(init, scope)?;
+                    let name = self.fresh(id);
+                    inner.bind(id, Binding::Variable(name.clone()));
+                    vars.push((symbol(&name), init, step.first().cloned()));
+                },
+                _ => runtime_error!("bad syntax: do expects (variable init [step]): {:?}", (self.strip(spec))),
+            }
+        }
+
+        let lp = symbol(&self.fresh("loop"));
+        let mut next = vec![lp.clone()];
+        for (name, _, step) in &vars {
+            next.push(match step {
+                Some(step) => self.expand(step, &inner)?,
+                None => name.clone(),
+            });
+        }
+        let test = self.expand(&exit[0], &inner)?;
+        let done = self.sequence(&exit[1..], &inner)?;
+        let mut again = vec![symbol("let"), Value::Nil];
+        for command in &items[3..] {
+            again.push(self.expand(command, &inner)?);
+        }
+        again.push(Value::list(next));
+
+        let params = Value::list(vars.iter().map(|(name, _, _)| name.clone()).collect());
+        let body = Self::branch(test, done, Value::list(again));
+        let procedure = Value::list(vec![symbol("lambda"), params, body]);
+        let mut start = vec![lp.clone()];
+        start.extend(vars.into_iter().map(|(_, init, _)| init));
+        Ok(Value::list(vec![symbol("let"), Value::Nil, Value::list(vec![symbol("define"), lp, procedure]), Value::list(start)]))
+    }
+
+    /**
+     * * (let-syntax ((name (syntax-rules ...)) ...) body...) becomes (let () body...)
+     * * the transformers of letrec-syntax can see each other, those of let-syntax can not
+     */
+    fn expand_let_syntax(&mut self, keyword: &str, items: &[Value], scope: &Rc<Scope>) -> Result<Value, RuntimeError> {
+        let bindings = match items.get(1).and_then(Value::to_vec) {
+            Some(bindings) => bindings,
+            None => runtime_error!("bad syntax: {} expects a binding list", keyword),
+        };
+
+        let inner = Scope::new(Some(scope.clone()));
+        let def_scope = match keyword {
+            "letrec-syntax" => inner.clone(),
+            _ => scope.clone(),
+        };
+        for binding in &bindings {
+            match binding.to_vec() {
+                Some(pair) if pair.len() == 2 => match &pair[0] {
+                    Value::Symbol(id) => {
+                        let m = self.parse_syntax_rules(&pair[1], &def_scope)?;
+                        inner.bind(id, Binding::Macro(m));
+                    },
+                    _ => runtime_error!("bad syntax: {} binds identifiers only", keyword),
+                },
+                _ => runtime_error!("bad syntax: invalid {} binding: {:?}", keyword, (self.strip(binding))),
+            }
+        }
+
+        let mut res = vec![symbol("let"), Value::Nil];
+        res.extend(self.expand_body(&items[2..], &inner)?);
+        Ok(Value::list(res))
+    }
+
+    /**
+     * * expand a lambda or let body: internal definitions are collected first so the
+     * * expressions (and the other definitions) can refer to all of them
+     */
+    fn expand_body(&mut self, forms: &[Value], scope: &Rc<Scope>) -> Result<Vec<Value>, RuntimeError> {
+        let mut queue: VecDeque<Value> = forms.iter().cloned().collect();
+        let mut pending = Vec::new();
+        let mut defined = HashSet::new();
+
+        while let Some(form) = queue.pop_front() {
+            let form = self.expand_head(form, scope)?;
+            match self.head_keyword(&form, scope) {
+                // * a begin in a body is spliced, the definitions in it are definitions of the body
+                Some("begin") => {
+                    for inner in self.form_items(&form)?[1..].iter().rev() {
+                        queue.push_front(inner.clone());
+                    }
+                },
+                Some("define") => {
+                    let (id, def) = self.parse_define(&form)?;
+                    if !defined.insert(id.clone()) {
+                        runtime_error!("The identifier is already defined!: {:?}", (self.base_name(&id)));
+                    }
+                    let name = self.fresh(&id);
+                    scope.bind(&id, Binding::Variable(name.clone()));
+                    pending.push(BodyForm::Define(name, def));
+                },
+                Some("define-syntax") => self.define_syntax(&form, scope)?,
+                _ => pending.push(BodyForm::Expr(form)),
+            }
+        }
+
+        pending.into_iter().map(|f| {
+            match f {
+                BodyForm::Define(name, def) => Ok(Value::list(vec![symbol("define"), symbol(&name), self.expand_definition(def, scope)?])),
+                BodyForm::Expr(form) => self.expand(&form, scope),
+            }
+        }).collect()
+    }
+
+    /**
+     * * (define id expr) or (define (id params...) body...)
+     */
+    fn parse_define(&self, form: &Value) -> Result<(String, Definition), RuntimeError> {
+        let items = self.form_items(form)?;
+
+        match (items.get(1), items.len()) {
+            (Some(Value::Symbol(id)), 3) => Ok((id.to_string(), Definition::Expr(items[2].clone()))),
+            (Some(Value::Pair(sig)), n) if n >= 3 => match sig.car() {
+                Value::Symbol(id) => Ok((id.to_string(), Definition::Lambda(sig.cdr(), items[2..].to_vec()))),
+                _ => runtime_error!("bad syntax: must supply a symbol as define name: {:?}", (self.strip(form))),
+            },
+            _ => runtime_error!("bad syntax: invalid define: {:?}", (self.strip(form))),
+        }
+    }
+
+    fn expand_definition(&mut self, def: Definition, scope: &Rc<Scope>) -> Result<Value, RuntimeError> {
+        match def {
+            Definition::Expr(expr) => self.expand(&expr, scope),
+            Definition::Lambda(params, body) => self.expand_lambda(&params, &body, scope),
+        }
+    }
+
+    /**
+     * * (define-syntax name (syntax-rules ...)) binds the macro in the given scope
+     */
+    fn define_syntax(&mut self, form: &Value, scope: &Rc<Scope>) -> Result<(), RuntimeError> {
+        match form.to_vec() {
+            Some(items) if items.len() == 3 => match &items[1] {
+                Value::Symbol(id) => {
+                    let m = self.parse_syntax_rules(&items[2], scope)?;
+                    scope.bind(id, Binding::Macro(m));
+                    Ok(())
+                },
+                _ => runtime_error!("bad syntax: define-syntax expects an identifier: {:?}", (self.strip(form))),
+            },
+            _ => runtime_error!("bad syntax: invalid define-syntax: {:?}", (self.strip(form))),
+        }
+    }
+
+    /**
+     * * (syntax-rules (literal ...) (pattern template) ...)
+     * * (syntax-rules ellipsis (literal ...) (pattern template) ...)
+     */
+    fn parse_syntax_rules(&self, spec: &Value, scope: &Rc<Scope>) -> Result<Rc<Macro>, RuntimeError> {
+        let items = match spec.to_vec() {
+            Some(items) if self.head_keyword(spec, scope) == Some("syntax-rules") => items,
+            _ => runtime_error!("bad syntax: expect a syntax-rules transformer: {:?}", (self.strip(spec))),
+        };
+
+        let (ellipsis, rest) = match items.get(1) {
+            Some(Value::Symbol(e)) => (*e, &items[2..]),
+            _ => (Symbol::intern("..."), &items[1..]),
+        };
+
+        let literals = match rest.first().and_then(Value::to_vec) {
+            Some(ls) => ls.iter().map(|l| match l {
+                Value::Symbol(s) => Ok(*s),
+                _ => runtime_error!("bad syntax: syntax-rules literals must be identifiers: {:?}", (self.strip(l))),
+            }).collect::<Result<Vec<Symbol>, RuntimeError>>()?,
+            _ => runtime_error!("bad syntax: syntax-rules expects a literal list: {:?}", (self.strip(spec))),
+        };
+
+        let rules = rest[1..].iter().map(|rule| match rule.to_vec() {
+            Some(r) if r.len() == 2 && matches!(r[0], Value::Pair(_)) => Ok((r[0].clone(), r[1].clone())),
+            _ => runtime_error!("bad syntax: invalid syntax-rules rule: {:?}", (self.strip(rule))),
+        }).collect::<Result<Vec<(Value, Value)>, RuntimeError>>()?;
+
+        Ok(Rc::new(Macro {
+            ellipsis,
+            literals,
+            rules,
+            scope: scope.clone(),
+        }))
+    }
+
+    /**
+     * * rewrite a macro use with the first matching rule
+     */
+    fn transcribe(&mut self, m: &Rc<Macro>, form: &Value, use_scope: &Rc<Scope>) -> Result<Value, RuntimeError> {
+        let args = match form {
+            Value::Pair(p) => p.cdr(),
+            _ => runtime_error!("bad syntax: invalid macro use: {:?}", (self.strip(form))),
+        };
+
+        for (pattern, template) in &m.rules {
+            // * the keyword position of the pattern is ignored
+            let pattern = match pattern {
+                Value::Pair(p) => p.cdr(),
+                _ => continue,
+            };
+            let mut binds = HashMap::new();
+            if self.match_pattern(m, &pattern, &args, use_scope, &mut binds) {
+                let mut renames = HashMap::new();
+                // * the expansion is reported at the macro use
+                return Ok(self.instantiate(m, template, &binds, &mut renames, false)?.with_span(form.span()));
+            }
+        }
+
+        runtime_error!("bad syntax: no syntax-rules pattern matches: {:?}", (self.strip(form)))
+    }
+
+    fn is_ellipsis(&self, m: &Macro, v: &Value) -> bool {
+        matches!(v, Value::Symbol(s) if *s == m.ellipsis && !m.literals.contains(s))
+    }
+
+    fn pattern_vars(&self, m: &Macro, pattern: &Value, vars: &mut Vec<Symbol>) {
+        match pattern {
+            Value::Symbol(s) if s != "_" && !m.literals.contains(s) && *s != m.ellipsis => vars.push(*s),
+            Value::Pair(_) => {
+                let (ps, tail) = pattern.parts();
+                ps.iter().for_each(|p| self.pattern_vars(m, p, vars));
+                self.pattern_vars(m, &tail, vars);
+            },
+            Value::Vector(items) => items.borrow().iter().for_each(|p| self.pattern_vars(m, p, vars)),
+            _ => {},
+        }
+    }
+
+    fn match_pattern(&self, m: &Macro, pattern: &Value, input: &Value, use_scope: &Rc<Scope>, binds: &mut HashMap<Symbol, Match>) -> bool {
+        match pattern {
+            Value::Symbol(p) if m.literals.contains(p) => match input {
+                // * a literal matches an identifier with the same binding
+                Value::Symbol(i) => self.resolve(i, use_scope) == self.resolve(p, &m.scope),
+                _ => false,
+            },
+            Value::Symbol(p) if p == "_" => true,
+            Value::Symbol(p) => {
+                binds.insert(*p, Match::One(input.clone()));
+                true
+            },
+            Value::Pair(_) => {
+                // * (p ... . ptail) against (i ... . itail), a () tail only matches a proper list
+                let (ps, ptail) = pattern.parts();
+                let (is, itail) = input.parts();
+                match ps.iter().position(|p| self.is_ellipsis(m, p)) {
+                    Some(e) if e >= 1 => {
+                        let (before, repeated, after) = (&ps[..e - 1], &ps[e - 1], &ps[e + 1..]);
+                        if is.len() < before.len() + after.len() || !self.match_pattern(m, &ptail, &itail, use_scope, binds) {
+                            return false;
+                        }
+                        let tail = is.len() - after.len();
+                        let fixed = before.iter().zip(is.iter()).chain(after.iter().zip(is[tail..].iter()));
+                        for (p, i) in fixed {
+                            if !self.match_pattern(m, p, i, use_scope, binds) {
+                                return false;
+                            }
+                        }
+
+                        let mut seqs = Vec::new();
+                        for i in &is[before.len()..tail] {
+                            let mut b = HashMap::new();
+                            if !self.match_pattern(m, repeated, i, use_scope, &mut b) {
+                                return false;
+                            }
+                            seqs.push(b);
+                        }
+                        let mut vars = Vec::new();
+                        self.pattern_vars(m, repeated, &mut vars);
+                        for var in vars {
+                            let many = seqs.iter_mut().filter_map(|b| b.remove(&var)).collect();
+                            binds.insert(var, Match::Many(many));
+                        }
+                        true
+                    },
+                    _ => {
+                        if is.len() < ps.len() || (ptail == Value::Nil && is.len() != ps.len()) {
+                            return false;
+                        }
+                        let rest = Value::list_with_tail(is[ps.len()..].to_vec(), itail);
+                        ps.iter().zip(is.iter()).all(|(p, i)| self.match_pattern(m, p, i, use_scope, binds))
+                            && self.match_pattern(m, &ptail, &rest, use_scope, binds)
+                    },
+                }
+            },
+            Value::Vector(_) => match input {
+                Value::Vector(_) => self.match_pattern(m, &Value::list(vector_items(pattern)), &Value::list(vector_items(input)), use_scope, binds),
+                _ => false,
+            },
+            _ => pattern == input,
+        }
+    }
+
+    /**
+     * * build the template: pattern variables are substituted and every other identifier
+     * * is renamed to an alias, the same identifier getting the same alias within one expansion
+     * ! `escaped` is set inside (... template) where the ellipsis has no special meaning
+     */
+    fn instantiate(&mut self, m: &Rc<Macro>, template: &Value, binds: &HashMap<Symbol, Match>, renames: &mut HashMap<String, String>, escaped: bool) -> Result<Value, RuntimeError> {
+        match template {
+            Value::Symbol(t) => match binds.get(t) {
+                Some(Match::One(v)) => Ok(v.clone()),
+                Some(Match::Many(_)) => runtime_error!("bad syntax: pattern variable {} used without an ellipsis", t),
+                None => Ok(symbol(&self.rename(t, m, renames))),
+            },
+            Value::Pair(_) => {
+                let (ts, tail) = template.parts();
+                if !escaped && ts.len() == 2 && tail == Value::Nil && self.is_ellipsis(m, &ts[0]) {
+                    return self.instantiate(m, &ts[1], binds, renames, true);
+                }
+
+                let mut res = Vec::new();
+                let mut i = 0;
+                while i < ts.len() {
+                    let mut depth = 0;
+                    while !escaped && i + 1 + depth < ts.len() && self.is_ellipsis(m, &ts[i + 1 + depth]) {
+                        depth += 1;
+                    }
+                    match depth {
+                        0 => res.push(self.instantiate(m, &ts[i], binds, renames, escaped)?),
+                        _ => res.extend(self.instantiate_ellipsis(m, &ts[i], binds, renames, depth)?),
+                    }
+                    i += 1 + depth;
+                }
+                let tail = self.instantiate(m, &tail, binds, renames, escaped)?;
+                Ok(Value::list_with_tail(res, tail))
+            },
+            Value::Vector(_) => {
+                let items = self.instantiate(m, &Value::list(vector_items(template)), binds, renames, escaped)?;
+                Ok(vector(items.to_vec().unwrap_or_default()))
+            },
+            _ => Ok(template.clone()),
+        }
+    }
+
+    fn instantiate_ellipsis(&mut self, m: &Rc<Macro>, template: &Value, binds: &HashMap<Symbol, Match>, renames: &mut HashMap<String, String>, depth: usize) -> Result<Vec<Value>, RuntimeError> {
+        let mut vars = Vec::new();
+        self.pattern_vars(m, template, &mut vars);
+        let seqs: Vec<(Symbol, Vec<Match>)> = vars.into_iter().filter_map(|v| match binds.get(&v) {
+            Some(Match::Many(ms)) => Some((v, ms.clone())),
+            _ => None,
+        }).collect();
+
+        let len = match seqs.first() {
+            Some((_, ms)) => ms.len(),
+            None => runtime_error!("bad syntax: no pattern variable to repeat in template: {:?}", (self.strip(template))),
+        };
+        if seqs.iter().any(|(_, ms)| ms.len() != len) {
+            runtime_error!("bad syntax: pattern variables of different lengths under one ellipsis: {:?}", (self.strip(template)));
+        }
+
+        let mut res = Vec::new();
+        for k in 0..len {
+            let mut b = binds.clone();
+            for (var, ms) in &seqs {
+                b.insert(*var, ms[k].clone());
+            }
+            match depth {
+                1 => res.push(self.instantiate(m, template, &b, renames, false)?),
+                _ => res.extend(self.instantiate_ellipsis(m, template, &b, renames, depth - 1)?),
+            }
+        }
+        Ok(res)
+    }
+
+    fn rename(&mut self, id: &str, m: &Rc<Macro>, renames: &mut HashMap<String, String>) -> String {
+        if let Some(alias) = renames.get(id) {
+            return alias.clone();
+        }
+        self.counter += 1;
+        let alias = format!("{}{}{}", id, SEPARATOR, self.counter);
+        self.aliases.insert(alias.clone(), Alias {
+            name: id.to_string(),
+            scope: m.scope.clone(),
+        });
+        renames.insert(id.to_string(), alias.clone());
+        alias
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::interpreter::tests::{eval_str, eval_write};
+
+    const MY_OR: &str = "(define-syntax my-or (syntax-rules ()
+        ((_) #f)
+        ((_ e) e)
+        ((_ e r ...) (let ((temp e)) (if temp temp (my-or r ...))))))";
+
+    #[test]
+    fn expand_recursive_macro() {
+        assert_eq!(eval_str(&format!("{} (my-or #f #f 3)", MY_OR)).unwrap(), Value::from(3));
+        assert_eq!(eval_str(&format!("{} (my-or)", MY_OR)).unwrap(), Value::Boolean(false));
+    }
+
+    #[test]
+    fn expand_avoids_capture() {
+        // * the temp bound by the macro must not capture the temp of the use site
+        let input = format!("{} (let ((temp 5)) (my-or #f temp))", MY_OR);
+        assert_eq!(eval_str(&input).unwrap(), Value::from(5));
+        // * shadowing if at the use site does not change the if of the template
+        let input = format!("{} (let ((if list)) (my-or #f 7))", MY_OR);
+        assert_eq!(eval_str(&input).unwrap(), Value::from(7));
+    }
+
+    #[test]
+    fn expand_free_identifier_refers_to_definition() {
+        let input = "(define x 10) (define-syntax get-x (syntax-rules () ((_) x))) (let ((x 20)) (get-x))";
+        assert_eq!(eval_str(input).unwrap(), Value::from(10));
+    }
+
+    #[test]
+    fn expand_ellipsis() {
+        let input = "(define-syntax sums (syntax-rules () ((_ (a b) ...) (list (+ a b) ...)))) (sums (1 2) (3 4))";
+        assert_eq!(eval_write(input), "(3 7)");
+        let input = "(define-syntax flip (syntax-rules () ((_ (a b ...) ...) '((b ... a) ...)))) (flip (1 2 3) (4) (5 6))";
+        assert_eq!(eval_write(input), "((2 3 1) (4) (6 5))");
+        let input = "(define-syntax tail (syntax-rules () ((_ a ... z) 'z))) (tail 1 2 3)";
+        assert_eq!(eval_write(input), "3");
+        let input = "(define-syntax rest (syntax-rules () ((_ a . r) 'r))) (rest 1 2 3)";
+        assert_eq!(eval_write(input), "(2 3)");
+        let input = "(define-syntax vec (syntax-rules () ((_ #(a ...)) (list 'a ...)))) (vec #(1 x))";
+        assert_eq!(eval_write(input), "(1 x)");
+        let input = "(define-syntax esc (syntax-rules () ((_ a) '(a (... ...))))) (esc 1)";
+        assert_eq!(eval_write(input), "(1 ...)");
+    }
+
+    #[test]
+    fn expand_literals() {
+        let input = "(define-syntax arrow (syntax-rules (=>) ((_ a => b) (list a b)) ((_ a b c) 'no)))";
+        assert_eq!(eval_write(&format!("{} (arrow 1 => 2)", input)), "(1 2)");
+        assert_eq!(eval_write(&format!("{} (arrow 1 + 2)", input)), "no");
+        // * a locally bound => is not the literal any more
+        assert_eq!(eval_write(&format!("{} (let ((=> 0)) (arrow 1 => 2))", input)), "no");
+    }
+
+    #[test]
+    fn expand_let_syntax() {
+        let input = "(define (f x) (let-syntax ((twice (syntax-rules () ((_ e) (+ e e))))) (twice x))) (f 21)";
+        assert_eq!(eval_str(input).unwrap(), Value::from(42));
+        let input = "(letrec-syntax ((my-count (syntax-rules () ((_) 0) ((_ x y ...) (+ 1 (my-count y ...)))))) (my-count a b c))";
+        assert_eq!(eval_str(input).unwrap(), Value::from(3));
+    }
+
+    #[test]
+    fn expand_internal_define_syntax() {
+        let input = "(define (g y) (define-syntax dbl (syntax-rules () ((_ e) (* 2 e)))) (define z (dbl y)) (+ z 1)) (g 4)";
+        assert_eq!(eval_str(input).unwrap(), Value::from(9));
+    }
+
+    #[test]
+    fn expand_rest_parameters() {
+        assert_eq!(eval_write("(define (f a . r) (list a r)) (f 1 2 3)"), "(1 (2 3))");
+        assert_eq!(eval_write("((lambda args args))"), "()");
+        // * the rest parameter is renamed like the others
+        let input = "(define-syntax rest-of (syntax-rules () ((_ e) (lambda (x . r) (list e r))))) (let ((r 'outer)) ((rest-of r) 1 2))";
+        assert_eq!(eval_write(input), "(outer (2))");
+        assert!(eval_str("(lambda (a . 1) a)").is_err());
+        assert!(eval_str("((lambda (a . r) a))").is_err());
+    }
+
+    #[test]
+    fn expand_errors() {
+        assert!(eval_str("(define-syntax m (syntax-rules () ((_ a) a))) (m)").is_err());
+        assert!(eval_str("(define-syntax m (syntax-rules () ((_ a ...) (list a)))) (m 1 2)").is_err());
+        assert!(eval_str("(list if)").is_err());
+    }
+
+    #[test]
+    fn expand_eval_datum() {
+        // * eval expands its datum like a toplevel form, derived forms and macros work there too
+        assert_eq!(eval_write("(list (eval '(when #t 1)) (eval '(and 1 2)) (eval '(cond (else 1))) (eval '(case 2 ((1) 'a) ((2) 'b))))"), "(1 2 1 b)");
+        assert_eq!(eval_write(&format!("{} (eval '(my-or #f 3))", MY_OR)), "3");
+        assert_eq!(eval_write("(eval '(begin (define z 4) (define w (+ z 1)))) (list z w)"), "(4 5)");
+        // * the forms run on the machine of the call, a collection among them keeps the frames of the caller
+        assert_eq!(eval_write("(define (f x) (let ((y (+ x 1))) (eval '(begin (gc) (define g 3))) (list x y g))) (f 1)"), "(1 2 3)");
+        assert_eq!(eval_write("(eval '(let ((x 1)) (do ((i 0 (+ i 1))) ((= i 3) (+ x i)))))"), "4");
+    }
+
+    #[test]
+    fn expand_renamed_names_do_not_collide() {
+        // * a user identifier spelled like a renamed local is a different variable
+        assert_eq!(eval_write("(define x.1 5) ((lambda (x) x.1) 0)"), "5");
+        assert_eq!(eval_write("(define temp.1 7) (define loop.2 9) (list (or #f temp.1) (do ((i 0 (+ i 1))) ((= i 2) loop.2)))"), "(7 9)");
+        assert_eq!(Expander::source_name(&format!("x.1{}3", SEPARATOR)), "x.1");
+        assert_eq!(Expander::source_name("x.1"), "x.1");
+    }
+
+    #[test]
+    fn expand_cond_and_case() {
+        // * only #f is false, the empty list is true
+        assert_eq!(eval_write("(cond ('() 1) (else 2))"), "1");
+        assert_eq!(eval_write("(cond (#f 1) (3))"), "3");
+        assert_eq!(eval_write("(cond ((assv 2 '((1 . a) (2 . b))) => cdr) (else 'none))"), "b");
+        assert_eq!(eval_write("(cond (#f 1))"), "()");
+        assert_eq!(eval_write("(case (* 2 3) ((2 3 5 7) 'prime) ((1 4 6 8 9) 'composite))"), "composite");
+        assert_eq!(eval_write("(case 'z ((a) 1) (else => (lambda (x) (list x x))))"), "(z z)");
+        // * a local named else is a variable, not the keyword
+        assert_eq!(eval_write("(let ((else #f)) (cond (else 1) (#t 2)))"), "2");
+        assert!(eval_str("(cond (else 1) (#t 2))").is_err());
+        assert!(eval_str("(case 1 (1 'one))").is_err());
+    }
+
+    #[test]
+    fn expand_and_or_when_unless() {
+        assert_eq!(eval_write("(list (and) (and 1 '()) (and 1 #f (car 1)))"), "(#true () #false)");
+        assert_eq!(eval_write("(list (or) (or #f 2 (car 1)) (or #f #f))"), "(#false 2 #false)");
+        assert_eq!(eval_write("(let ((temp 5)) (or #f temp))"), "5");
+        assert_eq!(eval_write("(list (when 0 1 2) (unless #f 3) (when #f 4))"), "(2 3 ())");
+    }
+
+    #[test]
+    fn expand_begin_and_do() {
+        assert_eq!(eval_write("(begin (define a 1) (define b (+ a 1))) b"), "2");
+        assert_eq!(eval_write("(define (f) (define x 1) (begin (define y 2)) (+ x y)) (f)"), "3");
+        assert_eq!(eval_write("(define-syntax two (syntax-rules () ((_ a b) (begin (define a 1) (define b 2))))) (two p q) (+ p q)"), "3");
+        assert!(eval_str("(list (begin))").is_err());
+        assert_eq!(eval_write("(do ((acc '()) (i 0 (+ i 1))) ((= i 4) acc) (set! acc (cons i acc)))"), "(3 2 1 0)");
+        assert_eq!(eval_write("(let ((i 10)) (do ((i 0 (+ i 1))) ((= i 3))) i)"), "10");
+        assert!(eval_str("(do ((i 0)) ())").is_err());
+    }
+}
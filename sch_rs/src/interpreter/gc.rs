@@ -0,0 +1,525 @@
+use super::eval::{Env, Function, Machine, RuntimeError, Tail, TailOperation, Value};
+use super::compile::{Op, Template};
+use super::address::{Body, Code};
+use super::vm::Locals;
+use super::strings::check_arity;
+use super::symbol::Symbol;
+use std::{cell::RefCell, collections::{HashMap, HashSet}, mem, rc::Rc};
+
+/**
+ * * the heap holding environments and vm frames, reclaimed by mark and sweep
+ * * a closure refers to its environment by a handle rather than an Rc, so a recursive
+ * * procedure stored in the very environment it closes over is no cycle that never gets freed
+ * ! a collection only happens at a safe point of the machine, which hands over everything it still uses as roots
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+struct Handle {
+    index: u32,
+    // * a slot is reused after its object was collected, an old handle to it no longer matches
+    generation: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct EnvRef(Handle);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LocalsRef(Handle);
+
+enum Object {
+    Env(Env),
+    Locals(Locals),
+}
+
+struct Slot {
+    generation: u32,
+    marked: bool,
+    object: Option<Object>,
+}
+
+// * no collection before the heap holds this many objects, afterwards whenever it doubled
+const MIN_THRESHOLD: usize = 10_000;
+
+/**
+ * * what the heap holds now and did so far
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeapStats {
+    pub live: usize,
+    pub allocated: u64,
+    pub collected: u64,
+    pub collections: u64,
+}
+
+struct Heap {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    // * the environments the host keeps, like the global env of an Evalator, with how often each is kept
+    roots: HashMap<Handle, usize>,
+    // * the values the host keeps through a Kept guard, by the id of the guard
+    kept: HashMap<u64, Value>,
+    next_kept: u64,
+    threshold: usize,
+    stats: HeapStats,
+}
+
+thread_local! {
+    static HEAP: RefCell<Heap> = RefCell::new(Heap::new());
+}
+
+impl Heap {
+    fn new() -> Heap {
+        Heap {
+            slots: Vec::new(),
+            free: Vec::new(),
+            roots: HashMap::new(),
+            kept: HashMap::new(),
+            next_kept: 0,
+            threshold: MIN_THRESHOLD,
+            stats: HeapStats { live: 0, allocated: 0, collected: 0, collections: 0 },
+        }
+    }
+
+    fn alloc(&mut self, object: Object) -> Handle {
+        self.stats.live += 1;
+        self.stats.allocated += 1;
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.object = Some(object);
+                Handle { index, generation: slot.generation }
+            },
+            None => {
+                self.slots.push(Slot { generation: 0, marked: false, object: Some(object) });
+                Handle { index: (self.slots.len() - 1) as u32, generation: 0 }
+            },
+        }
+    }
+
+    /**
+     * * the object behind a handle, an error once it was collected
+     * ! a value the host holds without a Kept guard does not survive a collection, its environments are gone then
+     */
+    fn get_mut(&mut self, h: Handle) -> Result<&mut Object, RuntimeError> {
+        let slot = &mut self.slots[h.index as usize];
+        match &mut slot.object {
+            Some(object) if slot.generation == h.generation => Ok(object),
+            _ => Err(RuntimeError::new("use of a collected heap object, only values reachable from a root survive a collection".to_string())),
+        }
+    }
+
+    // * mark the object, true the first time so its children get visited
+    fn mark(&mut self, h: Handle) -> bool {
+        let slot = &mut self.slots[h.index as usize];
+        match slot.object.is_some() && slot.generation == h.generation && !slot.marked {
+            true => {
+                slot.marked = true;
+                true
+            },
+            false => false,
+        }
+    }
+
+    /**
+     * * free every unmarked object, the objects are returned so they are dropped once the heap is no longer borrowed
+     */
+    fn sweep(&mut self) -> Vec<Object> {
+        let mut garbage = Vec::new();
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            match (slot.marked, slot.object.is_some()) {
+                (true, _) => slot.marked = false,
+                (false, true) => {
+                    garbage.extend(slot.object.take());
+                    slot.generation = slot.generation.wrapping_add(1);
+                    self.free.push(index as u32);
+                },
+                (false, false) => {},
+            }
+        }
+        self.stats.live -= garbage.len();
+        self.stats.collected += garbage.len() as u64;
+        self.stats.collections += 1;
+        self.threshold = MIN_THRESHOLD.max(self.stats.live * 2);
+        garbage
+    }
+}
+
+fn with_heap<R>(f: impl FnOnce(&mut Heap) -> R) -> R {
+    HEAP.with(|heap| f(&mut heap.borrow_mut()))
+}
+
+impl EnvRef {
+    pub(crate) fn alloc(env: Env) -> EnvRef {
+        EnvRef(with_heap(|heap| heap.alloc(Object::Env(env))))
+    }
+
+    /**
+     * * use the environment, f must not touch the heap itself
+     */
+    pub(crate) fn with<R>(self, f: impl FnOnce(&mut Env) -> R) -> Result<R, RuntimeError> {
+        with_heap(|heap| match heap.get_mut(self.0)? {
+            Object::Env(env) => Ok(f(env)),
+            Object::Locals(_) => unreachable!("an env handle always refers to an env"),
+        })
+    }
+}
+
+impl LocalsRef {
+    pub(crate) fn alloc(locals: Locals) -> LocalsRef {
+        LocalsRef(with_heap(|heap| heap.alloc(Object::Locals(locals))))
+    }
+
+    pub(crate) fn with<R>(self, f: impl FnOnce(&mut Locals) -> R) -> Result<R, RuntimeError> {
+        with_heap(|heap| match heap.get_mut(self.0)? {
+            Object::Locals(locals) => Ok(f(locals)),
+            Object::Env(_) => unreachable!("a locals handle always refers to locals"),
+        })
+    }
+}
+
+/**
+ * * keep env and everything reachable from it alive until remove_root
+ */
+pub(crate) fn add_root(env: EnvRef) {
+    with_heap(|heap| *heap.roots.entry(env.0).or_insert(0) += 1);
+}
+
+pub(crate) fn remove_root(env: EnvRef) {
+    with_heap(|heap| {
+        if let Some(count) = heap.roots.get_mut(&env.0) {
+            *count -= 1;
+            if *count == 0 {
+                heap.roots.remove(&env.0);
+            }
+        }
+    });
+}
+
+/**
+ * * a value of an evaluation the host holds on to, with everything reachable from it kept alive
+ * ! the value is a root of every collection until the guard is dropped
+ */
+pub struct Kept {
+    id: u64,
+    value: Value,
+}
+
+impl Kept {
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+}
+
+impl Drop for Kept {
+    fn drop(&mut self) {
+        // * the value is dropped once the heap is no longer borrowed
+        let value = with_heap(|heap| heap.kept.remove(&self.id));
+        mem::drop(value);
+    }
+}
+
+pub(crate) fn keep(value: Value) -> Kept {
+    let id = with_heap(|heap| {
+        let id = heap.next_kept;
+        heap.next_kept += 1;
+        heap.kept.insert(id, value.clone());
+        id
+    });
+    Kept { id, value }
+}
+
+pub fn stats() -> HeapStats {
+    with_heap(|heap| heap.stats.clone())
+}
+
+pub(crate) fn should_collect() -> bool {
+    with_heap(|heap| heap.stats.live >= heap.threshold)
+}
+
+/**
+ * * the roots of a collection and what is reachable from them
+ * * values and objects are visited from work lists, a long list does not recurse deeply
+ */
+pub struct Marker {
+    objects: Vec<Handle>,
+    values: Vec<Value>,
+    // * the shared data already visited, pairs and vectors may form cycles
+    seen: HashSet<usize>,
+}
+
+impl Marker {
+    pub(crate) fn value(&mut self, v: &Value) {
+        self.values.push(v.clone());
+    }
+
+    pub(crate) fn values<'a>(&mut self, vs: impl IntoIterator<Item = &'a Value>) {
+        self.values.extend(vs.into_iter().cloned());
+    }
+
+    pub(crate) fn env(&mut self, env: EnvRef) {
+        self.objects.push(env.0);
+    }
+
+    pub(crate) fn locals(&mut self, locals: Option<LocalsRef>) {
+        self.objects.extend(locals.map(|l| l.0));
+    }
+
+    // * true the first time the data behind rc is visited
+    pub(crate) fn first_visit<T: ?Sized>(&mut self, rc: &Rc<T>) -> bool {
+        self.seen.insert(Rc::as_ptr(rc) as *const () as usize)
+    }
+
+    pub(crate) fn template(&mut self, template: &Rc<Template>) {
+        if !self.first_visit(template) {
+            return;
+        }
+        for op in &template.code {
+            match op {
+                Op::Const(v) => self.value(v),
+                Op::Closure(t) => self.template(t),
+                _ => {},
+            }
+        }
+    }
+
+    pub(crate) fn code(&mut self, code: &Rc<Code>) {
+        if self.first_visit(code) {
+            code.trace(self);
+        }
+    }
+
+    pub(crate) fn body(&mut self, body: &Body) {
+        if self.first_visit(body) {
+            for code in body.iter() {
+                self.code(code);
+            }
+        }
+    }
+
+    fn trace_value(&mut self, v: &Value) {
+        match v {
+            Value::Pair(p) if self.first_visit(p) => {
+                self.value(&p.car());
+                self.value(&p.cdr());
+            },
+            Value::Vector(items) if self.first_visit(items) => self.values(items.borrow().iter()),
+            Value::Error(e) => self.value(e.irritants()),
+            Value::Procedure(Function::Closure(c)) if self.first_visit(c) => {
+                self.body(&c.lambda.body);
+                self.locals(c.locals);
+                self.env(c.globals);
+            },
+            Value::Procedure(Function::Compiled(c)) if self.first_visit(c) => {
+                self.template(&c.template);
+                self.locals(c.locals);
+                self.env(c.globals);
+            },
+            Value::Procedure(Function::Continuation(k)) if self.first_visit(k) => k.trace(self),
+            _ => {},
+        }
+    }
+
+    fn trace_object(&mut self, object: &Object) {
+        match object {
+            Object::Env(env) => env.trace(self),
+            Object::Locals(locals) => locals.trace(self),
+        }
+    }
+}
+
+/**
+ * * mark everything reachable from the host roots and from what trace marks, then free the rest
+ * * returns how many objects were freed
+ */
+pub(crate) fn collect(trace: impl FnOnce(&mut Marker)) -> usize {
+    let mut marker = Marker { objects: Vec::new(), values: Vec::new(), seen: HashSet::new() };
+    trace(&mut marker);
+
+    let garbage = with_heap(|heap| {
+        marker.objects.extend(heap.roots.keys().copied());
+        marker.values.extend(heap.kept.values().cloned());
+        loop {
+            if let Some(v) = marker.values.pop() {
+                marker.trace_value(&v);
+            } else if let Some(h) = marker.objects.pop() {
+                if heap.mark(h) {
+                    let object = heap.slots[h.index as usize].object.as_ref().expect("a marked object is alive");
+                    marker.trace_object(object);
+                }
+            } else {
+                break;
+            }
+        }
+        heap.sweep()
+    });
+    let freed = garbage.len();
+    mem::drop(garbage);
+    freed
+}
+
+/**
+ * * heap natives, registered into the root env by Env::new_root
+ */
+pub fn define_natives(env: &mut Env) {
+    env.define("heap-statistics", &Value::Procedure(Function::Native(native_heap_statistics))).unwrap();
+    let tail_natives: &[(&str, TailOperation)] = &[
+        ("gc", native_gc),
+    ];
+    for (name, op) in tail_natives {
+        env.define(name, &Value::Procedure(Function::TailNative(*op))).unwrap();
+    }
+}
+
+/**
+ * * (gc) collect now, returns how many objects were freed
+ * ! the continuation of the call is all the machine still needs, so the env of the call site is a root too
+ */
+fn native_gc(args: &[Value], env: EnvRef, m: &mut Machine) -> Result<Tail, RuntimeError> {
+    check_arity("gc", args, 0, 0)?;
+    let freed = m.collect(|marker| marker.env(env));
+    Ok(Tail::Return(Value::from(freed as i64)))
+}
+
+/**
+ * * (heap-statistics) an association list of the live objects and the totals so far
+ */
+fn native_heap_statistics(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("heap-statistics", args, 0, 0)?;
+    let stats = stats();
+    let entries = [
+        ("live", stats.live as i64),
+        ("allocated", stats.allocated as i64),
+        ("collected", stats.collected as i64),
+        ("collections", stats.collections as i64),
+    ];
+    Ok(Value::list(entries.iter().map(|(name, n)| Value::cons(Value::Symbol(Symbol::intern(name)), Value::from(*n))).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::interpreter::eval::{Engine, Evalator};
+
+    fn eval_on(evalator: &mut Evalator, input: &str) -> String {
+        format!("{:?}", evalator.eval_source("<input>", input).unwrap())
+    }
+
+    // * what stays live once everything unreachable is freed
+    fn live_after_collect() -> usize {
+        collect(|_| {});
+        stats().live
+    }
+
+    #[test]
+    fn gc_reclaims_recursive_definitions() {
+        for engine in [Engine::Tree, Engine::Vm] {
+            let mut evalator = Evalator::new();
+            evalator.set_engine(engine);
+            eval_on(&mut evalator, "(gc)");
+            let baseline = live_after_collect();
+            // * each f lives in the env it closes over, a cycle counting references would never free
+            for _ in 0..50 {
+                assert_eq!(eval_on(&mut evalator, "(let () (define (f n) (if (= n 0) 'done (f (- n 1)))) (f 10))"), "done");
+            }
+            assert!(stats().live > baseline, "{:?}", engine);
+            eval_on(&mut evalator, "(gc)");
+            assert_eq!(stats().live, baseline, "{:?}", engine);
+        }
+    }
+
+    #[test]
+    fn gc_keeps_reachable_objects() {
+        for engine in [Engine::Tree, Engine::Vm] {
+            let mut evalator = Evalator::new();
+            evalator.set_engine(engine);
+            eval_on(&mut evalator, "(define v (vector (let ((x 42)) (lambda () x))))");
+            eval_on(&mut evalator, "(define (make-counter) (define n 0) (lambda () (set! n (+ n 1)) n)) (define c (make-counter)) (c)");
+            eval_on(&mut evalator, "(gc)");
+            assert_eq!(eval_on(&mut evalator, "((vector-ref v 0))"), "42");
+            assert_eq!(eval_on(&mut evalator, "(c)"), "2");
+            // * the frames of the calls still running survive a collection in their middle
+            assert_eq!(eval_on(&mut evalator, "(define (f x) (let ((y (* x 2))) (gc) (+ x y))) (f 7)"), "21");
+            assert_eq!(eval_on(&mut evalator, "(+ 1 (call/cc (lambda (k) (gc) (k 2))))"), "3");
+        }
+    }
+
+    #[test]
+    fn gc_reports_a_collected_env() {
+        // * the host keeps a closure whose frame nothing in the evalator reaches, a collection frees it
+        let mut evalator = Evalator::new();
+        let f = evalator.eval_source("<input>", "(let ((x 1)) (lambda () x))").unwrap();
+        // * and an env which is the root of no evalator
+        let env = Env::new_root();
+        eval_on(&mut evalator, "(gc)");
+        let locals = match f {
+            Value::Procedure(Function::Closure(c)) => c.locals,
+            _ => unreachable!("a lambda evaluates to a closure"),
+        };
+        let err = Locals::get(locals, 0, 0).unwrap_err();
+        assert!(err.message().starts_with("use of a collected heap object"), "{}", err.message());
+        assert!(Locals::define(locals, 0, &Value::from(2)).is_err());
+        let err = env.get(Symbol::intern("car")).unwrap_err();
+        assert!(err.message().starts_with("use of a collected heap object"), "{}", err.message());
+        assert!(env.define(Symbol::intern("y"), &Value::from(2)).is_err());
+        assert_eq!((env.lookup("car"), env.bindings().len(), env.names().len()), (None, 0, 0));
+    }
+
+    #[test]
+    fn gc_keeps_a_kept_value() {
+        for engine in [Engine::Tree, Engine::Vm] {
+            let mut evalator = Evalator::new();
+            evalator.set_engine(engine);
+            let f = evalator.eval_source("<input>", "(let ((x 1)) (lambda () x))").unwrap();
+            let kept = evalator.keep(f);
+            // * the frame the closure is made in
+            let alive = |f: &Value| match f {
+                Value::Procedure(Function::Closure(c)) => c.locals.is_some_and(|locals| locals.with(|_| ()).is_ok()),
+                Value::Procedure(Function::Compiled(c)) => c.locals.is_some_and(|locals| locals.with(|_| ()).is_ok()),
+                _ => unreachable!("a lambda evaluates to a closure"),
+            };
+            eval_on(&mut evalator, "(gc)");
+            assert!(alive(kept.value()), "{:?}", engine);
+            let f = kept.value().clone();
+            drop(kept);
+            eval_on(&mut evalator, "(gc)");
+            assert!(!alive(&f), "{:?}", engine);
+        }
+    }
+
+    #[test]
+    fn gc_frees_a_dropped_evalator() {
+        let baseline = live_after_collect();
+        let mut evalator = Evalator::new();
+        eval_on(&mut evalator, "(define (f) f)");
+        assert!(live_after_collect() > baseline);
+        drop(evalator);
+        assert_eq!(live_after_collect(), baseline);
+    }
+
+    #[test]
+    fn gc_statistics() {
+        let mut evalator = Evalator::new();
+        let statistics = eval_on(&mut evalator, "(heap-statistics)");
+        assert!(statistics.starts_with("((live . ") && statistics.contains(") (collections . "), "{}", statistics);
+        eval_on(&mut evalator, "(gc)");
+        let before = stats();
+        eval_on(&mut evalator, "(let ((x 1)) x)");
+        assert_eq!(eval_on(&mut evalator, "(gc)"), "1");
+        let after = stats();
+        assert_eq!((after.allocated, after.collected, after.collections), (before.allocated + 1, before.collected + 1, before.collections + 1));
+        assert!(evalator.eval_source("<input>", "(gc 1)").is_err());
+    }
+
+    #[test]
+    fn gc_collects_automatically() {
+        for engine in [Engine::Tree, Engine::Vm] {
+            let mut evalator = Evalator::new();
+            evalator.set_engine(engine);
+            let before = stats().collections;
+            let input = "(define (loop n) (let ((f (lambda () n))) (if (= n 0) (f) (loop (- n 1))))) (loop 50000)";
+            assert_eq!(eval_on(&mut evalator, input), "0");
+            assert!(stats().collections > before, "{:?}", engine);
+            assert!(stats().live < 2 * MIN_THRESHOLD, "{:?}", engine);
+        }
+    }
+}
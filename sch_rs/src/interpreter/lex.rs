@@ -1,43 +1,98 @@
 use std::{vec::Vec};
-use std::iter::Peekable;
-use std::num::ParseIntError;
 use std::fmt;
+use std::rc::Rc;
+use super::number::Number;
+use super::span::{Source, Span, Spanned};
 
 pub struct SyntaxError {
     msg: String,
+    span: Option<Span>,
+    // * the input ended too early, more input could still make it valid
+    incomplete: bool,
 }
 impl fmt::Debug for SyntaxError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Syntax Error: {}", self.msg)
+        write!(f, "{}", self)
     }
 }
 
 impl fmt::Display for SyntaxError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Syntax Error: {}", self.msg)
+        match &self.span {
+            Some(span) => write!(f, "{}: Syntax Error: {}\n{}", span, self.msg, span.excerpt()),
+            None => write!(f, "Syntax Error: {}", self.msg),
+        }
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+impl SyntaxError {
+    fn new(msg: String, span: Option<Span>) -> SyntaxError {
+        SyntaxError { msg, span, incomplete: false }
+    }
+
+    pub fn is_incomplete(&self) -> bool {
+        self.incomplete
+    }
+
+    // * locate an error which does not know its position yet
+    fn at(mut self, span: Span) -> SyntaxError {
+        self.span.get_or_insert(span);
+        self
     }
 }
 
 macro_rules! syntax_error {
     ($($arg:tt),*) => (
-        return Err(SyntaxError { msg: format!($($arg),*)})
+        return Err(SyntaxError::new(format!($($arg),*), None))
+    )
+}
+
+macro_rules! incomplete_error {
+    ($($arg:tt),*) => (
+        return Err(SyntaxError { incomplete: true, ..SyntaxError::new(format!($($arg),*), None) })
     )
 }
 
 
-#[derive(Debug, PartialEq, Clone, Eq)]
+#[derive(Debug, PartialEq, Clone)]
 #[allow(missing_docs)]
 pub enum Token {
     Boolean(bool),
-    Integer(usize),
+    Number(Number),
+    String(String),
+    Char(char),
     Identifier(String),
     OpenParen,
+    VectorParen,
     CloseParen,
+    Dot,
+    Quote,
+    Quasiquote,
+    Unquote,
+    UnquoteSplicing,
 }
 
-impl From<usize> for Token {
-    fn from(i: usize) -> Token {
-        Token::Integer(i)
+/**
+ * * named character literals, shared by the lexer and the `write` formatting of chars
+ */
+pub const CHAR_NAMES: &[(&str, char)] = &[
+    ("space", ' '),
+    ("newline", '\n'),
+    ("tab", '\t'),
+    ("return", '\r'),
+    ("null", '\0'),
+    ("nul", '\0'),
+    ("alarm", '\u{7}'),
+    ("backspace", '\u{8}'),
+    ("delete", '\u{7f}'),
+    ("escape", '\u{1b}'),
+];
+
+impl From<Number> for Token {
+    fn from(n: Number) -> Token {
+        Token::Number(n)
     }
 }
 
@@ -67,110 +122,282 @@ impl From<&str> for Token {
 pub mod lexer {
     use super::*;
 
-    pub fn lex(input: &String) -> Result<Vec<Token>, SyntaxError> {
+    /**
+     * * the characters of the input together with the line and column of the next one
+     */
+    #[derive(Clone)]
+    struct Cursor<'a> {
+        chars: std::str::Chars<'a>,
+        line: usize,
+        column: usize,
+    }
 
-        let mut res = Vec::new();
+    impl<'a> Iterator for Cursor<'a> {
+        type Item = char;
 
-        let mut it = input.chars().peekable();
-
-
-        println!("input: {:?}", it.clone().collect::<String>());
-        while let Some(&c) = it.peek() {
-            // println!("current char: {}", c);
-            match c {
-                '1'..='9' => {
-                    // println!("current number token: {}", c);
-                    // res.push(Token::from(lexer::get_integer(c, &mut it)))
-                    let number = get_number_string(c, &mut it);
-                    println!("number string: {:?}", number);
-                    match number.parse::<usize>() {
-                        Ok(n) => res.push(Token::from(n)),
-                        _ => syntax_error!("only support integer number but got: {:?}", number)
-                    }
-                },
-                '(' | ')' | '+' | '-' | '=' | '[' | ']' => {
-                    // println!("current symbol token: {}", it.peek().unwrap());
-                    res.push(Token::from(c));
-                    it.next();
-                },
-                'a'..='z' | 'A'..='Z' =>  {
-                    // ! we have to clone the iterator, since the it will consume
-                    // ! the last item in iterator that the closure returns false
-                    // ! we still want to consume the letter going right after identifier
-                    let str_token = it.clone().by_ref().take_while(|&c| {
-                        let is_iden = c.is_alphanumeric();
-                        if is_iden {
-                            it.next();
-                        }
-                        is_iden
-                    }).collect::<String>();
-                    res.push(Token::from(str_token));
-                },
-                '#' => {
-                    it.next();
-                    match it.peek().unwrap() {
-                        &'t' => res.push(Token::Boolean(true)),
-                        &'f' => res.push(Token::Boolean(false)),
-                        _ => syntax_error!("invalid boolean expression"),
-                    }
-                    it.next();
-                }
-                _ => {
-                    // println!("token to skip: {}", it.peek().unwrap());
-                    it.next();
-                }
+        fn next(&mut self) -> Option<char> {
+            let c = self.chars.next()?;
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+            Some(c)
+        }
+    }
+
+    impl<'a> Cursor<'a> {
+        fn peek(&self) -> Option<char> {
+            self.chars.clone().next()
+        }
+
+        fn next_if(&mut self, pred: impl FnOnce(&char) -> bool) -> Option<char> {
+            match self.peek() {
+                Some(c) if pred(&c) => self.next(),
+                _ => None,
             }
         }
 
-        println!("Token vector from lexer: {:?}", res);
+        fn next_if_eq(&mut self, expected: &char) -> Option<char> {
+            self.next_if(|c| c == expected)
+        }
+    }
+
+    /**
+     * * whether the input stops in the middle of a datum: inside a string, an open list or after a quote
+     * * the REPL keeps reading lines while this holds
+     */
+    pub fn is_incomplete(input: &str) -> bool {
+        let tokens = match lex(input) {
+            Ok(tokens) => tokens,
+            Err(e) => return e.is_incomplete(),
+        };
+
+        let mut depth = 0;
+        for token in &tokens {
+            match token.item {
+                Token::OpenParen | Token::VectorParen => depth += 1,
+                Token::CloseParen => depth -= 1,
+                _ => {},
+            }
+        }
+        depth > 0 || matches!(tokens.last().map(|t| &t.item), Some(Token::Quote | Token::Quasiquote | Token::Unquote | Token::UnquoteSplicing))
+    }
+
+    /**
+     * * lex an input without a file, positions are reported in `<input>`
+     */
+    pub fn lex(input: &str) -> Result<Vec<Spanned<Token>>, SyntaxError> {
+        lex_source(&Source::new("<input>", input))
+    }
+
+    pub fn lex_source(source: &Rc<Source>) -> Result<Vec<Spanned<Token>>, SyntaxError> {
+
+        let mut res = Vec::new();
+
+        let mut it = Cursor {
+            chars: source.text().chars(),
+            line: 1,
+            column: 1,
+        };
+
+        while it.peek().is_some() {
+            let (line, column) = (it.line, it.column);
+            let token = read_token(&mut it);
+            let len = match it.line == line {
+                true => it.column - column,
+                false => usize::MAX,
+            };
+            let span = Span::new(source.clone(), line, column, len);
+
+            if let Some(token) = token.map_err(|e| e.at(span.clone()))? {
+                res.push(Spanned { item: token, span });
+            }
+        }
 
         Ok(res)
     }
 
-    fn read_until_splitter<T: Iterator<Item=char>> (d: Vec<char>, iter: &mut Peekable<T>) -> String {
-        let mut res: String = String::new();
+    /**
+     * * read the next token, None for whitespace and comments
+     */
+    fn read_token(it: &mut Cursor) -> Result<Option<Token>, SyntaxError> {
+        let c = match it.peek() {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+        let token = match c {
+            '(' | '[' => {
+                it.next();
+                Token::OpenParen
+            },
+            ')' | ']' => {
+                it.next();
+                Token::CloseParen
+            },
+            ';' => {
+                // * comment until the end of line
+                while it.next_if(|&c| c != '\n').is_some() {}
+                return Ok(None);
+            },
+            c if c.is_whitespace() => {
+                it.next();
+                return Ok(None);
+            },
+            '\'' => {
+                it.next();
+                Token::Quote
+            },
+            '`' => {
+                it.next();
+                Token::Quasiquote
+            },
+            ',' => {
+                it.next();
+                match it.next_if_eq(&'@') {
+                    Some(_) => Token::UnquoteSplicing,
+                    None => Token::Unquote,
+                }
+            },
+            '"' => {
+                it.next();
+                Token::String(read_string(it)?)
+            },
+            '#' if it.clone().nth(1) == Some('(') => {
+                it.next();
+                it.next();
+                Token::VectorParen
+            },
+            '#' if it.clone().nth(1) == Some('\\') => {
+                it.next();
+                it.next();
+                Token::Char(read_char(it)?)
+            },
+            _ => atom_token(read_atom(it))?,
+        };
+        Ok(Some(token))
+    }
+
+    fn is_delimiter(c: char) -> bool {
+        c.is_whitespace() || "()[];\"".contains(c)
+    }
 
+    /**
+     * * read a string literal after the opening quote, handling the escape sequences
+     * * \n \t \r \a \b \0 \\ \" \xHH; and a backslash before a line break
+     */
+    fn read_string(iter: &mut Cursor) -> Result<String, SyntaxError> {
+        let mut res = String::new();
         loop {
-            match iter.peek() {
-                Some(c) => {
-                    let is_splitter = d.contains(c);
-                    match is_splitter {
-                        true => break,
-                        _ => {
-                            res.push(*c);
-                            iter.next();
-                        }
+            match iter.next() {
+                Some('"') => return Ok(res),
+                Some('\\') => {
+                    match iter.next() {
+                        Some('n') => res.push('\n'),
+                        Some('t') => res.push('\t'),
+                        Some('r') => res.push('\r'),
+                        Some('a') => res.push('\u{7}'),
+                        Some('b') => res.push('\u{8}'),
+                        Some('0') => res.push('\0'),
+                        Some('\\') => res.push('\\'),
+                        Some('"') => res.push('"'),
+                        Some('x') | Some('X') => {
+                            let mut hex = String::new();
+                            while let Some(c) = iter.next_if(|&c| c != ';' && c != '"') {
+                                hex.push(c);
+                            }
+                            if iter.next() != Some(';') {
+                                syntax_error!("unterminated hex escape in string: \\x{}", hex);
+                            }
+                            match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                                Some(c) => res.push(c),
+                                None => syntax_error!("invalid hex escape in string: \\x{};", hex),
+                            }
+                        },
+                        // * line continuation: skip the line break and the leading whitespace of the next line
+                        Some(c) if c.is_whitespace() => {
+                            while iter.next_if(|&c| c.is_whitespace()).is_some() {}
+                        },
+                        Some(c) => syntax_error!("unknown escape sequence in string: \\{}", c),
+                        None => incomplete_error!("unterminated string literal: {:?}", res),
                     }
-                }
-                None => break
+                },
+                Some(c) => res.push(c),
+                None => incomplete_error!("unterminated string literal: {:?}", res),
             }
         }
-
-        res
     }
 
+    /**
+     * * read a character literal after `#\`, either a single char, a name like `space` or `xHH`
+     */
+    fn read_char(iter: &mut Cursor) -> Result<char, SyntaxError> {
+        let first = match iter.next() {
+            Some(c) => c,
+            None => syntax_error!("unexpected end of input in character literal"),
+        };
+        let rest = read_atom(iter);
+        if rest.is_empty() {
+            return Ok(first);
+        }
 
-    fn get_number_string<T: Iterator<Item=char>> (c: char, iter: &mut Peekable<T>) -> String {
+        let name = format!("{}{}", first, rest);
+        if let Some((_, c)) = CHAR_NAMES.iter().find(|(n, _)| *n == name) {
+            return Ok(*c);
+        }
+        if let Some(hex) = name.strip_prefix('x') {
+            if let Some(c) = u32::from_str_radix(hex, 16).ok().and_then(char::from_u32) {
+                return Ok(c);
+            }
+        }
+        syntax_error!("unknown character name: #\\{}", name)
+    }
 
-        println!("getting the number ...");
+    /**
+     * * read the characters of an atom (identifier, number or # literal) up to the next delimiter
+     */
+    fn read_atom(iter: &mut Cursor) -> String {
         let mut res: String = String::new();
-        loop {
-            match iter.peek() {
-                Some(c) => {
-                    let is_digit = c.is_digit(10) || c == &'.';
-                    if is_digit {
-                        res.push(*c);
-                        iter.next();
-                        continue;
-                    } 
-                    break;
-                }
-                _ => break
-            }
+        while let Some(c) = iter.next_if(|&c| !is_delimiter(c)) {
+            res.push(c);
         }
 
         res
     }
+
+    /**
+     * * an atom is a boolean, a number or otherwise an identifier
+     * ! an atom starting like a number must be a valid number, so `1/0` or `12abc` are rejected
+     */
+    fn atom_token(atom: String) -> Result<Token, SyntaxError> {
+        match atom.as_str() {
+            "#t" | "#true" => return Ok(Token::Boolean(true)),
+            "#f" | "#false" => return Ok(Token::Boolean(false)),
+            "." => return Ok(Token::Dot),
+            _ => {},
+        }
+
+        if let Some(n) = Number::parse(&atom) {
+            return Ok(Token::Number(n));
+        }
+
+        let mut chars = atom.chars();
+        let looks_numeric = match (chars.next(), chars.next()) {
+            (Some(c), _) if c.is_ascii_digit() => true,
+            (Some('+' | '-' | '.'), Some(c)) => c.is_ascii_digit(),
+            _ => false,
+        };
+
+        if atom.starts_with('#') {
+            syntax_error!("invalid # syntax: {}", atom);
+        }
+        if looks_numeric {
+            syntax_error!("invalid number: {}", atom);
+        }
+
+        Ok(Token::Identifier(atom))
+    }
 }
 
 
@@ -184,7 +411,7 @@ mod tests {
     #[test]
     fn lex_simple_number() {
         let test_input = "12345".to_string();
-        assert_eq!(lexer::lex(&test_input).unwrap(), vec![Token::Integer(12345)]);
+        assert_eq!(lexer::lex(&test_input).unwrap(), vec![Token::Number(Number::Integer(12345))]);
     }
 
     #[test]
@@ -192,6 +419,119 @@ mod tests {
         let test_input = "hello".to_string();
         assert_eq!(lexer::lex(&test_input).unwrap(), vec![Token::Identifier("hello".to_string())]);
     }
-    
+
+    #[test]
+    fn lex_numbers() {
+        let test_input = "(- -12 0 2.5 1/3 1e10 #x1F #b101)".to_string();
+        assert_eq!(lexer::lex(&test_input).unwrap(), vec![
+            Token::OpenParen,
+            Token::from("-"),
+            Token::from(Number::Integer(-12)),
+            Token::from(Number::Integer(0)),
+            Token::from(Number::Real(2.5)),
+            Token::from(Number::rational(1, 3).unwrap()),
+            Token::from(Number::Real(1e10)),
+            Token::from(Number::Integer(31)),
+            Token::from(Number::Integer(5)),
+            Token::CloseParen,
+        ]);
+    }
+
+    #[test]
+    fn lex_peculiar_identifiers() {
+        let test_input = "(zero? ... -> +x) ; comment".to_string();
+        assert_eq!(lexer::lex(&test_input).unwrap(), vec![
+            Token::OpenParen, Token::from("zero?"), Token::from("..."), Token::from("->"), Token::from("+x"), Token::CloseParen,
+        ]);
+    }
+
+    #[test]
+    fn lex_vector() {
+        assert_eq!(lexer::lex("#(1 #t)").unwrap(), vec![
+            Token::VectorParen, Token::from(Number::Integer(1)), Token::Boolean(true), Token::CloseParen,
+        ]);
+    }
+
+    #[test]
+    fn lex_dot() {
+        assert_eq!(lexer::lex("(a . b)").unwrap(), vec![
+            Token::OpenParen, Token::from("a"), Token::Dot, Token::from("b"), Token::CloseParen,
+        ]);
+        assert_eq!(lexer::lex(".5").unwrap(), vec![Token::from(Number::Real(0.5))]);
+    }
+
+    #[test]
+    fn lex_spans() {
+        let source = Source::new("test.sch", "(a\n  \"b c\" #\\x)");
+        let tokens = lexer::lex_source(&source).unwrap();
+        let spans: Vec<String> = tokens.iter().map(|t| format!("{}", t.span)).collect();
+        assert_eq!(spans, vec!["test.sch:1:1", "test.sch:1:2", "test.sch:2:3", "test.sch:2:9", "test.sch:2:12"]);
+        let err = lexer::lex_source(&Source::new("test.sch", "(a\n   12abc)")).unwrap_err();
+        assert_eq!(format!("{}", err), "test.sch:2:4: Syntax Error: invalid number: 12abc\n  |\n2 |    12abc)\n  |    ^^^^^");
+    }
+
+    #[test]
+    fn lex_incomplete() {
+        assert!(lexer::is_incomplete("(define (f x)\n  (+ x"));
+        assert!(lexer::is_incomplete("#(1 2"));
+        assert!(lexer::is_incomplete("(display \"a ) b"));
+        assert!(lexer::is_incomplete("'"));
+        assert!(!lexer::is_incomplete("(f x) (g y)"));
+        assert!(!lexer::is_incomplete("(f #\\( \")\") ; ("));
+        assert!(!lexer::is_incomplete("(f))"));
+        assert!(!lexer::is_incomplete("(12abc"));
+    }
+
+    #[test]
+    fn lex_strings() {
+        let test_input = r#"("hello" "a\tb\n" "\"q\"\\" "\x41;BC" "one \
+            line")"#;
+        assert_eq!(lexer::lex(test_input).unwrap(), vec![
+            Token::OpenParen,
+            Token::String("hello".to_string()),
+            Token::String("a\tb\n".to_string()),
+            Token::String("\"q\"\\".to_string()),
+            Token::String("ABC".to_string()),
+            Token::String("one line".to_string()),
+            Token::CloseParen,
+        ]);
+        assert!(lexer::lex("\"open").is_err());
+        assert!(lexer::lex(r#""\q""#).is_err());
+    }
+
+    #[test]
+    fn lex_chars() {
+        let test_input = r"(#\a #\space #\newline #\( #\x41 #\λ)";
+        assert_eq!(lexer::lex(test_input).unwrap(), vec![
+            Token::OpenParen,
+            Token::Char('a'),
+            Token::Char(' '),
+            Token::Char('\n'),
+            Token::Char('('),
+            Token::Char('A'),
+            Token::Char('λ'),
+            Token::CloseParen,
+        ]);
+        assert!(lexer::lex(r"#\bogus").is_err());
+    }
+
+    #[test]
+    fn lex_quotes() {
+        let test_input = "'a `(b ,c ,@d)";
+        assert_eq!(lexer::lex(test_input).unwrap(), vec![
+            Token::Quote, Token::from("a"),
+            Token::Quasiquote, Token::OpenParen, Token::from("b"),
+            Token::Unquote, Token::from("c"),
+            Token::UnquoteSplicing, Token::from("d"),
+            Token::CloseParen,
+        ]);
+    }
+
+    #[test]
+    fn lex_invalid_number() {
+        assert!(lexer::lex("12abc").is_err());
+        assert!(lexer::lex("1/0").is_err());
+        assert!(lexer::lex("#q").is_err());
+    }
 
 }
\ No newline at end of file
@@ -0,0 +1,379 @@
+use super::eval::{Env, Evalator, RuntimeError, Value};
+use super::expand::{Binding, Expander};
+use super::lex::lexer;
+use super::number::Number;
+use super::parser::Parser;
+use super::span::Source;
+use std::{collections::HashMap, env, fs, path::{Path, PathBuf}, rc::Rc};
+
+// * extra directories to look for libraries in, separated like PATH
+const LIBRARY_PATH_VAR: &str = "SCH_RS_LIBRARY_PATH";
+const EXTENSIONS: [&str; 3] = ["sld", "sch", "scm"];
+
+/**
+ * * a library once its body has been evaluated: what it exports, under the names importers see
+ */
+pub(crate) struct Library {
+    exports: Vec<(String, Binding)>,
+}
+
+/**
+ * * the libraries an Evalator knows, each is evaluated once and then served from the cache
+ * * (a b c) is looked up as a/b/c.sld, a/b/c.sch or a/b/c.scm in each directory of the search path
+ */
+pub(crate) struct Libraries {
+    loaded: HashMap<String, Rc<Library>>,
+    // * the libraries being defined, innermost last, importing one of them again is a cycle
+    pending: Vec<String>,
+    // * the directories of the library files being loaded, include is relative to the innermost one
+    files: Vec<PathBuf>,
+    search_path: Vec<PathBuf>,
+}
+
+impl Libraries {
+    /**
+     * * the search path is the directories of SCH_RS_LIBRARY_PATH followed by the working directory
+     */
+    pub(crate) fn new() -> Libraries {
+        let mut search_path: Vec<PathBuf> = env::var_os(LIBRARY_PATH_VAR)
+            .map(|paths| env::split_paths(&paths).collect())
+            .unwrap_or_default();
+        search_path.push(PathBuf::from("."));
+
+        Libraries {
+            loaded: HashMap::new(),
+            pending: Vec::new(),
+            files: Vec::new(),
+            search_path,
+        }
+    }
+
+    fn locate(&self, path: &Path) -> Option<PathBuf> {
+        self.search_path.iter()
+            .flat_map(|dir| EXTENSIONS.iter().map(move |ext| PathBuf::from(format!("{}.{}", dir.join(path).display(), ext))))
+            .find(|file| file.is_file())
+    }
+}
+
+/**
+ * * the key of a library name like (srfi 1), which is how it is written, and the path of its file
+ */
+fn library_name(name: &Value) -> Result<(String, PathBuf), RuntimeError> {
+    let parts = match name.to_vec() {
+        Some(parts) if !parts.is_empty() => parts,
+        _ => runtime_error!("bad syntax: a library name is a list of identifiers and integers: {:?}", name),
+    };
+    let mut path = PathBuf::new();
+    for part in &parts {
+        match part {
+            Value::Symbol(s) => path.push(s.as_str()),
+            Value::Number(Number::Integer(n)) if *n >= 0 => path.push(n.to_string()),
+            _ => runtime_error!("bad syntax: a library name is a list of identifiers and integers: {:?}", name),
+        }
+    }
+    Ok((format!("{:?}", name), path))
+}
+
+// * the libraries (scheme ...) all stand for the natives, the prelude and the special forms built into the interpreter
+fn is_builtin(name: &Value) -> bool {
+    matches!(name.to_vec().as_deref(), Some([Value::Symbol(s), ..]) if s == "scheme")
+}
+
+fn builtin_library(prelude: Vec<String>) -> Library {
+    let mut exports: Vec<(String, Binding)> = Env::new_root().names().into_iter().chain(prelude)
+        .map(|name| (name.clone(), Binding::Variable(name)))
+        .collect();
+    exports.extend(Expander::base_bindings());
+    Library { exports }
+}
+
+fn identifiers(what: &str, values: &[Value]) -> Result<Vec<String>, RuntimeError> {
+    values.iter().map(|v| match v {
+        Value::Symbol(s) => Ok(s.to_string()),
+        _ => runtime_error!("bad syntax: {} expects identifiers but got: {:?}", what, v),
+    }).collect()
+}
+
+/**
+ * * the import sets of R7RS: a library, or a set restricted with only or except, or renamed with prefix or rename
+ */
+enum ImportSet {
+    Library(Value),
+    Only(Box<ImportSet>, Vec<String>),
+    Except(Box<ImportSet>, Vec<String>),
+    Prefix(Box<ImportSet>, String),
+    Rename(Box<ImportSet>, Vec<(String, String)>),
+}
+
+impl ImportSet {
+    fn parse(set: &Value) -> Result<ImportSet, RuntimeError> {
+        let items = match set.to_vec() {
+            Some(items) => items,
+            None => runtime_error!("bad syntax: import set: {:?}", set),
+        };
+        let res = match items.as_slice() {
+            [Value::Symbol(k), inner, ids @ ..] if k == "only" => ImportSet::Only(Box::new(Self::parse(inner)?), identifiers("only", ids)?),
+            [Value::Symbol(k), inner, ids @ ..] if k == "except" => ImportSet::Except(Box::new(Self::parse(inner)?), identifiers("except", ids)?),
+            [Value::Symbol(k), inner, Value::Symbol(prefix)] if k == "prefix" => ImportSet::Prefix(Box::new(Self::parse(inner)?), prefix.to_string()),
+            [Value::Symbol(k), inner, renames @ ..] if k == "rename" => {
+                let renames = renames.iter().map(|r| match identifiers("rename", &r.to_vec().unwrap_or_default())?.as_slice() {
+                    [from, to] => Ok((from.clone(), to.clone())),
+                    _ => runtime_error!("bad syntax: rename expects pairs of identifiers but got: {:?}", r),
+                }).collect::<Result<Vec<(String, String)>, RuntimeError>>()?;
+                ImportSet::Rename(Box::new(Self::parse(inner)?), renames)
+            },
+            [Value::Symbol(k), ..] if k == "only" || k == "except" || k == "prefix" || k == "rename" => {
+                runtime_error!("bad syntax: import set: {:?}", set)
+            },
+            _ => ImportSet::Library(set.clone()),
+        };
+        Ok(res)
+    }
+
+    /**
+     * * the identifiers the set binds, loading the library it starts from if needed
+     * ! naming an identifier the inner set does not provide is an error
+     */
+    fn resolve(&self, ev: &mut Evalator) -> Result<Vec<(String, Binding)>, RuntimeError> {
+        let check = |bindings: &[(String, Binding)], ids: &mut dyn Iterator<Item = &String>| -> Result<(), RuntimeError> {
+            for id in ids {
+                if !bindings.iter().any(|(name, _)| name == id) {
+                    runtime_error!("import: {} is not exported by {:?}", id, (self.library()));
+                }
+            }
+            Ok(())
+        };
+        match self {
+            ImportSet::Library(name) => Ok(ev.find_library(name)?.exports.clone()),
+            ImportSet::Only(inner, ids) => {
+                let bindings = inner.resolve(ev)?;
+                check(&bindings, &mut ids.iter())?;
+                Ok(bindings.into_iter().filter(|(name, _)| ids.contains(name)).collect())
+            },
+            ImportSet::Except(inner, ids) => {
+                let bindings = inner.resolve(ev)?;
+                check(&bindings, &mut ids.iter())?;
+                Ok(bindings.into_iter().filter(|(name, _)| !ids.contains(name)).collect())
+            },
+            ImportSet::Prefix(inner, prefix) => {
+                Ok(inner.resolve(ev)?.into_iter().map(|(name, b)| (format!("{}{}", prefix, name), b)).collect())
+            },
+            ImportSet::Rename(inner, renames) => {
+                let bindings = inner.resolve(ev)?;
+                check(&bindings, &mut renames.iter().map(|(from, _)| from))?;
+                Ok(bindings.into_iter().map(|(name, b)| match renames.iter().find(|(from, _)| *from == name) {
+                    Some((_, to)) => (to.clone(), b),
+                    None => (name, b),
+                }).collect())
+            },
+        }
+    }
+
+    // * the name of the library the set starts from
+    fn library(&self) -> &Value {
+        match self {
+            ImportSet::Library(name) => name,
+            ImportSet::Only(inner, _) | ImportSet::Except(inner, _) | ImportSet::Prefix(inner, _) | ImportSet::Rename(inner, _) => inner.library(),
+        }
+    }
+}
+
+impl Evalator {
+    /**
+     * * look for library files in dir before the directories already on the search path
+     */
+    pub fn add_library_path<P: Into<PathBuf>>(&mut self, dir: P) {
+        self.libraries.search_path.insert(0, dir.into());
+    }
+
+    /**
+     * * (import set ...) bind the identifiers of every import set at the current toplevel
+     */
+    pub(super) fn import(&mut self, form: &Value) -> Result<(), RuntimeError> {
+        let items = match form.to_vec() {
+            Some(items) => items,
+            None => runtime_error!("bad syntax: import: {:?}", form),
+        };
+        for set in &items[1..] {
+            for (id, binding) in ImportSet::parse(set)?.resolve(self)? {
+                self.expander.borrow_mut().import(&id, binding);
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * * (define-library name decl ...) evaluate the body of a library and remember its exports
+     * * the declarations are (export spec ...), (import set ...), (begin form ...) and (include file ...)
+     * ! the body has a toplevel of its own, its definitions are only seen by importers
+     */
+    pub(super) fn define_library(&mut self, form: &Value) -> Result<(), RuntimeError> {
+        let items = match form.to_vec() {
+            Some(items) if items.len() >= 2 => items,
+            _ => runtime_error!("bad syntax: define-library expects a name: {:?}", form),
+        };
+        let (key, _) = library_name(&items[1])?;
+
+        self.libraries.pending.push(key.clone());
+        let saved = self.expander.borrow_mut().enter_library(key.clone());
+        let exports = self.library_declarations(&items[2..]);
+        self.expander.borrow_mut().leave_library(saved);
+        self.libraries.pending.pop();
+
+        self.libraries.loaded.insert(key, Rc::new(Library { exports: exports? }));
+        Ok(())
+    }
+
+    fn library_declarations(&mut self, decls: &[Value]) -> Result<Vec<(String, Binding)>, RuntimeError> {
+        let mut specs = Vec::new();
+        for decl in decls {
+            let items = decl.to_vec().unwrap_or_default();
+            match items.first() {
+                Some(Value::Symbol(k)) if k == "export" => specs.extend(items[1..].iter().cloned()),
+                Some(Value::Symbol(k)) if k == "import" => self.import(decl).map_err(|e| e.at(decl.span()))?,
+                Some(Value::Symbol(k)) if k == "begin" => {
+                    for form in &items[1..] {
+                        self.eval_toplevel(form)?;
+                    }
+                },
+                Some(Value::Symbol(k)) if k == "include" => {
+                    for file in &items[1..] {
+                        match file {
+                            Value::String(file) => self.include(file)?,
+                            _ => runtime_error!("bad syntax: include expects file names but got: {:?}", file),
+                        }
+                    }
+                },
+                _ => runtime_error!("bad syntax: unknown library declaration: {:?}", decl),
+            }
+        }
+
+        // * the exports are looked up once the whole body is defined, so they may come first
+        specs.iter().map(|spec| match spec {
+            Value::Symbol(id) => Ok((id.to_string(), self.expander.borrow().global_binding(id))),
+            _ => match identifiers("export", &spec.to_vec().unwrap_or_default())?.as_slice() {
+                [k, from, to] if k == "rename" => Ok((to.clone(), self.expander.borrow().global_binding(from))),
+                _ => runtime_error!("bad syntax: export expects identifiers or (rename from to) but got: {:?}", spec),
+            },
+        }).collect()
+    }
+
+    /**
+     * * the library called name, from the cache or else loaded from the search path
+     */
+    fn find_library(&mut self, name: &Value) -> Result<Rc<Library>, RuntimeError> {
+        let (key, path) = library_name(name)?;
+        if let Some(lib) = self.libraries.loaded.get(&key) {
+            return Ok(lib.clone());
+        }
+        if let Some(i) = self.libraries.pending.iter().position(|k| *k == key) {
+            runtime_error!("import cycle: {} -> {}", (self.libraries.pending[i..].join(" -> ")), key);
+        }
+        if is_builtin(name) {
+            let lib = Rc::new(builtin_library(self.root.defaults()));
+            self.libraries.loaded.insert(key, lib.clone());
+            return Ok(lib);
+        }
+
+        let file = match self.libraries.locate(&path) {
+            Some(file) => file,
+            None => runtime_error!("library {} not found, looked for {} in: {:?}", key, (path.display()), (self.libraries.search_path)),
+        };
+        self.load_file(&file)?;
+        match self.libraries.loaded.get(&key) {
+            Some(lib) => Ok(lib.clone()),
+            None => runtime_error!("{} does not define the library {}", (file.display()), key),
+        }
+    }
+
+    /**
+     * * (include "file") evaluate the forms of a file as part of the library body
+     */
+    fn include(&mut self, file: &str) -> Result<(), RuntimeError> {
+        let path = match self.libraries.files.last() {
+            Some(dir) => dir.join(file),
+            None => PathBuf::from(file),
+        };
+        self.load_file(&path)
+    }
+
+    // * evaluate every form of a library file at the current toplevel
+    fn load_file(&mut self, file: &Path) -> Result<(), RuntimeError> {
+        let text = match fs::read_to_string(file) {
+            Ok(text) => text,
+            Err(e) => runtime_error!("cannot read {}: {}", (file.display()), e),
+        };
+        let tokens = lexer::lex_source(&Source::new(&file.display().to_string(), &text)).map_err(|e| RuntimeError::new(e.to_string()))?;
+        let nodes = Parser::parse(&tokens).map_err(|e| RuntimeError::new(e.to_string()))?;
+
+        self.libraries.files.push(file.parent().map(Path::to_path_buf).unwrap_or_default());
+        let res = self.eval(&nodes).map(|_| ());
+        self.libraries.files.pop();
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::interpreter::tests::{eval_with, eval_write};
+
+    // * a directory of library files for one test, removed again by the test
+    fn library_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("sch_rs_{}_{}", name, std::process::id()));
+        for (file, text) in files {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+        dir
+    }
+
+    const SHAPES: &str = "(define-library (shapes)
+        (export area (rename perimeter perim) twice)
+        (import (scheme base))
+        (begin
+          (define (pair-sum a b) (+ a b))
+          (define-syntax twice (syntax-rules () ((_ e) (pair-sum e e))))
+          (define (area w h) (* w h))
+          (define (perimeter w h) (* 2 (pair-sum w h)))))";
+
+    #[test]
+    fn library_import_sets() {
+        assert_eq!(eval_write(&format!("{} (import (shapes)) (list (area 2 3) (perim 2 3) (twice 4))", SHAPES)), "(6 10 8)");
+        assert_eq!(eval_write(&format!("{} (import (prefix (only (shapes) area) s:)) (s:area 2 3)", SHAPES)), "6");
+        assert_eq!(eval_write(&format!("{} (import (rename (except (shapes) twice) (area surface))) (surface 2 3)", SHAPES)), "6");
+        assert_eq!(eval_write("(import (prefix (scheme base) b:)) (b:let ((x 1)) (b:+ x 1))"), "2");
+        // * the private definitions of a library and the names left out of an import set stay unbound
+        assert!(eval_with(&mut Evalator::new(), &format!("{} (import (shapes)) (pair-sum 1 2)", SHAPES)).is_err());
+        assert!(eval_with(&mut Evalator::new(), &format!("{} (import (only (shapes) area)) (perim 1 2)", SHAPES)).is_err());
+        let err = eval_with(&mut Evalator::new(), &format!("{} (import (only (shapes) volume))", SHAPES)).unwrap_err();
+        assert_eq!(err.message(), "import: volume is not exported by (shapes)");
+        assert!(eval_with(&mut Evalator::new(), "(import (prefix (scheme base)))").is_err());
+        assert!(eval_with(&mut Evalator::new(), "(define (f) (import (scheme base)))").is_err());
+    }
+
+    #[test]
+    fn library_files() {
+        let dir = library_dir("libs", &[
+            ("counter.sld", "(define-library (counter) (export cell) (begin (define cell (vector 0))))"),
+            ("uses/a.sld", "(define-library (uses a) (import (counter)) (begin (vector-set! cell 0 (+ (vector-ref cell 0) 1))))"),
+            ("uses/b.sch", "(define-library (uses b) (import (counter)) (include \"b-body.scm\"))"),
+            ("uses/b-body.scm", "(vector-set! cell 0 (+ (vector-ref cell 0) 10))"),
+            ("cycle/one.sld", "(define-library (cycle one) (export x) (import (cycle two)) (begin (define x 1)))"),
+            ("cycle/two.sld", "(define-library (cycle two) (export y) (import (cycle one)) (begin (define y 1)))"),
+            ("wrong.sld", "(define-library (right) (export))"),
+        ]);
+        let mut ev = Evalator::new();
+        ev.add_library_path(&dir);
+
+        // * counter is imported three times but evaluated once, so both increments land in the same cell
+        assert_eq!(format!("{:?}", eval_with(&mut ev, "(import (uses a) (uses b) (counter)) cell").unwrap()), "#(11)");
+        let err = eval_with(&mut ev, "(import (cycle one))").unwrap_err();
+        assert_eq!(err.message(), "import cycle: (cycle one) -> (cycle two) -> (cycle one)");
+        assert!(eval_with(&mut ev, "(import (missing))").unwrap_err().message().starts_with("library (missing) not found"));
+        assert!(eval_with(&mut ev, "(import (wrong))").unwrap_err().message().ends_with("does not define the library (wrong)"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,372 @@
+use super::eval::{Env, Function, RuntimeError, Value, ValueOperation};
+use super::span::Span;
+use super::strings::{check_arity, index_arg};
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
+
+/**
+ * * a cons cell, lists are chains of pairs ending in Value::Nil
+ * * a pair read from source remembers where, so errors in the form can point at it
+ * ! pairs are shared: every list built on top of a tail points at the same cells
+ */
+pub struct Pair {
+    car: RefCell<Value>,
+    cdr: RefCell<Value>,
+    span: Option<Rc<Span>>,
+}
+
+/**
+ * * structural equality, the pairs and vectors still to compare are kept on a work list
+ * * atoms are compared with eqv?, except for strings which are equal with the same characters
+ * ! data nested deeply through the car or the cdr is compared without recursing
+ * ! a pair of pairs or vectors met again is taken as equal, so circular data is compared in finite time
+ */
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        let mut pending = vec![(self.clone(), other.clone())];
+        let mut compared = HashSet::new();
+        while let Some((a, b)) = pending.pop() {
+            match (&a, &b) {
+                (Value::Pair(x), Value::Pair(y)) => {
+                    if Rc::ptr_eq(x, y) || !compared.insert((Rc::as_ptr(x) as usize, Rc::as_ptr(y) as usize)) {
+                        continue;
+                    }
+                    pending.push((x.cdr(), y.cdr()));
+                    pending.push((x.car(), y.car()));
+                },
+                (Value::Vector(x), Value::Vector(y)) => {
+                    if Rc::ptr_eq(x, y) || !compared.insert((Rc::as_ptr(x) as usize, Rc::as_ptr(y) as usize)) {
+                        continue;
+                    }
+                    let (x, y) = (x.borrow(), y.borrow());
+                    if x.len() != y.len() {
+                        return false;
+                    }
+                    pending.extend(x.iter().cloned().zip(y.iter().cloned()).rev());
+                },
+                (Value::String(x), Value::String(y)) if x == y => {},
+                (a, b) if a.is_eqv(b) => {},
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+// * pairs and vectors own further values, only those can nest deeply
+fn is_compound(v: &Value) -> bool {
+    matches!(v, Value::Pair(_) | Value::Vector(_))
+}
+
+/**
+ * * free the values a pair or a vector owned, the pairs and vectors no one else shares wait on a work list
+ * * each one is emptied before it is dropped, so its own drop has nothing left to free
+ * ! dropping them recursively would overflow the stack on long lists and deeply nested data
+ */
+pub(super) fn release(values: impl IntoIterator<Item = Value>) {
+    let mut pending: Vec<Value> = values.into_iter().filter(is_compound).collect();
+    while let Some(v) = pending.pop() {
+        match v {
+            Value::Pair(p) => {
+                if let Ok(pair) = Rc::try_unwrap(p) {
+                    pending.extend(vec![pair.car.replace(Value::Nil), pair.cdr.replace(Value::Nil)].into_iter().filter(is_compound));
+                }
+            },
+            Value::Vector(items) => {
+                if let Ok(items) = Rc::try_unwrap(items) {
+                    pending.extend(items.take().into_iter().filter(is_compound));
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+impl Drop for Pair {
+    fn drop(&mut self) {
+        release(vec![self.car.replace(Value::Nil), self.cdr.replace(Value::Nil)]);
+    }
+}
+
+impl Pair {
+    pub fn car(&self) -> Value {
+        self.car.borrow().clone()
+    }
+
+    pub fn cdr(&self) -> Value {
+        self.cdr.borrow().clone()
+    }
+
+    pub fn set_car(&self, v: Value) {
+        *self.car.borrow_mut() = v;
+    }
+
+    pub fn set_cdr(&self, v: Value) {
+        *self.cdr.borrow_mut() = v;
+    }
+
+    pub fn span(&self) -> Option<Rc<Span>> {
+        self.span.clone()
+    }
+}
+
+fn same_pair(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Pair(a), Value::Pair(b)) => Rc::ptr_eq(a, b),
+        _ => false,
+    }
+}
+
+impl Value {
+    pub fn cons(car: Value, cdr: Value) -> Value {
+        Value::Pair(Rc::new(Pair {
+            car: RefCell::new(car),
+            cdr: RefCell::new(cdr),
+            span: None,
+        }))
+    }
+
+    /**
+     * * the same list with a new first pair located at span, other values are returned as they are
+     */
+    pub fn with_span(self, span: Option<Rc<Span>>) -> Value {
+        match (&self, span) {
+            (Value::Pair(p), Some(span)) => Value::Pair(Rc::new(Pair {
+                car: RefCell::new(p.car()),
+                cdr: RefCell::new(p.cdr()),
+                span: Some(span),
+            })),
+            _ => self,
+        }
+    }
+
+    /**
+     * * where a form was read, if it is a pair which was read from source
+     */
+    pub fn span(&self) -> Option<Rc<Span>> {
+        match self {
+            Value::Pair(p) => p.span(),
+            _ => None,
+        }
+    }
+
+    pub fn list(items: Vec<Value>) -> Value {
+        Value::list_with_tail(items, Value::Nil)
+    }
+
+    /**
+     * * (items ... . tail), the tail is shared and not copied
+     */
+    pub fn list_with_tail(items: Vec<Value>, tail: Value) -> Value {
+        items.into_iter().rev().fold(tail, |acc, v| Value::cons(v, acc))
+    }
+
+    /**
+     * * the elements of a (possibly improper) list and whatever ends it, () for a proper list
+     * ! for a circular list the walk stops once the cycle is found and the tail is still a pair
+     */
+    pub fn parts(&self) -> (Vec<Value>, Value) {
+        let mut items = Vec::new();
+        let mut slow = self.clone();
+        let mut cur = self.clone();
+        while let Value::Pair(p) = cur {
+            items.push(p.car());
+            cur = p.cdr();
+            // * the slow pointer moves every other step, meeting it again means a cycle
+            if items.len() % 2 == 0 {
+                if let Value::Pair(s) = slow {
+                    slow = s.cdr();
+                }
+                if same_pair(&slow, &cur) {
+                    break;
+                }
+            }
+        }
+        (items, cur)
+    }
+
+    /**
+     * * the elements of a proper list, None for improper and circular lists
+     */
+    pub fn to_vec(&self) -> Option<Vec<Value>> {
+        match self.parts() {
+            (items, Value::Nil) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+/**
+ * * pair and list natives, registered into the root env by Env::new_root
+ */
+pub fn define_natives(env: &mut Env) {
+    let natives: &[(&str, ValueOperation)] = &[
+        ("cons", native_cons),
+        ("car", native_car),
+        ("cdr", native_cdr),
+        ("set-car!", native_set_car),
+        ("set-cdr!", native_set_cdr),
+        ("list", native_list),
+        ("pair?", native_is_pair),
+        ("null?", native_is_null),
+        ("length", native_length),
+        ("append", native_append),
+        ("reverse", native_reverse),
+        ("list-tail", native_list_tail),
+    ];
+
+    for (name, op) in natives {
+        env.define(name, &Value::Procedure(Function::Native(*op))).unwrap();
+    }
+}
+
+fn pair_arg<'a>(name: &str, v: &'a Value) -> Result<&'a Rc<Pair>, RuntimeError> {
+    match v {
+        Value::Pair(p) => Ok(p),
+        _ => runtime_error!("{} expects a pair but got: {:?}", name, v),
+    }
+}
+
+pub(crate) fn list_arg(name: &str, v: &Value) -> Result<Vec<Value>, RuntimeError> {
+    match v.to_vec() {
+        Some(items) => Ok(items),
+        None => runtime_error!("{} expects a proper list but got: {:?}", name, v),
+    }
+}
+
+pub(crate) fn native_cons(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("cons", args, 2, 2)?;
+    Ok(Value::cons(args[0].clone(), args[1].clone()))
+}
+
+fn native_car(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("car", args, 1, 1)?;
+    Ok(pair_arg("car", &args[0])?.car())
+}
+
+fn native_cdr(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("cdr", args, 1, 1)?;
+    Ok(pair_arg("cdr", &args[0])?.cdr())
+}
+
+fn native_set_car(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("set-car!", args, 2, 2)?;
+    pair_arg("set-car!", &args[0])?.set_car(args[1].clone());
+    Ok(Value::Unit)
+}
+
+fn native_set_cdr(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("set-cdr!", args, 2, 2)?;
+    pair_arg("set-cdr!", &args[0])?.set_cdr(args[1].clone());
+    Ok(Value::Unit)
+}
+
+/**
+ * * (list v ...)
+ */
+pub(crate) fn native_list(args: &[Value]) -> Result<Value, RuntimeError> {
+    Ok(Value::list(args.to_vec()))
+}
+
+fn native_is_pair(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("pair?", args, 1, 1)?;
+    Ok(Value::Boolean(matches!(args[0], Value::Pair(_))))
+}
+
+fn native_is_null(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("null?", args, 1, 1)?;
+    Ok(Value::Boolean(args[0] == Value::Nil))
+}
+
+fn native_length(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("length", args, 1, 1)?;
+    Ok(Value::from(list_arg("length", &args[0])?.len() as i64))
+}
+
+/**
+ * * (append list ... obj) copies every list but the last argument, which becomes the shared tail
+ */
+pub(crate) fn native_append(args: &[Value]) -> Result<Value, RuntimeError> {
+    match args.split_last() {
+        Some((last, init)) => {
+            let mut items = Vec::new();
+            for v in init {
+                items.extend(list_arg("append", v)?);
+            }
+            Ok(Value::list_with_tail(items, last.clone()))
+        },
+        None => Ok(Value::Nil),
+    }
+}
+
+fn native_reverse(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("reverse", args, 1, 1)?;
+    let items = list_arg("reverse", &args[0])?;
+    Ok(items.into_iter().fold(Value::Nil, |acc, v| Value::cons(v, acc)))
+}
+
+/**
+ * * (list-tail list k) the sublist after the first k elements, shared with the argument
+ */
+fn native_list_tail(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("list-tail", args, 2, 2)?;
+    let k = index_arg("list-tail", &args[1])?;
+    let mut cur = args[0].clone();
+    for _ in 0..k {
+        cur = match cur {
+            Value::Pair(p) => p.cdr(),
+            _ => runtime_error!("list-tail: index {} is out of range for {:?}", k, (args[0])),
+        };
+    }
+    Ok(cur)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::interpreter::tests::{eval_str, eval_write};
+
+    #[test]
+    fn list_pairs() {
+        assert_eq!(eval_write("(cons 1 2)"), "(1 . 2)");
+        assert_eq!(eval_write("(cons 1 (cons 2 '()))"), "(1 2)");
+        assert_eq!(eval_write("'(1 2 . 3)"), "(1 2 . 3)");
+        assert_eq!(eval_write("'(1 . (2 . (3 . ())))"), "(1 2 3)");
+        assert_eq!(eval_write("(car '(1 . 2))"), "1");
+        assert_eq!(eval_write("(cdr '(1 2))"), "(2)");
+        assert_eq!(eval_write("(list)"), "()");
+        assert_eq!(eval_write("(list (pair? '(1)) (pair? '()) (null? '()) (null? '(1)))"), "(#true #false #true #false)");
+        assert!(eval_str("(car '())").is_err());
+        assert!(eval_str("(cdr 1)").is_err());
+    }
+
+    #[test]
+    fn list_operations() {
+        assert_eq!(eval_write("(length '(1 2 3))"), "3");
+        assert_eq!(eval_write("(append '(1) '(2 3) '() '(4))"), "(1 2 3 4)");
+        assert_eq!(eval_write("(append '(1) 2)"), "(1 . 2)");
+        assert_eq!(eval_write("(append)"), "()");
+        assert_eq!(eval_write("(reverse '(1 (2) 3))"), "(3 (2) 1)");
+        assert_eq!(eval_write("(list-tail '(1 2 3) 1)"), "(2 3)");
+        assert!(eval_str("(length '(1 . 2))").is_err());
+        assert!(eval_str("(append '(1 . 2) '(3))").is_err());
+        assert!(eval_str("(list-tail '(1) 2)").is_err());
+    }
+
+    #[test]
+    fn list_mutation() {
+        let input = "(define tail (list 2 3)) (define xs (cons 1 tail)) (set-car! tail 20) (set-cdr! (cdr tail) '(4)) xs";
+        assert_eq!(eval_write(input), "(1 20 3 4)");
+        assert!(eval_str("(define xs (list 1)) (set-cdr! xs xs) (length xs)").is_err());
+        assert!(eval_str("(set-car! '() 1)").is_err());
+    }
+
+    #[test]
+    fn list_sharing() {
+        let tail = Value::list(vec![Value::from(2), Value::from(3)]);
+        let appended = native_append(&[Value::list(vec![Value::from(1)]), tail.clone()]).unwrap();
+        assert!(same_pair(&native_cdr(&[appended]).unwrap(), &tail));
+        let rest = native_list_tail(&[tail.clone(), Value::from(1)]).unwrap();
+        assert!(same_pair(&rest, &native_cdr(&[tail]).unwrap()));
+    }
+}
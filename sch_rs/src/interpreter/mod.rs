@@ -1,3 +1,55 @@
+macro_rules! runtime_error {
+    ($($arg:tt),*) => (
+        return Err($crate::interpreter::eval::RuntimeError::new(format!($($arg),*)))
+    )
+}
+
+pub mod span;
 pub mod lex;
 pub mod parser;
-pub mod eval;
\ No newline at end of file
+pub mod eval;
+pub mod number;
+pub mod arithmetic;
+pub mod bignum;
+pub mod strings;
+pub mod list;
+pub mod vector;
+pub mod exception;
+pub mod system;
+pub mod expand;
+pub mod library;
+pub mod compile;
+pub mod address;
+pub mod vm;
+pub mod gc;
+pub mod symbol;
+
+/**
+ * * evaluating source text in the tests of every module
+ */
+#[cfg(test)]
+pub(crate) mod tests {
+
+    use super::eval::{Evalator, RuntimeError, Value};
+    use crate::Error;
+
+    /**
+     * * evaluate input with ev, a lex or parse error becomes a runtime error carrying its message
+     */
+    pub(crate) fn eval_with(ev: &mut Evalator, input: &str) -> Result<Value, RuntimeError> {
+        ev.eval_source("<input>", input).map_err(|e| match e {
+            Error::Runtime(e) => e,
+            e => RuntimeError::new(e.to_string()),
+        })
+    }
+
+    // * evaluate input with a new evalator which has the prelude
+    pub(crate) fn eval_str(input: &str) -> Result<Value, RuntimeError> {
+        eval_with(&mut Evalator::new(), input)
+    }
+
+    // * the written representation of the value of input
+    pub(crate) fn eval_write(input: &str) -> String {
+        format!("{:?}", eval_str(input).unwrap())
+    }
+}
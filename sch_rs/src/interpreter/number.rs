@@ -0,0 +1,628 @@
+use std::fmt;
+use std::cmp::Ordering;
+use super::bignum::BigInt;
+
+/**
+ * * the numeric tower: exact integers and rationals, inexact reals
+ * * exact integers are fixnums and transparently promote to bignums on overflow
+ * ! a big integer is only used when the value does not fit an i64,
+ * ! a rational is always normalized and its denominator is greater than 1
+ * ! so an exact number has exactly one representation
+ */
+#[derive(Debug, PartialEq, Clone)]
+pub enum Number {
+    Integer(i64),
+    Big(BigInt),
+    Rational(BigInt, BigInt),
+    Real(f64),
+}
+
+pub type NumberResult = Result<Number, String>;
+
+fn division_by_zero() -> String {
+    "division by zero".to_string()
+}
+
+impl Number {
+
+    /**
+     * * build an exact rational n/d, normalizing the sign and common factors
+     */
+    pub fn rational(n: i64, d: i64) -> NumberResult {
+        Number::from_parts(BigInt::from(n), BigInt::from(d))
+    }
+
+    fn from_parts(n: BigInt, d: BigInt) -> NumberResult {
+        if d.is_zero() {
+            return Err(division_by_zero());
+        }
+        let g = n.gcd(&d);
+        let (mut n, mut d) = (n.divrem(&g).unwrap().0, d.divrem(&g).unwrap().0);
+        if d.is_negative() {
+            n = n.neg();
+            d = d.neg();
+        }
+        match d == BigInt::from(1) {
+            true => Ok(Number::from_big(n)),
+            false => Ok(Number::Rational(n, d)),
+        }
+    }
+
+    /**
+     * * demote a big integer back to a fixnum when it fits
+     */
+    pub fn from_big(b: BigInt) -> Number {
+        match b.to_i64() {
+            Some(i) => Number::Integer(i),
+            None => Number::Big(b),
+        }
+    }
+
+    pub fn is_exact(&self) -> bool {
+        !matches!(self, Number::Real(_))
+    }
+
+    /**
+     * * eqv?: the same exactness and the same value, reals compare by their bits so 0.0 and -0.0 differ
+     */
+    pub fn is_eqv(&self, other: &Number) -> bool {
+        match (self, other) {
+            (Number::Real(a), Number::Real(b)) => a.to_bits() == b.to_bits(),
+            _ => self == other,
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        match self {
+            Number::Integer(_) | Number::Big(_) => true,
+            Number::Rational(_, _) => false,
+            Number::Real(f) => f.is_finite() && f.fract() == 0.0,
+        }
+    }
+
+    pub fn is_positive(&self) -> bool {
+        self.compare(&Number::Integer(0)) == Some(Ordering::Greater)
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.compare(&Number::Integer(0)) == Some(Ordering::Less)
+    }
+
+    /**
+     * * whether an integer is even, false for anything else
+     */
+    pub fn is_even(&self) -> bool {
+        match self {
+            Number::Integer(i) => i % 2 == 0,
+            Number::Big(b) => b.is_even(),
+            Number::Rational(_, _) => false,
+            Number::Real(f) => f % 2.0 == 0.0,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match self {
+            Number::Integer(i) => *i == 0,
+            Number::Big(_) | Number::Rational(_, _) => false,
+            Number::Real(f) => *f == 0.0,
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Number::Integer(i) => *i as f64,
+            Number::Big(b) => b.to_f64(),
+            Number::Rational(n, d) => n.to_f64() / d.to_f64(),
+            Number::Real(f) => *f,
+        }
+    }
+
+    // * numerator and denominator of an exact number
+    fn parts(&self) -> Option<(BigInt, BigInt)> {
+        match self {
+            Number::Integer(i) => Some((BigInt::from(*i), BigInt::from(1))),
+            Number::Big(b) => Some((b.clone(), BigInt::from(1))),
+            Number::Rational(n, d) => Some((n.clone(), d.clone())),
+            Number::Real(_) => None,
+        }
+    }
+
+    pub fn to_inexact(&self) -> Number {
+        Number::Real(self.to_f64())
+    }
+
+    /**
+     * * convert to an exact number, an inexact real is converted without rounding
+     */
+    pub fn to_exact(&self) -> NumberResult {
+        match self {
+            Number::Real(f) => {
+                if !f.is_finite() {
+                    return Err(format!("no exact representation for {}", self));
+                }
+                // * split the float into mantissa * 2^exponent
+                let bits = f.to_bits();
+                let exponent = ((bits >> 52) & 0x7ff) as i32;
+                let fraction = (bits & ((1 << 52) - 1)) as i64;
+                let (mantissa, exponent) = match exponent {
+                    0 => (fraction, -1074),
+                    _ => (fraction | (1 << 52), exponent - 1075),
+                };
+                let mantissa = BigInt::from(if f.is_sign_negative() { -mantissa } else { mantissa });
+                match exponent >= 0 {
+                    true => Ok(Number::from_big(mantissa.shl(exponent as u32))),
+                    false => Number::from_parts(mantissa, BigInt::from(1).shl((-exponent) as u32)),
+                }
+            },
+            _ => Ok(self.clone()),
+        }
+    }
+
+    /**
+     * * combine two numbers, fixnums try the checked operation first,
+     * * other exact operands go through the exact rational operation
+     * * and any inexact operand makes the result inexact
+     */
+    fn combine(&self, other: &Number,
+        fixnum: fn(i64, i64) -> Option<i64>,
+        exact: fn(BigInt, BigInt, BigInt, BigInt) -> NumberResult,
+        inexact: fn(f64, f64) -> f64) -> NumberResult {
+        if let (Number::Integer(a), Number::Integer(b)) = (self, other) {
+            if let Some(i) = fixnum(*a, *b) {
+                return Ok(Number::Integer(i));
+            }
+        }
+        match (self.parts(), other.parts()) {
+            (Some((n1, d1)), Some((n2, d2))) => exact(n1, d1, n2, d2),
+            _ => Ok(Number::Real(inexact(self.to_f64(), other.to_f64()))),
+        }
+    }
+
+    pub fn add(&self, other: &Number) -> NumberResult {
+        self.combine(other, i64::checked_add, |n1, d1, n2, d2| {
+            Number::from_parts(n1.mul(&d2).add(&n2.mul(&d1)), d1.mul(&d2))
+        }, |a, b| a + b)
+    }
+
+    pub fn sub(&self, other: &Number) -> NumberResult {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &Number) -> NumberResult {
+        self.combine(other, i64::checked_mul, |n1, d1, n2, d2| {
+            Number::from_parts(n1.mul(&n2), d1.mul(&d2))
+        }, |a, b| a * b)
+    }
+
+    pub fn div(&self, other: &Number) -> NumberResult {
+        self.combine(other, |a, b| {
+            match a.checked_rem(b) {
+                Some(0) => a.checked_div(b),
+                _ => None,
+            }
+        }, |n1, d1, n2, d2| {
+            Number::from_parts(n1.mul(&d2), d1.mul(&n2))
+        }, |a, b| a / b)
+    }
+
+    pub fn neg(&self) -> Number {
+        match self {
+            Number::Integer(i) => match i.checked_neg() {
+                Some(i) => Number::Integer(i),
+                None => Number::Big(BigInt::from(*i).neg()),
+            },
+            Number::Big(b) => Number::from_big(b.neg()),
+            Number::Rational(n, d) => Number::Rational(n.neg(), d.clone()),
+            Number::Real(f) => Number::Real(-f),
+        }
+    }
+
+    pub fn abs(&self) -> Number {
+        match self.is_negative() {
+            true => self.neg(),
+            false => self.clone(),
+        }
+    }
+
+    /**
+     * * the truncating quotient and the remainder of two integers, the remainder has the sign of the dividend
+     * ! an inexact operand gives inexact results, dividing by zero is an error for both
+     */
+    fn divide_integers(&self, other: &Number) -> Result<(Number, Number), String> {
+        if !self.is_integer() || !other.is_integer() {
+            return Err(format!("integer division expects integers but got: {} and {}", self, other));
+        }
+        if other.is_zero() {
+            return Err(division_by_zero());
+        }
+        if let (Number::Integer(a), Number::Integer(b)) = (self, other) {
+            // * only i64::MIN / -1 overflows, it goes on to the big integers
+            if let (Some(q), Some(r)) = (a.checked_div(*b), a.checked_rem(*b)) {
+                return Ok((Number::Integer(q), Number::Integer(r)));
+            }
+        }
+        match (self.parts(), other.parts()) {
+            (Some((a, _)), Some((b, _))) => {
+                let (q, r) = a.divrem(&b).unwrap();
+                Ok((Number::from_big(q), Number::from_big(r)))
+            },
+            _ => {
+                let (a, b) = (self.to_f64(), other.to_f64());
+                Ok((Number::Real((a / b).trunc()), Number::Real(a % b)))
+            },
+        }
+    }
+
+    pub fn quotient(&self, other: &Number) -> NumberResult {
+        Ok(self.divide_integers(other)?.0)
+    }
+
+    pub fn remainder(&self, other: &Number) -> NumberResult {
+        Ok(self.divide_integers(other)?.1)
+    }
+
+    /**
+     * * the remainder of the floored division, it has the sign of the divisor
+     */
+    pub fn modulo(&self, other: &Number) -> NumberResult {
+        let r = self.divide_integers(other)?.1;
+        match !r.is_zero() && r.is_negative() != other.is_negative() {
+            true => r.add(other),
+            false => Ok(r),
+        }
+    }
+
+    /**
+     * * the greatest common divisor of two integers, never negative
+     */
+    pub fn gcd(&self, other: &Number) -> NumberResult {
+        if !self.is_integer() || !other.is_integer() {
+            return Err(format!("gcd expects integers but got: {} and {}", self, other));
+        }
+        match (self.parts(), other.parts()) {
+            (Some((a, _)), Some((b, _))) => Ok(Number::from_big(a.gcd(&b))),
+            _ => {
+                let (mut a, mut b) = (self.to_f64().abs(), other.to_f64().abs());
+                while b != 0.0 {
+                    (a, b) = (b, a % b);
+                }
+                Ok(Number::Real(a))
+            },
+        }
+    }
+
+    /**
+     * * the least common multiple of two integers, never negative
+     */
+    pub fn lcm(&self, other: &Number) -> NumberResult {
+        let g = self.gcd(other)?;
+        if g.is_zero() {
+            return Ok(g);
+        }
+        self.mul(other)?.abs().div(&g)
+    }
+
+    /**
+     * * self raised to the power other, exact for an exact base and an exact integer exponent
+     * ! anything else goes through floats, a negative base with a fractional exponent is NaN
+     */
+    pub fn expt(&self, other: &Number) -> NumberResult {
+        match (self.parts(), other) {
+            (Some((n, d)), Number::Integer(e)) => {
+                let (n, d) = (n.pow(e.unsigned_abs()), d.pow(e.unsigned_abs()));
+                match *e >= 0 {
+                    true => Number::from_parts(n, d),
+                    false => Number::from_parts(d, n),
+                }
+            },
+            // * only 0, 1 and -1 have an exact power for an exponent past i64
+            (Some(_), Number::Big(_)) => match self {
+                Number::Integer(0) if other.is_negative() => Err(division_by_zero()),
+                Number::Integer(0) | Number::Integer(1) => Ok(self.clone()),
+                Number::Integer(-1) if other.is_even() => Ok(Number::Integer(1)),
+                Number::Integer(-1) => Ok(Number::Integer(-1)),
+                _ => Err(format!("expt: the exact power of {} to {} is too large", self, other)),
+            },
+            _ => Ok(Number::Real(self.to_f64().powf(other.to_f64()))),
+        }
+    }
+
+    /**
+     * * the root s and the rest r of a non-negative exact integer, s * s + r is the number
+     */
+    pub fn exact_integer_sqrt(&self) -> Option<(Number, Number)> {
+        match self {
+            Number::Integer(i) if *i >= 0 => {
+                let s = (*i as u64).isqrt() as i64;
+                Some((Number::Integer(s), Number::Integer(i - s * s)))
+            },
+            Number::Big(b) if !b.is_negative() => {
+                let s = b.isqrt();
+                let r = b.sub(&s.mul(&s));
+                Some((Number::from_big(s), Number::from_big(r)))
+            },
+            _ => None,
+        }
+    }
+
+    /**
+     * * the digits of an exact number in radix, None for an inexact one in a radix other than 10
+     */
+    pub fn to_string_radix(&self, radix: u32) -> Option<String> {
+        match (self, radix) {
+            (_, 10) => Some(self.to_string()),
+            (Number::Integer(i), _) => Some(BigInt::from(*i).to_string_radix(radix)),
+            (Number::Big(b), _) => Some(b.to_string_radix(radix)),
+            (Number::Rational(n, d), _) => Some(format!("{}/{}", n.to_string_radix(radix), d.to_string_radix(radix))),
+            (Number::Real(_), _) => None,
+        }
+    }
+
+    /**
+     * * numeric ordering, exact numbers are compared exactly
+     * ! comparing against NaN has no ordering
+     */
+    pub fn compare(&self, other: &Number) -> Option<Ordering> {
+        if let (Number::Integer(a), Number::Integer(b)) = (self, other) {
+            return Some(a.cmp(b));
+        }
+        match (self.parts(), other.parts()) {
+            (Some((n1, d1)), Some((n2, d2))) => Some(n1.mul(&d2).cmp(&n2.mul(&d1))),
+            _ => self.to_f64().partial_cmp(&other.to_f64()),
+        }
+    }
+
+    /**
+     * * parse a numeric literal with optional #x #b #o #d radix and #e #i exactness prefixes
+     * * returns None when the text is not a number
+     */
+    pub fn parse(text: &str) -> Option<Number> {
+        Number::parse_radix(text, 10)
+    }
+
+    /**
+     * * parse digits in radix, unless the text starts with a radix prefix of its own
+     */
+    pub fn parse_radix(text: &str, mut radix: u32) -> Option<Number> {
+        let mut exactness = None;
+        let mut rest = text;
+
+        while let Some(prefix) = rest.strip_prefix('#') {
+            match prefix.chars().next().map(|c| c.to_ascii_lowercase()) {
+                Some('x') => radix = 16,
+                Some('b') => radix = 2,
+                Some('o') => radix = 8,
+                Some('d') => radix = 10,
+                Some('e') => exactness = Some(true),
+                Some('i') => exactness = Some(false),
+                _ => return None,
+            }
+            rest = &rest[2..];
+        }
+
+        let n = Number::parse_real(rest, radix, exactness == Some(true))?;
+        match exactness {
+            Some(true) => n.to_exact().ok(),
+            Some(false) => Some(n.to_inexact()),
+            None => Some(n),
+        }
+    }
+
+    fn parse_real(text: &str, radix: u32, exact: bool) -> Option<Number> {
+        match text {
+            "+inf.0" => return Some(Number::Real(f64::INFINITY)),
+            "-inf.0" => return Some(Number::Real(f64::NEG_INFINITY)),
+            "+nan.0" | "-nan.0" => return Some(Number::Real(f64::NAN)),
+            _ => {},
+        }
+
+        let (negative, digits) = match text.chars().next() {
+            Some('-') => (true, &text[1..]),
+            Some('+') => (false, &text[1..]),
+            _ => (false, text),
+        };
+        if digits.is_empty() || digits.starts_with(['+', '-']) {
+            return None;
+        }
+        let sign = |n: Number| if negative { Some(n.neg()) } else { Some(n) };
+
+        if let Some((num, den)) = digits.split_once('/') {
+            let num = BigInt::parse_radix(num, radix)?;
+            let den = BigInt::parse_radix(den, radix)?;
+            return sign(Number::from_parts(num, den).ok()?);
+        }
+
+        if let Some(i) = Number::parse_uinteger(digits, radix) {
+            return sign(i);
+        }
+
+        if radix != 10 || !digits.chars().any(|c| c.is_ascii_digit())
+            || !digits.chars().all(|c| c.is_ascii_digit() || "eE.+-".contains(c)) {
+            return None;
+        }
+
+        match exact {
+            true => sign(Number::parse_exact_decimal(digits)?),
+            false => sign(Number::Real(digits.parse::<f64>().ok()?)),
+        }
+    }
+
+    fn parse_uinteger(digits: &str, radix: u32) -> Option<Number> {
+        if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+            return None;
+        }
+        match i64::from_str_radix(digits, radix) {
+            Ok(i) => Some(Number::Integer(i)),
+            Err(_) => Some(Number::from_big(BigInt::parse_radix(digits, radix)?)),
+        }
+    }
+
+    /**
+     * * #e1.25 is read as 5/4 rather than going through a lossy float
+     */
+    fn parse_exact_decimal(digits: &str) -> Option<Number> {
+        let (mantissa, exponent) = match digits.find(['e', 'E']) {
+            Some(i) => (&digits[..i], digits[i + 1..].parse::<i32>().ok()?),
+            None => (digits, 0),
+        };
+        let (int_part, frac_part) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        let all_digits = format!("{}{}", int_part, frac_part);
+        let n = Number::parse_uinteger(&all_digits, 10)?;
+        let scale = exponent - frac_part.len() as i32;
+        let ten = Number::from_big(BigInt::from(10).pow(scale.unsigned_abs() as u64));
+        match scale >= 0 {
+            true => n.mul(&ten).ok(),
+            false => n.div(&ten).ok(),
+        }
+    }
+}
+
+impl From<i64> for Number {
+    fn from(i: i64) -> Number {
+        Number::Integer(i)
+    }
+}
+
+impl From<f64> for Number {
+    fn from(f: f64) -> Number {
+        Number::Real(f)
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Number::Integer(i) => write!(f, "{}", i),
+            Number::Big(b) => write!(f, "{}", b),
+            Number::Rational(n, d) => write!(f, "{}/{}", n, d),
+            Number::Real(r) => {
+                if r.is_nan() {
+                    write!(f, "+nan.0")
+                } else if r.is_infinite() {
+                    write!(f, "{}inf.0", if *r > 0.0 { "+" } else { "-" })
+                } else {
+                    // * debug formatting always keeps a fraction or exponent, so 1.0 stays inexact when read back
+                    write!(f, "{:?}", r)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn parse_literals() {
+        assert_eq!(Number::parse("0"), Some(Number::Integer(0)));
+        assert_eq!(Number::parse("-12"), Some(Number::Integer(-12)));
+        assert_eq!(Number::parse("2.5"), Some(Number::Real(2.5)));
+        assert_eq!(Number::parse("1/3"), Number::rational(1, 3).ok());
+        assert_eq!(Number::parse("-6/4"), Number::rational(-3, 2).ok());
+        assert_eq!(Number::parse("1e10"), Some(Number::Real(1e10)));
+        assert_eq!(Number::parse("#xff"), Some(Number::Integer(255)));
+        assert_eq!(Number::parse("#b-101"), Some(Number::Integer(-5)));
+        assert_eq!(Number::parse("#e1.25"), Number::rational(5, 4).ok());
+        assert_eq!(Number::parse("#i1/2"), Some(Number::Real(0.5)));
+    }
+
+    #[test]
+    fn parse_non_numbers() {
+        assert_eq!(Number::parse("-"), None);
+        assert_eq!(Number::parse("+"), None);
+        assert_eq!(Number::parse("..."), None);
+        assert_eq!(Number::parse("1/0"), None);
+        assert_eq!(Number::parse("inf"), None);
+        assert_eq!(Number::parse("#b102"), None);
+    }
+
+    #[test]
+    fn exact_promotion() {
+        let third = Number::rational(1, 3).unwrap();
+        assert_eq!(third.add(&third).unwrap().add(&third).unwrap(), Number::Integer(1));
+        assert_eq!(Number::Integer(1).div(&Number::Integer(4)).unwrap(), Number::rational(1, 4).unwrap());
+        assert_eq!(Number::Integer(1).add(&Number::Real(0.5)).unwrap(), Number::Real(1.5));
+        assert!(Number::Integer(1).div(&Number::Integer(0)).is_err());
+        assert_eq!(Number::Integer(1).div(&Number::Real(0.0)).unwrap(), Number::Real(f64::INFINITY));
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(Number::Real(1.0).to_string(), "1.0");
+        assert_eq!(Number::rational(-1, 2).unwrap().to_string(), "-1/2");
+        assert_eq!(Number::Real(f64::NEG_INFINITY).to_string(), "-inf.0");
+    }
+
+    #[test]
+    fn fixnum_overflow_promotes() {
+        let n = Number::Integer(99999999999);
+        assert_eq!(n.mul(&n).unwrap().to_string(), "9999999999800000000001");
+        let max = Number::Integer(i64::MAX);
+        let big = max.add(&Number::Integer(1)).unwrap();
+        assert!(matches!(big, Number::Big(_)));
+        assert_eq!(big.sub(&Number::Integer(1)).unwrap(), max);
+        assert_eq!(Number::Integer(i64::MIN).neg().to_string(), "9223372036854775808");
+        assert_eq!(Number::Integer(i64::MIN).div(&Number::Integer(-1)).unwrap().to_string(), "9223372036854775808");
+    }
+
+    #[test]
+    fn integer_division() {
+        let div = |a: i64, b: i64| {
+            let (a, b) = (Number::Integer(a), Number::Integer(b));
+            (a.quotient(&b).unwrap().to_string(), a.remainder(&b).unwrap().to_string(), a.modulo(&b).unwrap().to_string())
+        };
+        assert_eq!(div(13, 4), ("3".to_string(), "1".to_string(), "1".to_string()));
+        assert_eq!(div(-13, 4), ("-3".to_string(), "-1".to_string(), "3".to_string()));
+        assert_eq!(div(13, -4), ("-3".to_string(), "1".to_string(), "-3".to_string()));
+        assert_eq!(div(i64::MIN, -1).0, "9223372036854775808");
+        assert_eq!(Number::Real(-13.0).modulo(&Number::Integer(4)).unwrap(), Number::Real(3.0));
+        assert!(Number::Integer(1).quotient(&Number::Integer(0)).is_err());
+        assert!(Number::Real(1.5).remainder(&Number::Integer(1)).is_err());
+    }
+
+    #[test]
+    fn powers_and_roots() {
+        assert_eq!(Number::Integer(2).expt(&Number::Integer(100)).unwrap().to_string(), "1267650600228229401496703205376");
+        assert_eq!(Number::Integer(2).expt(&Number::Integer(-2)).unwrap(), Number::rational(1, 4).unwrap());
+        assert_eq!(Number::rational(2, 3).unwrap().expt(&Number::Integer(2)).unwrap(), Number::rational(4, 9).unwrap());
+        assert_eq!(Number::Integer(4).expt(&Number::Real(0.5)).unwrap(), Number::Real(2.0));
+        assert!(Number::Integer(0).expt(&Number::Integer(-1)).is_err());
+        let big = Number::parse("100000000000000000001").unwrap();
+        assert_eq!(Number::Integer(0).expt(&big).unwrap(), Number::Integer(0));
+        assert_eq!(Number::Integer(1).expt(&big.neg()).unwrap(), Number::Integer(1));
+        assert_eq!(Number::Integer(-1).expt(&big).unwrap(), Number::Integer(-1));
+        assert_eq!(Number::Integer(-1).expt(&big.add(&Number::Integer(1)).unwrap()).unwrap(), Number::Integer(1));
+        assert!(Number::Integer(0).expt(&big.neg()).is_err());
+        assert!(Number::Integer(2).expt(&big).is_err());
+        assert!(Number::rational(1, 2).unwrap().expt(&big).is_err());
+        assert_eq!(Number::Real(1.0).expt(&big).unwrap(), Number::Real(1.0));
+        assert_eq!(Number::Integer(17).exact_integer_sqrt(), Some((Number::Integer(4), Number::Integer(1))));
+        assert_eq!(Number::Integer(-1).exact_integer_sqrt(), None);
+        assert_eq!(Number::Integer(-12).gcd(&Number::Integer(18)).unwrap(), Number::Integer(6));
+        assert_eq!(Number::Integer(-4).lcm(&Number::Integer(6)).unwrap(), Number::Integer(12));
+        assert_eq!(Number::Integer(0).lcm(&Number::Integer(6)).unwrap(), Number::Integer(0));
+    }
+
+    #[test]
+    fn radix_conversion() {
+        assert_eq!(Number::Integer(-255).to_string_radix(16).as_deref(), Some("-ff"));
+        assert_eq!(Number::rational(1, 2).unwrap().to_string_radix(2).as_deref(), Some("1/10"));
+        assert_eq!(Number::Real(0.5).to_string_radix(2), None);
+        assert_eq!(Number::parse_radix("ff", 16), Some(Number::Integer(255)));
+        assert_eq!(Number::parse_radix("#d10", 16), Some(Number::Integer(10)));
+        assert_eq!(Number::parse_radix("12", 2), None);
+    }
+
+    #[test]
+    fn big_literals_and_comparison() {
+        let big = Number::parse("123456789012345678901234567890").unwrap();
+        assert_eq!(big.to_string(), "123456789012345678901234567890");
+        assert_eq!(big.compare(&Number::Integer(1)), Some(Ordering::Greater));
+        assert_eq!(Number::parse("-123456789012345678901234567890").unwrap().compare(&big), Some(Ordering::Less));
+        let third = Number::parse("1/123456789012345678901234567890").unwrap();
+        assert_eq!(third.mul(&big).unwrap(), Number::Integer(1));
+        assert_eq!(Number::Real(1e20).to_exact().unwrap().to_string(), "100000000000000000000");
+    }
+}
@@ -1,78 +1,199 @@
-use super::lex::{Token};
-use std::slice;
-use crate::util::parse_test_template;
+use super::lex::Token;
+use super::number::Number;
+use super::span::{Span, Spanned};
+use std::{fmt, slice};
 
 
-#[derive(Debug, PartialEq, Clone, Hash, Eq)]
+#[derive(Debug, Clone)]
 pub enum Node {
     Identifier(String),
-    Integer(usize),
+    Number(Number),
+    String(String),
+    Char(char),
     List(Vec<Node>),
+    DottedList(Vec<Node>, Box<Node>),
+    Vector(Vec<Node>),
     Boolean(bool),
+    // * a compound datum and where it was read, the parser wraps every list and vector
+    Located(Box<Node>, Span),
 }
 
+/**
+ * * nodes compare by their datum, where they were read does not matter
+ */
+impl PartialEq for Node {
+    fn eq(&self, other: &Node) -> bool {
+        match (self, other) {
+            (Node::Located(a, _), b) => **a == *b,
+            (a, Node::Located(b, _)) => *a == **b,
+            (Node::Identifier(a), Node::Identifier(b)) => a == b,
+            (Node::Number(a), Node::Number(b)) => a == b,
+            (Node::String(a), Node::String(b)) => a == b,
+            (Node::Char(a), Node::Char(b)) => a == b,
+            (Node::List(a), Node::List(b)) => a == b,
+            (Node::DottedList(a, ta), Node::DottedList(b, tb)) => a == b && ta == tb,
+            (Node::Vector(a), Node::Vector(b)) => a == b,
+            (Node::Boolean(a), Node::Boolean(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/**
+ * * a token stream which is no valid datum, located at the offending token
+ */
+pub struct ParseError {
+    msg: String,
+    span: Span,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: Parse Error: {}\n{}", self.span, self.msg, self.span.excerpt())
+    }
+}
+
+impl fmt::Debug for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+macro_rules! parse_error {
+    ($span:expr, $($arg:tt),*) => (
+        return Err(ParseError { msg: format!($($arg),*), span: $span.clone() })
+    )
+}
+
+/**
+ * * how deeply lists, vectors and quotations may nest
+ * ! the later passes recurse over nested data, the limit keeps them off the end of the rust stack
+ */
+pub(crate) const MAX_DEPTH: usize = 200;
 
 pub struct Parser<'a> {
-    tokens: slice::Iter<'a, Token>
+    tokens: slice::Iter<'a, Spanned<Token>>,
+    depth: usize,
 }
 
 impl<'a> Parser<'a> {
-    pub fn parse(tokens: &Vec<Token>) -> Result<Vec<Node>, String> {
+    pub fn parse(tokens: &[Spanned<Token>]) -> Result<Vec<Node>, ParseError> {
 
         let mut parser = Parser {
             tokens: tokens.iter(),
+            depth: 0,
         };
 
-        parser.parse_nodes(0)
+        parser.parse_nodes()
     }
 
-    fn parse_nodes(&mut self, depth: u32) -> Result<Vec<Node>, String> {
+    fn parse_nodes(&mut self) -> Result<Vec<Node>, ParseError> {
         let mut nodes = Vec::new();
         loop {
-            match self.parse_node(depth) {
-                Ok(Some(node)) => {nodes.push(node);},
-                Ok(None) => {
-                    return Ok(nodes);
+            match self.tokens.next() {
+                Some(token) => match self.parse_node(token)? {
+                    Some(node) => nodes.push(node),
+                    None => parse_error!(token.span, "No matching open paren!"),
                 },
-                Err(e) => {
-                    return Err(e);
-                }
+                None => return Ok(nodes),
             }
         }
     }
-    fn parse_node(&mut self, depth: u32) -> Result<Option<Node>, String> {
 
-        match self.tokens.next() {
-            Some(token) => {
-                match token {
-                    Token::Integer(i) => Ok(Some(Node::Integer(i.clone()))),
-                    Token::OpenParen => {
-                        let inner = self.parse_nodes(depth+1).unwrap();
-                        Ok(Some(Node::List(inner)))
-                    },
+    /**
+     * * the datum starting at token, None for a close paren
+     */
+    fn parse_node(&mut self, token: &Spanned<Token>) -> Result<Option<Node>, ParseError> {
+        let span = &token.span;
+        if self.depth >= MAX_DEPTH {
+            parse_error!(span, "nesting deeper than {} levels", MAX_DEPTH);
+        }
 
-                    Token::CloseParen => {
-                        if depth > 0 {
-                            Ok(None)
-                        } else {
-                            Err("No matching open paren!".to_string())
-                        }
-                    },
-                    Token::Boolean(b) => Ok(Some(Node::Boolean(*b))),
-                    Token::Identifier(name) => Ok(Some(Node::Identifier(name.to_string()))),
+        self.depth += 1;
+        let res = self.parse_token(token, span);
+        self.depth -= 1;
+        res
+    }
 
-                }
+    fn parse_token(&mut self, token: &Spanned<Token>, span: &Span) -> Result<Option<Node>, ParseError> {
+        match &token.item {
+            Token::Number(n) => Ok(Some(Node::Number(n.clone()))),
+            Token::OpenParen => Ok(Some(self.parse_list(span)?)),
+            Token::VectorParen => match self.parse_list(span)? {
+                Node::Located(list, span) => match *list {
+                    Node::List(items) => Ok(Some(Node::Located(Box::new(Node::Vector(items)), span))),
+                    _ => parse_error!(span, "Unexpected . in a vector literal"),
+                },
+                _ => parse_error!(span, "Unexpected . in a vector literal"),
             },
-            None => {
-                if depth == 0 {
-                    Ok(None)
-                } else {
-                    Err(format!("Unexpected end of input at depth: {}", depth))
-                }
+            Token::CloseParen => Ok(None),
+            Token::Boolean(b) => Ok(Some(Node::Boolean(*b))),
+            Token::String(s) => Ok(Some(Node::String(s.clone()))),
+            Token::Char(c) => Ok(Some(Node::Char(*c))),
+            Token::Identifier(name) => Ok(Some(Node::Identifier(name.to_string()))),
+            Token::Dot => parse_error!(span, "Unexpected . outside of a list"),
+            Token::Quote => self.parse_abbreviation("quote", span),
+            Token::Quasiquote => self.parse_abbreviation("quasiquote", span),
+            Token::Unquote => self.parse_abbreviation("unquote", span),
+            Token::UnquoteSplicing => self.parse_abbreviation("unquote-splicing", span),
+        }
+    }
+
+    /**
+     * * the datum following the token at span, which must not be a close paren or the end of input
+     */
+    fn parse_datum(&mut self, span: &Span, context: &str) -> Result<Node, ParseError> {
+        match self.tokens.next() {
+            Some(token) => match self.parse_node(token)? {
+                Some(node) => Ok(node),
+                None => parse_error!(token.span, "expect a datum {}", context),
             },
-            _ => Err("Invalid token type!".to_string()),
+            None => parse_error!(span, "Unexpected end of input, expect a datum {}", context),
         }
     }
+
+    /**
+     * * the elements of a list up to its close paren, (a b . c) reads as a dotted list
+     * ! exactly one datum must follow the dot, and at least one must precede it
+     */
+    fn parse_list(&mut self, open: &Span) -> Result<Node, ParseError> {
+        let mut items = Vec::new();
+        loop {
+            let token = match self.tokens.next() {
+                Some(token) => token,
+                None => parse_error!(open, "Unexpected end of input, this paren is never closed"),
+            };
+
+            if let Token::Dot = token.item {
+                if items.is_empty() {
+                    parse_error!(token.span, "expect a datum before .");
+                }
+                let tail = self.parse_datum(&token.span, "after .")?;
+                return match self.tokens.next() {
+                    Some(close) if close.item == Token::CloseParen => {
+                        Ok(Node::Located(Box::new(Node::DottedList(items, Box::new(tail))), open.to(&close.span)))
+                    },
+                    Some(other) => parse_error!(other.span, "expect ) after the datum following ."),
+                    None => parse_error!(open, "Unexpected end of input, this paren is never closed"),
+                };
+            }
+
+            match self.parse_node(token)? {
+                Some(node) => items.push(node),
+                None => return Ok(Node::Located(Box::new(Node::List(items)), open.to(&token.span))),
+            }
+        }
+    }
+
+    /**
+     * * 'x `x ,x ,@x read as (quote x) (quasiquote x) (unquote x) (unquote-splicing x)
+     */
+    fn parse_abbreviation(&mut self, name: &str, span: &Span) -> Result<Option<Node>, ParseError> {
+        let node = self.parse_datum(span, &format!("after {}", name))?;
+        Ok(Some(Node::Located(Box::new(Node::List(vec![Node::Identifier(name.to_string()), node])), span.clone())))
+    }
    
 }
 
@@ -80,21 +201,73 @@ impl<'a> Parser<'a> {
 mod tests {
 
     use super::*;
+    use crate::util::{parse_test_template, spanned};
+    use crate::interpreter::lex::lexer;
 
     #[test]
     fn parse_int() {
-        let input = vec![Token::Integer(1234)];
-        let exp = vec![Node::Integer(1234)];
+        let input = vec![Token::Number(Number::Integer(1234))];
+        let exp = vec![Node::Number(Number::Integer(1234))];
         parse_test_template(input, exp);
     }
     
     #[test]
     fn parse_paren_int() {
-        let input = vec![Token::OpenParen, Token::Integer(1234), Token::CloseParen];
-        let exp = vec![Node::List(vec![Node::Integer(1234)])];
+        let input = vec![Token::OpenParen, Token::Number(Number::Integer(1234)), Token::CloseParen];
+        let exp = vec![Node::List(vec![Node::Number(Number::Integer(1234))])];
+        parse_test_template(input, exp);
+    }
+
+    #[test]
+    fn parse_quote() {
+        let input = vec![Token::Quote, Token::OpenParen, Token::from("a"), Token::Unquote, Token::from("b"), Token::CloseParen];
+        let exp = vec![Node::List(vec![
+            Node::Identifier("quote".to_string()),
+            Node::List(vec![
+                Node::Identifier("a".to_string()),
+                Node::List(vec![Node::Identifier("unquote".to_string()), Node::Identifier("b".to_string())]),
+            ]),
+        ])];
         parse_test_template(input, exp);
     }
-}
 
+    #[test]
+    fn parse_quote_without_datum() {
+        assert!(Parser::parse(&spanned(vec![Token::Quote])).is_err());
+        assert!(Parser::parse(&spanned(vec![Token::OpenParen, Token::Quote, Token::CloseParen])).is_err());
+    }
+
+    #[test]
+    fn parse_vector() {
+        let input = vec![Token::VectorParen, Token::from("a"), Token::OpenParen, Token::CloseParen, Token::CloseParen];
+        let exp = vec![Node::Vector(vec![Node::Identifier("a".to_string()), Node::List(vec![])])];
+        parse_test_template(input, exp);
+        assert!(Parser::parse(&spanned(vec![Token::VectorParen, Token::from("a"), Token::Dot, Token::from("b"), Token::CloseParen])).is_err());
+    }
 
+    #[test]
+    fn parse_dotted_list() {
+        let input = vec![Token::OpenParen, Token::from("a"), Token::from("b"), Token::Dot, Token::from("c"), Token::CloseParen];
+        let exp = vec![Node::DottedList(
+            vec![Node::Identifier("a".to_string()), Node::Identifier("b".to_string())],
+            Box::new(Node::Identifier("c".to_string())),
+        )];
+        parse_test_template(input, exp);
+        assert!(Parser::parse(&spanned(vec![Token::OpenParen, Token::Dot, Token::from("a"), Token::CloseParen])).is_err());
+        assert!(Parser::parse(&spanned(vec![Token::OpenParen, Token::from("a"), Token::Dot, Token::CloseParen])).is_err());
+        assert!(Parser::parse(&spanned(vec![Token::OpenParen, Token::from("a"), Token::Dot, Token::from("b"), Token::from("c"), Token::CloseParen])).is_err());
+        assert!(Parser::parse(&spanned(vec![Token::Dot])).is_err());
+    }
 
+    #[test]
+    fn parse_error_locations() {
+        let err = Parser::parse(&lexer::lex("(a\n (b c)").unwrap()).unwrap_err();
+        assert!(format!("{}", err).starts_with("<input>:1:1: Parse Error: Unexpected end of input"));
+        let err = Parser::parse(&lexer::lex("(a) b)").unwrap()).unwrap_err();
+        assert!(format!("{}", err).starts_with("<input>:1:6: Parse Error: No matching open paren!"));
+        let err = Parser::parse(&lexer::lex("#(a\n . b)").unwrap()).unwrap_err();
+        assert!(format!("{}", err).starts_with("<input>:1:1: Parse Error: Unexpected . in a vector literal"));
+        let deep = format!("{}{}", "(".repeat(5000), ")".repeat(5000));
+        assert!(format!("{}", Parser::parse(&lexer::lex(&deep).unwrap()).unwrap_err()).contains("nesting deeper than 200 levels"));
+    }
+}
@@ -0,0 +1,118 @@
+use std::{fmt, rc::Rc};
+
+/**
+ * * a named source text, shared by every span pointing into it
+ */
+pub struct Source {
+    name: String,
+    text: String,
+}
+
+impl Source {
+    pub fn new(name: &str, text: &str) -> Rc<Source> {
+        Rc::new(Source {
+            name: name.to_string(),
+            text: text.to_string(),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    fn line(&self, line: usize) -> Option<&str> {
+        self.text.lines().nth(line - 1)
+    }
+}
+
+/**
+ * * a position in a source: 1-based line and column and the length in chars
+ * ! a span covering several lines is underlined up to the end of its first line
+ */
+#[derive(Clone)]
+pub struct Span {
+    source: Rc<Source>,
+    pub line: usize,
+    pub column: usize,
+    len: usize,
+}
+
+impl Span {
+    pub fn new(source: Rc<Source>, line: usize, column: usize, len: usize) -> Span {
+        Span { source, line, column, len }
+    }
+
+    /**
+     * * the span from the start of self to the end of end
+     */
+    pub fn to(&self, end: &Span) -> Span {
+        let len = match end.line == self.line {
+            true => end.column.saturating_add(end.len).saturating_sub(self.column),
+            false => usize::MAX,
+        };
+        Span::new(self.source.clone(), self.line, self.column, len)
+    }
+
+    /**
+     * * the source line of the span with the span underlined by carets
+     */
+    pub fn excerpt(&self) -> String {
+        let text = self.source.line(self.line).unwrap_or("");
+        let width = self.line.to_string().len();
+        let available = text.chars().count().saturating_sub(self.column - 1);
+        let carets = self.len.min(available).max(1);
+        format!(
+            "{:w$} |\n{} | {}\n{:w$} | {}{}",
+            "", self.line, text, "", " ".repeat(self.column - 1), "^".repeat(carets), w = width,
+        )
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.source.name, self.line, self.column)
+    }
+}
+
+impl fmt::Debug for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+/**
+ * * an item together with where it was read from
+ * * it compares equal to the bare item, positions do not take part in equality
+ */
+#[derive(Debug, Clone)]
+pub struct Spanned<T> {
+    pub item: T,
+    pub span: Span,
+}
+
+impl<T: PartialEq> PartialEq<T> for Spanned<T> {
+    fn eq(&self, other: &T) -> bool {
+        self.item == *other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn span_excerpt() {
+        let source = Source::new("test.sch", "(define x 1)\n(car  x)\n");
+        let span = Span::new(source.clone(), 2, 1, 1).to(&Span::new(source.clone(), 2, 8, 1));
+        assert_eq!(format!("{}", span), "test.sch:2:1");
+        assert_eq!(span.excerpt(), "  |\n2 | (car  x)\n  | ^^^^^^^^");
+        // * a span running over several lines is underlined to the end of its first line
+        let span = Span::new(source.clone(), 1, 9, 1).to(&Span::new(source, 2, 1, 1));
+        assert_eq!(span.excerpt(), "  |\n1 | (define x 1)\n  |         ^^^^");
+    }
+}
@@ -0,0 +1,376 @@
+use super::eval::{Env, Function, RuntimeError, Value, ValueOperation};
+use super::number::Number;
+use super::list::list_arg;
+use super::symbol::Symbol;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+
+/**
+ * * string, char and symbol natives, registered into the root env by Env::new_root
+ */
+pub fn define_natives(env: &mut Env) {
+    let natives: &[(&str, ValueOperation)] = &[
+        ("string?", native_is_string),
+        ("char?", native_is_char),
+        ("symbol?", native_is_symbol),
+        ("string-length", native_string_length),
+        ("string-ref", native_string_ref),
+        ("substring", native_substring),
+        ("string-append", native_string_append),
+        ("string-copy", native_string_copy),
+        ("string", native_string),
+        ("make-string", native_make_string),
+        ("string-upcase", native_string_upcase),
+        ("string-downcase", native_string_downcase),
+        ("string=?", native_string_eq),
+        ("string<?", native_string_lt),
+        ("string>?", native_string_gt),
+        ("string<=?", native_string_le),
+        ("string>=?", native_string_ge),
+        ("string->symbol", native_string_to_symbol),
+        ("symbol->string", native_symbol_to_string),
+        ("string->list", native_string_to_list),
+        ("list->string", native_list_to_string),
+        ("char->integer", native_char_to_integer),
+        ("integer->char", native_integer_to_char),
+        ("char=?", native_char_eq),
+        ("char<?", native_char_lt),
+        ("char>?", native_char_gt),
+        ("char<=?", native_char_le),
+        ("char>=?", native_char_ge),
+        ("char-upcase", native_char_upcase),
+        ("char-downcase", native_char_downcase),
+        ("char-alphabetic?", native_is_char_alphabetic),
+        ("char-numeric?", native_is_char_numeric),
+        ("char-whitespace?", native_is_char_whitespace),
+        ("char-upper-case?", native_is_char_upper_case),
+        ("char-lower-case?", native_is_char_lower_case),
+        ("digit-value", native_digit_value),
+    ];
+
+    for (name, op) in natives {
+        env.define(name, &Value::Procedure(Function::Native(*op))).unwrap();
+    }
+}
+
+pub(crate) fn check_arity(name: &str, args: &[Value], min: usize, max: usize) -> Result<(), RuntimeError> {
+    if args.len() < min || args.len() > max {
+        runtime_error!("{} expects {} to {} arguments but got: {:?}", name, min, max, args);
+    }
+    Ok(())
+}
+
+fn string_arg<'a>(name: &str, v: &'a Value) -> Result<&'a str, RuntimeError> {
+    match v {
+        Value::String(s) => Ok(s),
+        _ => runtime_error!("{} expects a string but got: {:?}", name, v),
+    }
+}
+
+fn char_arg(name: &str, v: &Value) -> Result<char, RuntimeError> {
+    match v {
+        Value::Char(c) => Ok(*c),
+        _ => runtime_error!("{} expects a char but got: {:?}", name, v),
+    }
+}
+
+/**
+ * * an exact non-negative integer used as an index or a length
+ */
+pub(crate) fn index_arg(name: &str, v: &Value) -> Result<usize, RuntimeError> {
+    match v {
+        Value::Number(Number::Integer(i)) if *i >= 0 => Ok(*i as usize),
+        _ => runtime_error!("{} expects an exact non-negative integer but got: {:?}", name, v),
+    }
+}
+
+/**
+ * * the chars of a string between the optional start and end arguments
+ */
+fn char_range(name: &str, s: &str, start: Option<&Value>, end: Option<&Value>) -> Result<Vec<char>, RuntimeError> {
+    let chars: Vec<char> = s.chars().collect();
+    let start = match start {
+        Some(v) => index_arg(name, v)?,
+        None => 0,
+    };
+    let end = match end {
+        Some(v) => index_arg(name, v)?,
+        None => chars.len(),
+    };
+    if start > end || end > chars.len() {
+        runtime_error!("{}: range {} to {} out of bounds for {:?}", name, start, end, s);
+    }
+    Ok(chars[start..end].to_vec())
+}
+
+fn native_is_string(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("string?", args, 1, 1)?;
+    Ok(Value::Boolean(matches!(args[0], Value::String(_))))
+}
+
+fn native_is_char(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("char?", args, 1, 1)?;
+    Ok(Value::Boolean(matches!(args[0], Value::Char(_))))
+}
+
+fn native_is_symbol(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("symbol?", args, 1, 1)?;
+    Ok(Value::Boolean(matches!(args[0], Value::Symbol(_))))
+}
+
+fn native_string_length(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("string-length", args, 1, 1)?;
+    Ok(Value::from(string_arg("string-length", &args[0])?.chars().count() as i64))
+}
+
+fn native_string_ref(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("string-ref", args, 2, 2)?;
+    let s = string_arg("string-ref", &args[0])?;
+    let k = index_arg("string-ref", &args[1])?;
+    match s.chars().nth(k) {
+        Some(c) => Ok(Value::Char(c)),
+        None => runtime_error!("string-ref: index {} out of bounds for {:?}", k, s),
+    }
+}
+
+fn native_substring(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("substring", args, 2, 3)?;
+    let s = string_arg("substring", &args[0])?;
+    Ok(Value::String(char_range("substring", s, args.get(1), args.get(2))?.into_iter().collect::<String>().into()))
+}
+
+fn native_string_copy(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("string-copy", args, 1, 3)?;
+    let s = string_arg("string-copy", &args[0])?;
+    Ok(Value::String(char_range("string-copy", s, args.get(1), args.get(2))?.into_iter().collect::<String>().into()))
+}
+
+fn native_string_append(args: &[Value]) -> Result<Value, RuntimeError> {
+    let mut res = String::new();
+    for v in args {
+        res.push_str(string_arg("string-append", v)?);
+    }
+    Ok(Value::String(res.into()))
+}
+
+fn native_string(args: &[Value]) -> Result<Value, RuntimeError> {
+    let chars: Result<String, RuntimeError> = args.iter().map(|v| char_arg("string", v)).collect();
+    Ok(Value::String(chars?.into()))
+}
+
+fn native_make_string(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("make-string", args, 1, 2)?;
+    let k = index_arg("make-string", &args[0])?;
+    let c = match args.get(1) {
+        Some(v) => char_arg("make-string", v)?,
+        None => ' ',
+    };
+    Ok(Value::String(std::iter::repeat_n(c, k).collect::<String>().into()))
+}
+
+fn native_string_upcase(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("string-upcase", args, 1, 1)?;
+    Ok(Value::String(string_arg("string-upcase", &args[0])?.to_uppercase().into()))
+}
+
+fn native_string_downcase(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("string-downcase", args, 1, 1)?;
+    Ok(Value::String(string_arg("string-downcase", &args[0])?.to_lowercase().into()))
+}
+
+/**
+ * * chained comparison: every adjacent pair of arguments must satisfy the predicate
+ */
+fn compare_chain<T: Ord>(name: &str, args: &[Value], get: fn(&str, &Value) -> Result<T, RuntimeError>, pred: fn(Ordering) -> bool) -> Result<Value, RuntimeError> {
+    if args.is_empty() {
+        runtime_error!("{} expects at least one argument", name);
+    }
+    let items = args.iter().map(|v| get(name, v)).collect::<Result<Vec<T>, RuntimeError>>()?;
+    Ok(Value::Boolean(items.windows(2).all(|w| pred(w[0].cmp(&w[1])))))
+}
+
+fn owned_string_arg(name: &str, v: &Value) -> Result<String, RuntimeError> {
+    string_arg(name, v).map(|s| s.to_string())
+}
+
+fn native_string_eq(args: &[Value]) -> Result<Value, RuntimeError> {
+    compare_chain("string=?", args, owned_string_arg, |o| o == Ordering::Equal)
+}
+
+fn native_string_lt(args: &[Value]) -> Result<Value, RuntimeError> {
+    compare_chain("string<?", args, owned_string_arg, |o| o == Ordering::Less)
+}
+
+fn native_string_gt(args: &[Value]) -> Result<Value, RuntimeError> {
+    compare_chain("string>?", args, owned_string_arg, |o| o == Ordering::Greater)
+}
+
+fn native_string_le(args: &[Value]) -> Result<Value, RuntimeError> {
+    compare_chain("string<=?", args, owned_string_arg, |o| o != Ordering::Greater)
+}
+
+fn native_string_ge(args: &[Value]) -> Result<Value, RuntimeError> {
+    compare_chain("string>=?", args, owned_string_arg, |o| o != Ordering::Less)
+}
+
+fn native_char_eq(args: &[Value]) -> Result<Value, RuntimeError> {
+    compare_chain("char=?", args, char_arg, |o| o == Ordering::Equal)
+}
+
+fn native_char_lt(args: &[Value]) -> Result<Value, RuntimeError> {
+    compare_chain("char<?", args, char_arg, |o| o == Ordering::Less)
+}
+
+fn native_char_gt(args: &[Value]) -> Result<Value, RuntimeError> {
+    compare_chain("char>?", args, char_arg, |o| o == Ordering::Greater)
+}
+
+fn native_char_le(args: &[Value]) -> Result<Value, RuntimeError> {
+    compare_chain("char<=?", args, char_arg, |o| o != Ordering::Greater)
+}
+
+fn native_char_ge(args: &[Value]) -> Result<Value, RuntimeError> {
+    compare_chain("char>=?", args, char_arg, |o| o != Ordering::Less)
+}
+
+fn native_string_to_symbol(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("string->symbol", args, 1, 1)?;
+    Ok(Value::Symbol(Symbol::intern(string_arg("string->symbol", &args[0])?)))
+}
+
+fn native_symbol_to_string(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("symbol->string", args, 1, 1)?;
+    match &args[0] {
+        Value::Symbol(s) => Ok(Value::String(s.to_string().into())),
+        v => runtime_error!("symbol->string expects a symbol but got: {:?}", v),
+    }
+}
+
+fn native_string_to_list(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("string->list", args, 1, 3)?;
+    let s = string_arg("string->list", &args[0])?;
+    let chars = char_range("string->list", s, args.get(1), args.get(2))?;
+    Ok(Value::list(chars.into_iter().map(Value::Char).collect()))
+}
+
+fn native_list_to_string(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("list->string", args, 1, 1)?;
+    native_string(&list_arg("list->string", &args[0])?)
+}
+
+fn native_char_to_integer(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("char->integer", args, 1, 1)?;
+    Ok(Value::from(char_arg("char->integer", &args[0])? as i64))
+}
+
+fn native_integer_to_char(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("integer->char", args, 1, 1)?;
+    let i = index_arg("integer->char", &args[0])?;
+    match u32::try_from(i).ok().and_then(char::from_u32) {
+        Some(c) => Ok(Value::Char(c)),
+        None => runtime_error!("integer->char: {} is not a unicode scalar value", i),
+    }
+}
+
+/**
+ * * a char to char conversion, for the case mappings which may expand we keep the char as is
+ */
+fn map_char(name: &str, args: &[Value], f: fn(char) -> Option<char>) -> Result<Value, RuntimeError> {
+    check_arity(name, args, 1, 1)?;
+    let c = char_arg(name, &args[0])?;
+    Ok(Value::Char(f(c).unwrap_or(c)))
+}
+
+fn single_case(mut it: impl Iterator<Item=char>) -> Option<char> {
+    match (it.next(), it.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+fn native_char_upcase(args: &[Value]) -> Result<Value, RuntimeError> {
+    map_char("char-upcase", args, |c| single_case(c.to_uppercase()))
+}
+
+fn native_char_downcase(args: &[Value]) -> Result<Value, RuntimeError> {
+    map_char("char-downcase", args, |c| single_case(c.to_lowercase()))
+}
+
+fn char_predicate(name: &str, args: &[Value], f: fn(char) -> bool) -> Result<Value, RuntimeError> {
+    check_arity(name, args, 1, 1)?;
+    Ok(Value::Boolean(f(char_arg(name, &args[0])?)))
+}
+
+fn native_is_char_alphabetic(args: &[Value]) -> Result<Value, RuntimeError> {
+    char_predicate("char-alphabetic?", args, char::is_alphabetic)
+}
+
+fn native_is_char_numeric(args: &[Value]) -> Result<Value, RuntimeError> {
+    char_predicate("char-numeric?", args, char::is_numeric)
+}
+
+fn native_is_char_whitespace(args: &[Value]) -> Result<Value, RuntimeError> {
+    char_predicate("char-whitespace?", args, char::is_whitespace)
+}
+
+fn native_is_char_upper_case(args: &[Value]) -> Result<Value, RuntimeError> {
+    char_predicate("char-upper-case?", args, char::is_uppercase)
+}
+
+fn native_is_char_lower_case(args: &[Value]) -> Result<Value, RuntimeError> {
+    char_predicate("char-lower-case?", args, char::is_lowercase)
+}
+
+fn native_digit_value(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("digit-value", args, 1, 1)?;
+    match char_arg("digit-value", &args[0])?.to_digit(10) {
+        Some(d) => Ok(Value::from(d as i64)),
+        None => Ok(Value::Boolean(false)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::interpreter::tests::eval_str;
+
+    #[test]
+    fn string_procedures() {
+        assert_eq!(eval_str(r#"(string-length "héllo")"#).unwrap(), Value::from(5));
+        assert_eq!(eval_str(r#"(string-append "foo" "" "bar")"#).unwrap(), Value::String("foobar".into()));
+        assert_eq!(eval_str(r#"(substring "hello world" 6 11)"#).unwrap(), Value::String("world".into()));
+        assert_eq!(eval_str(r#"(string-ref "abc" 1)"#).unwrap(), Value::Char('b'));
+        assert_eq!(eval_str(r#"(string->symbol "abc")"#).unwrap(), Value::Symbol(Symbol::intern("abc")));
+        assert_eq!(eval_str(r#"(string #\a #\b)"#).unwrap(), Value::String("ab".into()));
+        assert_eq!(eval_str(r#"(string<? "abc" "abd" "b")"#).unwrap(), Value::Boolean(true));
+        assert_eq!(eval_str(r#"(string=? "abc" "abc" "abd")"#).unwrap(), Value::Boolean(false));
+    }
+
+    #[test]
+    fn string_errors() {
+        assert!(eval_str(r#"(string-ref "abc" 3)"#).is_err());
+        assert!(eval_str(r#"(substring "abc" 2 1)"#).is_err());
+        assert!(eval_str(r#"(string-length 5)"#).is_err());
+        assert!(eval_str(r#"(string-append "a" 1)"#).is_err());
+    }
+
+    #[test]
+    fn char_procedures() {
+        assert_eq!(eval_str(r"(char->integer #\A)").unwrap(), Value::from(65));
+        assert_eq!(eval_str(r"(integer->char 955)").unwrap(), Value::Char('λ'));
+        assert_eq!(eval_str(r"(char-upcase #\a)").unwrap(), Value::Char('A'));
+        assert_eq!(eval_str(r"(char<? #\a #\b #\c)").unwrap(), Value::Boolean(true));
+        assert_eq!(eval_str(r"(digit-value #\7)").unwrap(), Value::from(7));
+    }
+
+    #[test]
+    fn write_formatting() {
+        let s = Value::String("say \"hi\"\n".into());
+        assert_eq!(format!("{}", s), "say \"hi\"\n");
+        assert_eq!(format!("{:?}", s), r#""say \"hi\"\n""#);
+        assert_eq!(format!("{:?}", Value::Char(' ')), r"#\space");
+        assert_eq!(format!("{:?}", Value::Char('a')), r"#\a");
+        assert_eq!(format!("{}", Value::Char('a')), "a");
+    }
+}
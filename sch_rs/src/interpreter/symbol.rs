@@ -0,0 +1,136 @@
+use std::{cell::RefCell, collections::{HashMap, HashSet}, fmt, hash::{BuildHasherDefault, Hasher}, ops::Deref};
+
+/**
+ * * an interned identifier, every symbol with the same name has the same id
+ * * comparing two symbols, looking one up in an env or hashing it works on the id alone
+ * ! names are never freed, like the table of any interpreter they only grow with the identifiers in use
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+/**
+ * * a map keyed by symbols, like the variables of an env
+ */
+pub type SymbolMap<V> = HashMap<Symbol, V, BuildHasherDefault<SymbolHasher>>;
+
+pub type SymbolSet = HashSet<Symbol, BuildHasherDefault<SymbolHasher>>;
+
+/**
+ * * hashes a symbol by spreading its id over the bits, nothing is left to hash once a name is interned
+ */
+#[derive(Default)]
+pub struct SymbolHasher(u64);
+
+impl Hasher for SymbolHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.write_u64(u64::from(*b));
+        }
+    }
+
+    fn write_u32(&mut self, id: u32) {
+        self.write_u64(u64::from(id));
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        // * the fibonacci multiplier moves the small ids into the high bits the table probes with
+        self.0 = (self.0 ^ n).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+struct Table {
+    names: Vec<&'static str>,
+    ids: HashMap<&'static str, Symbol>,
+}
+
+thread_local! {
+    // * values never leave the thread which made them, neither do the ids in them
+    static TABLE: RefCell<Table> = RefCell::new(Table { names: Vec::new(), ids: HashMap::new() });
+}
+
+impl Symbol {
+    /**
+     * * the symbol called name, made the first time the name is seen
+     */
+    pub fn intern(name: &str) -> Symbol {
+        TABLE.with(|table| {
+            let mut table = table.borrow_mut();
+            if let Some(symbol) = table.ids.get(name) {
+                return *symbol;
+            }
+            let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+            let symbol = Symbol(table.names.len() as u32);
+            table.names.push(name);
+            table.ids.insert(name, symbol);
+            symbol
+        })
+    }
+
+    pub fn as_str(self) -> &'static str {
+        TABLE.with(|table| table.borrow().names[self.0 as usize])
+    }
+
+    pub fn id(self) -> u32 {
+        self.0
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Symbol {
+        Symbol::intern(name)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// * like the name it stands for, so messages quote identifiers as before
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn symbol_interning() {
+        let a = Symbol::intern("lambda");
+        assert_eq!(a, Symbol::intern(&(String::from("lamb") + "da")));
+        assert_ne!(a, Symbol::intern("lambda?"));
+        assert_eq!(a.id(), Symbol::from("lambda").id());
+        assert_eq!(a, "lambda");
+        assert_eq!(format!("{} {:?}", a, a), "lambda \"lambda\"");
+        assert!(a.starts_with("lam"));
+    }
+}
@@ -0,0 +1,53 @@
+use super::eval::{Env, Function, RuntimeError, Value, ValueOperation};
+use super::strings::check_arity;
+use std::cell::RefCell;
+
+thread_local! {
+    // * the program name and its arguments, as given to the script runner
+    static COMMAND_LINE: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/**
+ * * set what (command-line) returns for the evaluators of this thread
+ */
+pub fn set_command_line(args: Vec<String>) {
+    COMMAND_LINE.with(|c| *c.borrow_mut() = args);
+}
+
+/**
+ * * natives about the process the interpreter runs in, registered into the root env by Env::new_root
+ */
+pub fn define_natives(env: &mut Env) {
+    let natives: &[(&str, ValueOperation)] = &[
+        ("command-line", native_command_line),
+    ];
+
+    for (name, op) in natives {
+        env.define(name, &Value::Procedure(Function::Native(*op))).unwrap();
+    }
+}
+
+/**
+ * * (command-line) the list of the program name and its arguments, as strings
+ * ! in the repl nothing has been set and the list is empty
+ */
+fn native_command_line(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("command-line", args, 0, 0)?;
+    let items = COMMAND_LINE.with(|c| c.borrow().iter().map(|s| Value::String(s.as_str().into())).collect());
+    Ok(Value::list(items))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::interpreter::tests::eval_write;
+
+    #[test]
+    fn system_command_line() {
+        assert_eq!(eval_write("(command-line)"), "()");
+        set_command_line(vec!["script.sch".to_string(), "-n".to_string(), "3".to_string()]);
+        assert_eq!(eval_write("(cdr (command-line))"), "(\"-n\" \"3\")");
+        set_command_line(Vec::new());
+    }
+}
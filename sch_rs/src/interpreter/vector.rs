@@ -0,0 +1,226 @@
+use super::eval::{Env, Function, Machine, RuntimeError, Tail, TailOperation, Value, ValueOperation};
+use super::gc::EnvRef;
+use super::list::{list_arg, release};
+use super::strings::{check_arity, index_arg};
+use std::{cell::RefCell, mem, ops::Deref, rc::Rc};
+
+/**
+ * * vector natives, registered into the root env by Env::new_root
+ */
+pub fn define_natives(env: &mut Env) {
+    let natives: &[(&str, ValueOperation)] = &[
+        ("vector?", native_is_vector),
+        ("vector", native_vector),
+        ("make-vector", native_make_vector),
+        ("vector-length", native_vector_length),
+        ("vector-ref", native_vector_ref),
+        ("vector-set!", native_vector_set),
+        ("vector->list", native_vector_to_list),
+        ("list->vector", native_list_to_vector),
+        ("vector-fill!", native_vector_fill),
+    ];
+
+    for (name, op) in natives {
+        env.define(name, &Value::Procedure(Function::Native(*op))).unwrap();
+    }
+
+    // * natives calling back into scheme procedures need the evaluator
+    let tail_natives: &[(&str, TailOperation)] = &[
+        ("vector-map", native_vector_map),
+    ];
+
+    for (name, op) in tail_natives {
+        env.define(name, &Value::Procedure(Function::TailNative(*op))).unwrap();
+    }
+}
+
+/**
+ * * the items of a vector, shared by every copy of the value
+ * ! vectors nested in it are freed on the work list of release instead of recursively
+ */
+pub struct Vector(RefCell<Vec<Value>>);
+
+impl Vector {
+    pub fn new(items: Vec<Value>) -> Vector {
+        Vector(RefCell::new(items))
+    }
+}
+
+impl Deref for Vector {
+    type Target = RefCell<Vec<Value>>;
+
+    fn deref(&self) -> &RefCell<Vec<Value>> {
+        &self.0
+    }
+}
+
+impl Drop for Vector {
+    fn drop(&mut self) {
+        release(mem::take(self.0.get_mut()));
+    }
+}
+
+fn new_vector(items: Vec<Value>) -> Value {
+    Value::Vector(Rc::new(Vector::new(items)))
+}
+
+fn vector_arg<'a>(name: &str, v: &'a Value) -> Result<&'a Rc<Vector>, RuntimeError> {
+    match v {
+        Value::Vector(items) => Ok(items),
+        _ => runtime_error!("{} expects a vector but got: {:?}", name, v),
+    }
+}
+
+/**
+ * * the optional start and end arguments of a vector of length len
+ */
+fn vector_range(name: &str, len: usize, start: Option<&Value>, end: Option<&Value>) -> Result<(usize, usize), RuntimeError> {
+    let start = match start {
+        Some(v) => index_arg(name, v)?,
+        None => 0,
+    };
+    let end = match end {
+        Some(v) => index_arg(name, v)?,
+        None => len,
+    };
+    if start > end || end > len {
+        runtime_error!("{}: range {} to {} out of bounds for a vector of length {}", name, start, end, len);
+    }
+    Ok((start, end))
+}
+
+fn native_is_vector(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("vector?", args, 1, 1)?;
+    Ok(Value::Boolean(matches!(args[0], Value::Vector(_))))
+}
+
+fn native_vector(args: &[Value]) -> Result<Value, RuntimeError> {
+    Ok(new_vector(args.to_vec()))
+}
+
+/**
+ * * (make-vector k [fill])
+ */
+fn native_make_vector(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("make-vector", args, 1, 2)?;
+    let k = index_arg("make-vector", &args[0])?;
+    let fill = args.get(1).cloned().unwrap_or(Value::Unit);
+    Ok(new_vector(vec![fill; k]))
+}
+
+fn native_vector_length(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("vector-length", args, 1, 1)?;
+    Ok(Value::from(vector_arg("vector-length", &args[0])?.borrow().len() as i64))
+}
+
+fn native_vector_ref(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("vector-ref", args, 2, 2)?;
+    let items = vector_arg("vector-ref", &args[0])?.borrow();
+    let k = index_arg("vector-ref", &args[1])?;
+    match items.get(k) {
+        Some(v) => Ok(v.clone()),
+        None => runtime_error!("vector-ref: index {} out of range for a vector of length {}", k, (items.len())),
+    }
+}
+
+fn native_vector_set(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("vector-set!", args, 3, 3)?;
+    let mut items = vector_arg("vector-set!", &args[0])?.borrow_mut();
+    let k = index_arg("vector-set!", &args[1])?;
+    match items.get_mut(k) {
+        Some(slot) => *slot = args[2].clone(),
+        None => runtime_error!("vector-set!: index {} out of range for a vector of length {}", k, (items.len())),
+    }
+    Ok(Value::Unit)
+}
+
+/**
+ * * (vector->list v [start [end]])
+ */
+fn native_vector_to_list(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("vector->list", args, 1, 3)?;
+    let items = vector_arg("vector->list", &args[0])?.borrow();
+    let (start, end) = vector_range("vector->list", items.len(), args.get(1), args.get(2))?;
+    Ok(Value::list(items[start..end].to_vec()))
+}
+
+pub(crate) fn native_list_to_vector(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("list->vector", args, 1, 1)?;
+    Ok(new_vector(list_arg("list->vector", &args[0])?))
+}
+
+/**
+ * * (vector-fill! v fill [start [end]])
+ */
+fn native_vector_fill(args: &[Value]) -> Result<Value, RuntimeError> {
+    check_arity("vector-fill!", args, 2, 4)?;
+    let mut items = vector_arg("vector-fill!", &args[0])?.borrow_mut();
+    let (start, end) = vector_range("vector-fill!", items.len(), args.get(2), args.get(3))?;
+    for slot in &mut items[start..end] {
+        *slot = args[1].clone();
+    }
+    Ok(Value::Unit)
+}
+
+/**
+ * * (vector-map proc v1 v2 ...) a new vector as long as the shortest argument
+ * ! the vectors are copied first, so proc may mutate them
+ */
+fn native_vector_map(args: &[Value], env: EnvRef, m: &mut Machine) -> Result<Tail, RuntimeError> {
+    if args.len() < 2 {
+        runtime_error!("vector-map expects a procedure and at least one vector but got: {:?}", args);
+    }
+    let vectors = args[1..].iter()
+        .map(|v| Ok(vector_arg("vector-map", v)?.borrow().clone()))
+        .collect::<Result<Vec<Vec<Value>>, RuntimeError>>()?;
+    let len = vectors.iter().map(Vec::len).min().unwrap_or(0);
+
+    let arg_lists = (0..len).map(|k| vectors.iter().map(|v| v[k].clone()).collect()).collect();
+    m.map(args[0].clone(), arg_lists, new_vector, env)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::interpreter::tests::{eval_str, eval_write};
+
+    #[test]
+    fn vector_literals() {
+        assert_eq!(eval_write("#(1 \"a\" #\\b (c))"), "#(1 \"a\" #\\b (c))");
+        assert_eq!(eval_write("'#(a #(b))"), "#(a #(b))");
+        assert_eq!(eval_write("(vector 1 2)"), "#(1 2)");
+        assert_eq!(eval_write("(make-vector 2 'x)"), "#(x x)");
+        assert_eq!(eval_write("(list (vector? #()) (vector? '()))"), "(#true #false)");
+    }
+
+    #[test]
+    fn vector_access() {
+        assert_eq!(eval_write("(vector-ref #(1 2 3) 1)"), "2");
+        assert_eq!(eval_write("(vector-length (make-vector 3))"), "3");
+        assert_eq!(eval_write("(define v (vector 1 2 3)) (vector-set! v 0 'a) v"), "#(a 2 3)");
+        assert_eq!(eval_write("(define v (vector 1 2 3)) (vector-fill! v 0 1) v"), "#(1 0 0)");
+        assert_eq!(eval_write("(vector->list #(1 2 3) 1)"), "(2 3)");
+        assert_eq!(eval_write("(list->vector '(1 2))"), "#(1 2)");
+        assert!(eval_str("(vector-ref #(1) 1)").is_err());
+        assert!(eval_str("(vector-set! #(1) -1 0)").is_err());
+        assert!(eval_str("(vector->list #(1 2) 2 1)").is_err());
+    }
+
+    #[test]
+    fn vector_sharing_and_map() {
+        // * a vector is one shared object, mutation is seen through every reference
+        assert_eq!(eval_write("(define v (vector 1 2)) (define w v) (vector-set! w 1 0) v"), "#(1 0)");
+        assert_eq!(eval_write("(vector-map + #(1 2 3) #(10 20))"), "#(11 22)");
+        assert_eq!(eval_write("(vector-map (lambda (x) (* x x)) #(1 2 3))"), "#(1 4 9)");
+        assert!(eval_str("(vector-map 1 #(1))").is_err());
+    }
+
+    #[test]
+    fn vector_containing_itself() {
+        // * data met again inside itself prints as ..., shared data which is no cycle prints in full
+        assert_eq!(eval_write("(define v (vector 1 2)) (vector-set! v 0 v) v"), "#(... 2)");
+        assert_eq!(eval_write("(define l (list 1 2)) (set-car! l l) l"), "(... 2)");
+        assert_eq!(eval_write("(define v (vector 1)) (define l (list v)) (vector-set! v 0 l) l"), "(#(...))");
+        assert_eq!(eval_write("(define x (list 1)) (list x x (vector x x))"), "((1) (1) #((1) (1)))");
+    }
+}
@@ -0,0 +1,389 @@
+use super::compile::{Op, Template};
+use super::eval::{Compiled, Frame, Function, Machine, RuntimeError, Tail, Value};
+use super::expand::Expander;
+use super::gc::{self, EnvRef, LocalsRef, Marker};
+use super::symbol::Symbol;
+use std::{iter, mem, rc::Rc};
+
+/**
+ * * a frame of local variables made by a call or a let, slots are None until their define ran
+ * * the tree-walker keeps the local variables of resolved code in these frames as well
+ * ! frames live on the gc heap, closures made in a frame refer back to it
+ */
+pub struct Locals {
+    slots: Vec<Option<Value>>,
+    names: Rc<Vec<Symbol>>,
+    parent: Option<LocalsRef>,
+}
+
+impl Locals {
+    pub(super) fn alloc(values: Vec<Value>, names: Rc<Vec<Symbol>>, parent: Option<LocalsRef>) -> LocalsRef {
+        let unassigned = names.len() - values.len();
+        let slots = values.into_iter().map(Some).chain(iter::repeat_n(None, unassigned)).collect();
+        LocalsRef::alloc(Locals { slots, names, parent })
+    }
+
+    // * the frame depth levels out from the innermost frame locals
+    fn frame(locals: Option<LocalsRef>, depth: usize) -> Result<LocalsRef, RuntimeError> {
+        let mut frame = locals.expect("only existing frames are addressed");
+        for _ in 0..depth {
+            frame = frame.with(|f| f.parent)?.expect("only existing frames are addressed");
+        }
+        Ok(frame)
+    }
+
+    /**
+     * * the value in a slot of the frame depth levels out, a slot whose define has not run yet is an error
+     */
+    pub(super) fn get(locals: Option<LocalsRef>, depth: usize, slot: usize) -> Result<Value, RuntimeError> {
+        match Locals::frame(locals, depth)?.with(|f| f.slots[slot].clone().ok_or(f.names[slot]))? {
+            Ok(v) => Ok(v),
+            Err(name) => runtime_error!("Used before define: {:?}", (Expander::source_name(&name.to_string()))),
+        }
+    }
+
+    pub(super) fn set(locals: Option<LocalsRef>, depth: usize, slot: usize, v: &Value) -> Result<(), RuntimeError> {
+        let res = Locals::frame(locals, depth)?.with(|f| match &mut f.slots[slot] {
+            Some(old) => {
+                *old = v.clone();
+                Ok(())
+            },
+            None => Err(f.names[slot]),
+        })?;
+        match res {
+            Ok(()) => Ok(()),
+            Err(name) => runtime_error!("Can't set an undefined variable: {:?}", name),
+        }
+    }
+
+    // * a define binds a slot of the innermost frame, running it again assigns the slot
+    pub(super) fn define(locals: Option<LocalsRef>, slot: usize, v: &Value) -> Result<(), RuntimeError> {
+        Locals::frame(locals, 0)?.with(|f| f.slots[slot] = Some(v.clone()))
+    }
+
+    pub(super) fn trace(&self, m: &mut Marker) {
+        m.locals(self.parent);
+        m.values(self.slots.iter().flatten());
+    }
+}
+
+/**
+ * * compiled code being run: where it is, its frames and the operands computed so far
+ * ! an activation waiting for a call is a frame of the machine stack, so continuations copy it
+ */
+#[derive(Clone)]
+pub struct Activation {
+    template: Rc<Template>,
+    pc: usize,
+    locals: Option<LocalsRef>,
+    globals: EnvRef,
+    stack: Vec<Value>,
+}
+
+impl Activation {
+    /**
+     * * the activation running a toplevel form
+     */
+    pub(super) fn toplevel(template: Rc<Template>, globals: EnvRef) -> Activation {
+        Activation { template, pc: 0, locals: None, globals, stack: Vec::new() }
+    }
+
+    /**
+     * * the activation of a compiled closure called with args
+     */
+    pub(super) fn call(template: &Rc<Template>, locals: Option<LocalsRef>, globals: EnvRef, args: Vec<Value>) -> Result<Activation, RuntimeError> {
+        let args = template.params.arguments(args)?;
+        Ok(Activation {
+            template: template.clone(),
+            pc: 0,
+            locals: match template.names.is_empty() {
+                true => locals,
+                false => Some(Locals::alloc(args, template.names.clone(), locals)),
+            },
+            globals,
+            stack: Vec::new(),
+        })
+    }
+
+    pub(super) fn trace(&self, m: &mut Marker) {
+        m.template(&self.template);
+        m.locals(self.locals);
+        m.env(self.globals);
+        m.values(self.stack.iter());
+    }
+
+    // * hand the value of a call back to the code waiting for it
+    pub(super) fn push(&mut self, v: Value) {
+        self.stack.push(v);
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("the compiler balances the operand stack")
+    }
+}
+
+impl Machine {
+    /**
+     * * run compiled code until it returns to a frame which is not compiled code,
+     * * or hands a call of a procedure which is not compiled over to the machine
+     * ! calls between compiled closures stay in this loop, a tail call replaces the activation
+     */
+    pub(super) fn execute(&mut self, mut act: Activation) -> Result<Tail, RuntimeError> {
+        'activation: loop {
+            // * a call or a return between compiled procedures is a safe point for the collector
+            if gc::should_collect() {
+                self.collect(|m| act.trace(m));
+            }
+            let template = act.template.clone();
+            loop {
+                let pc = act.pc;
+                act.pc += 1;
+                let v = match &template.code[pc] {
+                    Op::Const(v) => v.clone(),
+                    Op::Local(depth, slot) => match Locals::get(act.locals, *depth, *slot) {
+                        Ok(v) => v,
+                        Err(e) => return self.fail(&template, pc, e),
+                    },
+                    Op::SetLocal(depth, slot) => {
+                        let v = act.pop();
+                        if let Err(e) = Locals::set(act.locals, *depth, *slot, &v) {
+                            return self.fail(&template, pc, e);
+                        }
+                        Value::Unit
+                    },
+                    Op::DefineLocal(slot) => {
+                        let v = act.pop();
+                        if let Err(e) = Locals::define(act.locals, *slot, &v) {
+                            return self.fail(&template, pc, e);
+                        }
+                        v
+                    },
+                    Op::Global(name) => {
+                        let res = act.globals.get(*name);
+                        match res {
+                            Ok(v) => v,
+                            Err(e) => return self.fail(&template, pc, e),
+                        }
+                    },
+                    Op::SetGlobal(name) => {
+                        let v = act.pop();
+                        let res = act.globals.set(*name, &v);
+                        if let Err(e) = res {
+                            return self.fail(&template, pc, e);
+                        }
+                        Value::Unit
+                    },
+                    Op::DefineGlobal(name) => {
+                        let v = act.pop();
+                        let res = act.globals.define(*name, &v);
+                        if let Err(e) = res {
+                            return self.fail(&template, pc, e);
+                        }
+                        v
+                    },
+                    Op::Pop => {
+                        act.pop();
+                        continue;
+                    },
+                    Op::Jump(target) => {
+                        act.pc = *target;
+                        continue;
+                    },
+                    Op::JumpIfFalse(target) => {
+                        if act.pop() == Value::Boolean(false) {
+                            act.pc = *target;
+                        }
+                        continue;
+                    },
+                    Op::Closure(t) => Value::Procedure(Function::Compiled(Rc::new(Compiled { template: t.clone(), locals: act.locals, globals: act.globals }))),
+                    Op::Enter(count, names) => {
+                        let values = act.stack.split_off(act.stack.len() - count);
+                        act.locals = Some(Locals::alloc(values, names.clone(), act.locals.take()));
+                        continue;
+                    },
+                    Op::Leave => {
+                        act.locals = match act.locals.map(|l| l.with(|l| l.parent)).transpose() {
+                            Ok(parent) => parent.flatten(),
+                            Err(e) => return self.fail(&template, pc, e),
+                        };
+                        continue;
+                    },
+                    Op::Return => {
+                        let v = act.pop();
+                        match self.stack.pop() {
+                            Some(Frame::Code(mut caller)) => {
+                                caller.stack.push(v);
+                                act = caller;
+                                continue 'activation;
+                            },
+                            Some(frame) => return self.resume(frame, v),
+                            None => return Ok(Tail::Return(v)),
+                        }
+                    },
+                    op @ Op::Call(argc) | op @ Op::TailCall(argc) => {
+                        let tail = matches!(op, Op::TailCall(_));
+                        self.locate(&template, pc);
+                        let base = act.stack.len() - argc;
+
+                        // * natives run right here, without taking their arguments off the stack first
+                        if let Value::Procedure(Function::Native(f)) = &act.stack[base - 1] {
+                            let v = f(&act.stack[base..])?;
+                            act.stack.truncate(base - 1);
+                            act.stack.push(v);
+                            // * after a tail call of a native, the return following it is next
+                            continue;
+                        }
+
+                        let args = act.stack.split_off(base);
+                        let f = match act.pop() {
+                            Value::Procedure(f) => f,
+                            v => runtime_error!("first entry must be procedure: {:?}", v),
+                        };
+                        match &f {
+                            Function::Compiled(c) => {
+                                let callee = Activation::call(&c.template, c.locals, c.globals, args)?;
+                                match tail {
+                                    true => act = callee,
+                                    false => self.stack.push(Frame::Code(mem::replace(&mut act, callee))),
+                                }
+                                continue 'activation;
+                            },
+                            _ => {
+                                let env = act.globals;
+                                if !tail {
+                                    self.stack.push(Frame::Code(act));
+                                }
+                                return self.apply(&f, &args, env);
+                            },
+                        }
+                    },
+                };
+                act.stack.push(v);
+            }
+        }
+    }
+
+    // * report an error at the form the instruction was compiled from
+    // * an instruction outside of any located form of its body keeps the span of the call
+    fn fail(&mut self, template: &Template, pc: usize, e: RuntimeError) -> Result<Tail, RuntimeError> {
+        self.locate(template, pc);
+        Err(e)
+    }
+
+    fn locate(&mut self, template: &Template, pc: usize) {
+        if let Some(span) = &template.spans[pc] {
+            self.span = Some(span.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::interpreter::eval::{Engine, Evalator};
+
+    fn eval_on(engine: Engine, input: &str) -> Result<String, String> {
+        // * the prelude is compiled as well when the vm is tested
+        let mut evalator = Evalator::bare();
+        evalator.set_engine(engine);
+        evalator.load_prelude().unwrap();
+        evalator.eval_source("<input>", input).map(|v| format!("{:?}", v)).map_err(|e| e.to_string())
+    }
+
+    // * the vm must give what the tree-walker gives, errors included
+    fn same(input: &str) -> String {
+        let res = eval_on(Engine::Vm, input);
+        assert_eq!(res, eval_on(Engine::Tree, input), "{}", input);
+        res.unwrap_or_else(|e| e)
+    }
+
+    #[test]
+    fn vm_benchmarks() {
+        assert_eq!(same(&format!("{}(fib 15)", include_str!("../../bench/fib.sch"))), "610");
+        assert_eq!(same(&format!("{}(tak 12 8 4)", include_str!("../../bench/tak.sch"))), "5");
+        assert_eq!(same(&format!("{}(queens 6)", include_str!("../../bench/nqueens.sch"))), "4");
+    }
+
+    #[test]
+    fn vm_closures_and_frames() {
+        assert_eq!(same("(define (counter) (let ((n 0)) (lambda () (set! n (+ n 1)) n))) (define c (counter)) (c) (c)"), "2");
+        assert_eq!(same("(define (f x) (define (g) (* x y)) (define y 2) (g)) (f 21)"), "42");
+        assert_eq!(same("(let ((x 1)) (let ((x 2) (y x)) (list x y)))"), "(2 1)");
+        assert_eq!(same("(define x 'y) `(x ,x ,@(list 1 2) #(,x))"), "(x y 1 2 #(y))");
+        assert_eq!(same("(define (f) (let () 1 2)) (f)"), "2");
+        // * each evaluation of a lambda is a new procedure
+        assert_eq!(same("(define (mk) (lambda () 1)) (define f (mk)) (list (eq? (mk) (mk)) (eq? f f) (equal? (mk) (mk)))"), "(#false #true #false)");
+        assert_eq!(same("(define-syntax swap! (syntax-rules () ((_ a b) (let ((t a)) (set! a b) (set! b t))))) (define (f x y) (swap! x y) (list x y)) (f 1 2)"), "(2 1)");
+    }
+
+    #[test]
+    fn vm_tail_calls() {
+        assert_eq!(same("(define (count n acc) (if (= n 0) acc (count (- n 1) (+ acc 1)))) (count 100000 1)"), "100001");
+        assert_eq!(same("(define (even n) (if (= n 0) #t (odd (- n 1)))) (define (odd n) (if (= n 0) #f (even (- n 1)))) (even 100001)"), "#false");
+        assert_eq!(same("(define (loop n) (let ((m (- n 1))) (if (= m 0) 'done (loop m)))) (loop 100000)"), "done");
+        assert_eq!(same("(define (loop n) (if (= n 0) 'done (apply loop (list (- n 1))))) (loop 10000)"), "done");
+    }
+
+    #[test]
+    fn vm_derived_forms() {
+        assert_eq!(same("(define (f x) (cond ((< x 0) 'neg) ((assv x '((0 . zero))) => cdr) (else 'pos))) (map f '(-1 0 1))"), "(neg zero pos)");
+        assert_eq!(same("(case (car '(c d)) ((a e i o u) 'vowel) ((w y) 'semivowel) (else => list))"), "(c)");
+        assert_eq!(same("(list (and 1 2) (or #f '()) (when #f 1) (unless #f 2))"), "(2 () () 2)");
+        assert_eq!(same("(do ((v (make-vector 3)) (i 0 (+ i 1))) ((= i 3) v) (vector-set! v i i))"), "#(0 1 2)");
+        // * the last expression of each form is in tail position
+        assert_eq!(same("(define (loop n) (cond ((= n 0) 'done) (else (and #t (or #f (when #t (unless #f (case 1 ((1) (begin (loop (- n 1)))))))))))) (loop 100000)"), "done");
+        assert_eq!(same("(do ((i 0 (+ i 1)) (acc 0 (+ acc i))) ((= i 100000) acc))"), "4999950000");
+        assert_eq!(same("(define (loop n) (if (= n 0) 'done (eval (list 'unless (list '= n 1) (list 'loop (- n 1)) ''done)))) (loop 100000)"), "done");
+    }
+
+    #[test]
+    fn vm_rest_parameters() {
+        assert_eq!(same("(define (f a . r) (list a r)) (list (f 1) (f 1 2 3))"), "((1 ()) (1 (2 3)))");
+        assert_eq!(same("(define (g . xs) (apply + xs)) (g 1 2 3)"), "6");
+        assert_eq!(same("(define (h x) (lambda (y . z) (list x y z))) ((h 1) 2 3)"), "(1 2 (3))");
+        assert!(same("(define (f a b . r) a) (f 1)").contains("expect at least 2 arguments but got 1"));
+    }
+
+    #[test]
+    fn vm_prelude() {
+        assert_eq!(same("(map (lambda (x y) (* x y)) '(1 2 3) '(4 5))"), "(4 10)");
+        assert_eq!(same("(fold-left cons '() '(1 2))"), "((() . 1) . 2)");
+        assert_eq!(same("(define sum 0) (for-each (lambda (x) (set! sum (+ sum x))) (iota 5 1)) sum"), "15");
+        assert_eq!(same("(list-index (lambda (x) (= x 3)) (iota 10))"), "3");
+    }
+
+    #[test]
+    fn vm_control() {
+        assert_eq!(same("(+ 1 (call/cc (lambda (k) (+ 10 (k 2)))))"), "3");
+        let input = "(define k #f) (define seen '()) \
+            (define (run) (let ((n (call/cc (lambda (c) (set! k c) 2)))) (set! seen (cons n seen)) (if (= n 0) (reverse seen) (k (- n 1))))) \
+            (run)";
+        assert_eq!(same(input), "(2 1 0)");
+        // * re-entering a body runs its define again, which assigns the name
+        let input = "(define kk #f) (define n 0) \
+            (define (f) (define x (call/cc (lambda (k) (set! kk k) 1))) (set! n (+ n 1)) (if (< n 3) (kk (+ x 1)) (list n x))) \
+            (f)";
+        assert_eq!(same(input), "(3 3)");
+        let input = "(define trace '()) (define (note x) (lambda () (set! trace (cons x trace)))) \
+            (call/cc (lambda (k) (dynamic-wind (note 'in) (lambda () (k 1) ((note 'skipped))) (note 'out)))) (reverse trace)";
+        assert_eq!(same(input), "(in out)");
+        assert_eq!(same("(vector-map (lambda (x) (* x x)) #(1 2 3))"), "#(1 4 9)");
+        assert_eq!(same("(with-exception-handler (lambda (e) 10) (lambda () (+ 1 (raise-continuable 'oops))))"), "11");
+        assert_eq!(same("(define (safe-car x) (guard (e (#t 'none)) (car x))) (define (f x) (safe-car x)) (list (f '(1)) (f 1))"), "(1 none)");
+        assert_eq!(same("(guard (e ((error-object? e) (error-object-message e))) ((lambda () (car 1))))"), "\"car expects a pair but got: 1\"");
+    }
+
+    #[test]
+    fn vm_errors() {
+        let err = same("(define (f)\n  (define a b)\n  (define b 1)\n  a)\n(f)");
+        // * the definitions of a body have no location of their own, the call is reported
+        assert!(err.starts_with("<input>:5:1: Runtime Error: Used before define"), "{}", err);
+        let err = same("(define (f x) x)\n(list 1\n  (f))");
+        assert!(err.starts_with("<input>:3:3: Runtime Error: expect 1 arguments but got 0"), "{}", err);
+        let err = same("(define (f)\n  (+ 1 undefined))\n(f)");
+        assert!(err.starts_with("<input>:2:3: Runtime Error: Used before define: \"undefined\""), "{}", err);
+        assert_eq!(same("(define x 1) (define x 2) x"), "2");
+        assert!(same("(define (f) (define x 1) (define x 2) x) (f)").contains("already defined"));
+        assert!(eval_on(Engine::Vm, "(1 2)").unwrap_err().contains("first entry must be procedure: 1"));
+    }
+}
@@ -1,3 +1,10 @@
+#![allow(clippy::doc_lazy_continuation)]
+
 pub mod repl;
+pub mod editor;
+pub mod script;
 pub mod interpreter;
-pub mod util;
\ No newline at end of file
+pub mod util;
+pub mod error;
+
+pub use error::Error;
\ No newline at end of file
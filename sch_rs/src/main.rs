@@ -1,8 +1,27 @@
 use sch_rs::repl::Repl;
-
+use sch_rs::script::{Mode, USAGE};
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process;
 
 
 fn main() {
-    let repl = Repl {};
-    repl.run();
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    match Mode::from_args(args, io::stdin().is_terminal()) {
+        Ok(Mode::Repl(options)) => Repl::with_options(options).run(),
+        Ok(Mode::Help) => println!("{}", USAGE),
+        Ok(Mode::Script(script)) => {
+            // * an error no handler caught fails the process, so shell pipelines can see it
+            if let Err(e) = script.run() {
+                let _ = io::stdout().flush();
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        },
+        Err(msg) => {
+            eprintln!("sch_rs: {}\n{}", msg, USAGE);
+            process::exit(2);
+        },
+    }
 }
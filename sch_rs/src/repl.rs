@@ -1,98 +1,521 @@
 use std::io;
-use std::io::{Write};
+use std::io::{BufRead, Write};
 use std::fs;
-use crate::interpreter::{eval::Evalator, eval::{RuntimeError, Value}, lex::lexer, parser::Parser};
+use std::path::Path;
+use std::slice;
+use std::time::Instant;
+use crate::editor::{self, Completer, Editor};
+use crate::interpreter::{eval::{Engine, Evalator, Function, Params, Value}, expand::{Expander, SEPARATOR}, lex::lexer, parser::{Node, Parser}, span::{Source, Span}};
+use crate::Error;
+use crate::script::Options;
 
+const PROMPT: &str = "scheme_rs> ";
+// * shown while the input read so far is not a complete datum
+const CONTINUATION_PROMPT: &str = "      ...> ";
+
+/**
+ * * where the REPL reads its lines from, the line editor on a terminal or any buffered reader
+ */
+pub trait Input {
+    /**
+     * * show prompt and append the next line to buf, Ok(0) at the end of input
+     * ! an Interrupted error abandons the input read so far
+     */
+    fn read_line(&mut self, prompt: &str, out: &mut dyn Write, buf: &mut String, complete: Completer) -> io::Result<usize>;
+
+    // * remember a whole input, which may span several lines
+    fn add_history(&mut self, _entry: &str) {}
+}
+
+impl<R: BufRead> Input for R {
+    fn read_line(&mut self, prompt: &str, out: &mut dyn Write, buf: &mut String, _complete: Completer) -> io::Result<usize> {
+        write!(out, "{}", prompt)?;
+        out.flush()?;
+        BufRead::read_line(self, buf)
+    }
+}
+
+/**
+ * * the interactive loop, one Evalator lives for the whole session
+ * * so definitions made by one input are seen by the following ones
+ */
 pub struct Repl {
+    evalator: Evalator,
+    // * what a reset makes the new evalator with
+    options: Options,
 }
 
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Repl {
-    pub fn run(&self) {
-        
+    pub fn new() -> Repl {
+        Repl::with_options(Options::default())
+    }
+
+    pub fn with_options(options: Options) -> Repl {
+        Repl { evalator: options.evalator(), options }
+    }
+
+    pub fn set_engine(&mut self, engine: Engine) {
+        self.options.engine = engine;
+        self.evalator.set_engine(engine);
+    }
+
+    /**
+     * * on a terminal the lines are read with the line editor, otherwise stdin is read as it comes
+     */
+    pub fn run(&mut self) {
+        // * the session is over once stdout is gone, there is nobody left to report to
+        if editor::is_terminal() {
+            let _ = self.session(&mut Editor::new(), io::stdout());
+        } else {
+            let stdin = io::stdin();
+            let _ = self.run_with(stdin.lock(), io::stdout());
+        }
+    }
+
+    /**
+     * * read inputs until `quit` or the end of input, printing the result of every form to out
+     * * an input goes on over several lines until its parens and strings are balanced
+     * ! an error is printed and the loop goes on with the state the failed input left behind
+     */
+    pub fn run_with<R: BufRead, W: Write>(&mut self, mut input: R, out: W) -> io::Result<()> {
+        self.session(&mut input, out)
+    }
+
+    fn session<I: Input, W: Write>(&mut self, input: &mut I, mut out: W) -> io::Result<()> {
+
         let mut cmd = String::new();
 
-        println!("start the repl");
+        writeln!(out, "start the repl")?;
 
-        loop {
+        'session: loop {
 
             cmd.clear();
-            
-            print!("scheme_rs> ");
 
-            io::stdout().flush().unwrap();
-            io::stdin().read_line(&mut cmd).unwrap();
+            let mut prompt = PROMPT;
+            let eof = loop {
+                match input.read_line(prompt, &mut out, &mut cmd, &|before| self.complete(before)) {
+                    Ok(0) => break true,
+                    Ok(_) if is_incomplete(&cmd) => prompt = CONTINUATION_PROMPT,
+                    Ok(_) => break false,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue 'session,
+                    Err(e) => return Err(e),
+                }
+            };
+            input.add_history(&cmd);
 
+            let (name, arg) = split_command(&cmd);
 
-            let cmd: Vec<&str> = cmd.trim().split(" ").collect();
+            if name.is_empty() {
+                if eof {
+                    writeln!(out)?;
+                    return Ok(());
+                }
+                continue;
+            }
 
-            assert_eq!(cmd.len()<=2, true);
-            
-            match cmd[0] {
-                "quit" => {
-                    println!("quitting the sch_rs");
-                    break;
+            match COMMANDS.iter().find(|c| c.name == name) {
+                Some(command) => match (command.run)(self, &arg, &mut out) {
+                    Ok(Flow::Quit) => return Ok(()),
+                    Ok(Flow::Continue) => {},
+                    Err(e) => writeln!(out, "{}", e)?,
+                },
+                None => {
+                    if let Err(e) = self.eval_print(&cmd, &mut out) {
+                        writeln!(out, "{}", e)?;
+                    }
                 },
-                "load" => {
-                    println!("load command: {}", cmd[1]);
-                    match self.load(cmd[1]) {
-                        Ok(s) => {
-                            println!("Tokenize result: {:?}", self.interp(&s))
-                        },
-                        Err(e) => eprintln!("Error in loading exmaple {}: {}", cmd[1], e),
-                    };
-                }
-                _ => { continue; },
+            }
+
+            if eof {
+                writeln!(out)?;
+                return Ok(());
             }
         }
     }
 
-    fn load(&self, file: &str) -> Result<String, io::Error> {
+    /**
+     * * every form of the input is evaluated and printed in turn, up to the first error
+     */
+    fn eval_print(&mut self, input: &str, out: &mut dyn Write) -> Result<(), Error> {
+        for node in self.read("<repl>", input)? {
+            let v = self.evalator.eval(slice::from_ref(&node))?;
+            Self::print(out, &v)?;
+        }
+        Ok(())
+    }
 
-        // let f = File::open(format!("./script/{}", file)).expect("File does not exist");
-        let path = &format!("./example/{}", file);
+    /**
+     * * results are printed the way `write` prints them, nothing for an unspecified value
+     */
+    fn print(out: &mut dyn Write, v: &Value) -> io::Result<()> {
+        match v {
+            Value::Unit => Ok(()),
+            _ => writeln!(out, "{:?}", v),
+        }
+    }
 
-        fs::read_to_string(path)
+    /**
+     * * the completions of the word before the cursor: file names after `load`, bound identifiers otherwise
+     * * returns the char index the word starts at and the candidates
+     */
+    fn complete(&self, before: &str) -> (usize, Vec<String>) {
+        let trimmed = before.trim_start();
+        if trimmed.starts_with(',') && !trimmed.contains(char::is_whitespace) {
+            let names = COMMANDS.iter().map(|c| c.name).filter(|n| n.starts_with(trimmed)).map(String::from).collect();
+            return (before.len() - trimmed.len(), names);
+        }
+        let is_load = match before.trim_start().strip_prefix("load") {
+            Some(rest) => rest.starts_with(char::is_whitespace) && !rest.trim_start().contains(char::is_whitespace),
+            None => false,
+        };
+        let start = match is_load {
+            true => before.rfind(char::is_whitespace).map_or(0, |i| i + 1),
+            false => before.rfind(|c: char| c.is_whitespace() || "()[]\"';`,".contains(c)).map_or(0, |i| i + 1),
+        };
+        let word = &before[start..];
+        let candidates = match is_load {
+            true => complete_file(Path::new("."), word),
+            false => self.evalator.bound_names().into_iter().filter(|n| n.starts_with(word)).collect(),
+        };
+        (before[..start].chars().count(), candidates)
     }
 
-    fn interp(&self, input: &String) -> Result<Value, RuntimeError> {
-        match lexer::lex(input) {
-            Ok(tokens) => {
-                let nodes = Parser::parse(&tokens).unwrap();
-                println!("nodes parsed: {:?}", nodes);
-                let evalator = Evalator::new();
-                let res = evalator.eval(&nodes);
-                println!("interp result: {:?}", res);
-                res
-            }
-            _ => panic!(format!("Error in lexing input: {}", input)),
+    fn usage(name: &str, out: &mut dyn Write) -> Result<Flow, Error> {
+        let command = COMMANDS.iter().find(|c| c.name == name).unwrap();
+        writeln!(out, "usage: {} {}", command.name, command.args)?;
+        Ok(Flow::Continue)
+    }
+
+    fn command_quit(&mut self, arg: &str, out: &mut dyn Write) -> Result<Flow, Error> {
+        if !arg.trim().is_empty() {
+            return Self::usage("quit", out);
+        }
+        writeln!(out, "quitting the sch_rs")?;
+        Ok(Flow::Quit)
+    }
+
+    fn command_load(&mut self, arg: &str, out: &mut dyn Write) -> Result<Flow, Error> {
+        let file = match arg.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [file] => file.to_string(),
+            _ => return Self::usage("load", out),
+        };
+        let v = self.load(&file).and_then(|s| self.interp(&file, &s))?;
+        Self::print(out, &v)?;
+        Ok(Flow::Continue)
+    }
+
+    fn command_env(&mut self, arg: &str, out: &mut dyn Write) -> Result<Flow, Error> {
+        if !arg.trim().is_empty() {
+            return Self::usage(",env", out);
+        }
+        for (name, v) in self.evalator.bindings() {
+            writeln!(out, "{} = {:?}", name, v)?;
+        }
+        Ok(Flow::Continue)
+    }
+
+    /**
+     * * what a name means at toplevel, a closure shows its parameters and where its body was read
+     */
+    fn command_describe(&mut self, arg: &str, out: &mut dyn Write) -> Result<Flow, Error> {
+        let name = match arg.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [name] => name.to_string(),
+            _ => return Self::usage(",describe", out),
+        };
+        match self.evalator.lookup(&name) {
+            Some(Value::Procedure(Function::Closure(c))) => {
+                Self::describe_procedure(&name, "procedure", c.lambda.params(), c.lambda.source(), out)?;
+            },
+            Some(Value::Procedure(Function::Compiled(c))) => {
+                Self::describe_procedure(&name, "compiled procedure", c.template.params(), c.template.source(), out)?;
+            },
+            Some(Value::Procedure(Function::Continuation(_))) => writeln!(out, "{} is a continuation", name)?,
+            Some(Value::Procedure(_)) => writeln!(out, "{} is a native procedure", name)?,
+            Some(v) => writeln!(out, "{} is bound to {:?}", name, v)?,
+            None if self.evalator.is_keyword(&name) => writeln!(out, "{} is syntax", name)?,
+            None => writeln!(out, "{} is not bound", name)?,
+        }
+        Ok(Flow::Continue)
+    }
+
+    fn describe_procedure(name: &str, kind: &str, params: &Params, source: Option<&Span>, out: &mut dyn Write) -> io::Result<()> {
+        let required = params.required().iter().map(|p| Expander::source_name(p)).collect::<Vec<_>>();
+        let plural = if required.len() == 1 { "" } else { "s" };
+        // * the parameter list as it was written, a rest parameter after a dot
+        let (least, list) = match params.rest() {
+            None => ("", format!("({})", required.join(" "))),
+            Some(rest) if required.is_empty() => ("at least ", Expander::source_name(&rest).to_string()),
+            Some(rest) => ("at least ", format!("({} . {})", required.join(" "), Expander::source_name(&rest))),
+        };
+        writeln!(out, "{} is a {} of {}{} argument{}: {}", name, kind, least, (required.len()), plural, list)?;
+        if let Some(source) = source {
+            writeln!(out, "its body is at {}\n{}", source, source.excerpt())?;
+        }
+        Ok(())
+    }
+
+    fn command_time(&mut self, arg: &str, out: &mut dyn Write) -> Result<Flow, Error> {
+        if arg.trim().is_empty() {
+            return Self::usage(",time", out);
+        }
+        let start = Instant::now();
+        let res = self.eval_print(arg, out);
+        writeln!(out, "; took {:?}", start.elapsed())?;
+        res.map(|_| Flow::Continue)
+    }
+
+    fn command_expand(&mut self, arg: &str, out: &mut dyn Write) -> Result<Flow, Error> {
+        if arg.trim().is_empty() {
+            return Self::usage(",expand", out);
+        }
+        let nodes = self.read("<repl>", arg)?;
+        for form in self.evalator.expand(&nodes)? {
+            // * renamed identifiers are shown as name.N, the separator itself is a control char
+            writeln!(out, "{}", format!("{:?}", form).replace(SEPARATOR, "."))?;
+        }
+        Ok(Flow::Continue)
+    }
+
+    fn command_reset(&mut self, arg: &str, out: &mut dyn Write) -> Result<Flow, Error> {
+        if !arg.trim().is_empty() {
+            return Self::usage(",reset", out);
+        }
+        self.evalator = self.options.evalator();
+        writeln!(out, "the interpreter is reset")?;
+        Ok(Flow::Continue)
+    }
+
+    fn command_help(&mut self, _arg: &str, out: &mut dyn Write) -> Result<Flow, Error> {
+        for command in COMMANDS {
+            let usage = format!("{} {}", command.name, command.args);
+            writeln!(out, "{:<20}{}", usage.trim_end(), command.help)?;
         }
+        writeln!(out, "any other input is evaluated")?;
+        Ok(Flow::Continue)
+    }
+
+    fn read(&self, name: &str, input: &str) -> Result<Vec<Node>, Error> {
+        let tokens = lexer::lex_source(&Source::new(name, input))?;
+        Ok(Parser::parse(&tokens)?)
+    }
+
+    /**
+     * * the text of a file, a relative path is taken from the working directory
+     */
+    fn load(&self, file: &str) -> Result<String, Error> {
+        Ok(fs::read_to_string(file)?)
     }
+
+    /**
+     * * evaluate the text of the file name in the session, errors are reported at their place in it
+     */
+    fn interp(&mut self, name: &str, input: &str) -> Result<Value, Error> {
+        self.evalator.eval_source(name, input)
+    }
+}
+
+enum Flow {
+    Continue,
+    Quit,
 }
 
+/**
+ * * an input starting with the name of a command runs the command on the rest of the input
+ */
+struct Command {
+    name: &'static str,
+    args: &'static str,
+    help: &'static str,
+    run: fn(&mut Repl, &str, &mut dyn Write) -> Result<Flow, Error>,
+}
+
+const COMMANDS: &[Command] = &[
+    Command { name: "quit", args: "", help: "leave the repl", run: Repl::command_quit },
+    Command { name: "load", args: "<file>", help: "evaluate a file", run: Repl::command_load },
+    Command { name: ",env", args: "", help: "list the bindings of the root env", run: Repl::command_env },
+    Command { name: ",describe", args: "<sym>", help: "show the value, arity and source of a binding", run: Repl::command_describe },
+    Command { name: ",time", args: "<expr>", help: "evaluate expr and show how long it took", run: Repl::command_time },
+    Command { name: ",expand", args: "<expr>", help: "show expr after macro expansion and desugaring", run: Repl::command_expand },
+    Command { name: ",reset", args: "", help: "start again from a fresh interpreter", run: Repl::command_reset },
+    Command { name: ",help", args: "", help: "list the commands", run: Repl::command_help },
+];
+
+/**
+ * * the first word of the input and the rest of it
+ * ! the rest keeps the place it had in the input, so errors in it point at the right column
+ */
+fn split_command(cmd: &str) -> (&str, String) {
+    let start = cmd.len() - cmd.trim_start().len();
+    let end = cmd[start..].find(char::is_whitespace).map_or(cmd.len(), |i| start + i);
+    (&cmd[start..end], format!("{}{}", " ".repeat(end), &cmd[end..]))
+}
+
+/**
+ * * whether more lines are needed, for a command only its argument has to be balanced
+ */
+fn is_incomplete(cmd: &str) -> bool {
+    let (name, arg) = split_command(cmd);
+    match COMMANDS.iter().any(|c| c.name == name) {
+        true => lexer::is_incomplete(&arg),
+        false => lexer::is_incomplete(cmd),
+    }
+}
+
+/**
+ * * the paths under dir starting with word, directories end with a slash so completion can go on inside them
+ */
+fn complete_file(dir: &Path, word: &str) -> Vec<String> {
+    let (sub, prefix) = match word.rfind('/') {
+        Some(i) => word.split_at(i + 1),
+        None => ("", word),
+    };
+    let entries = match fs::read_dir(dir.join(sub)) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut names: Vec<String> = entries.filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let slash = if entry.path().is_dir() { "/" } else { "" };
+            match name.starts_with(prefix) && (prefix.starts_with('.') || !name.starts_with('.')) {
+                true => Some(format!("{}{}{}", sub, name, slash)),
+                false => None,
+            }
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+#[cfg(test)]
 mod test {
     use super::*;
 
 
     fn test_template(input: &str, exp: Value) {
-        let reploop = Repl{};
+        let mut reploop = Repl::new();
             match reploop.load(input) {
-                Ok(s) => assert_eq!(reploop.interp(&s).unwrap(), exp),
-                _ => panic!(format!("fail to load the file: {}", input)),
+                Ok(s) => assert_eq!(reploop.interp(input, &s).unwrap(), exp),
+                _ => panic!("fail to load the file: {}", input),
             }
     }
+
+    fn session(input: &str) -> String {
+        session_with(Options::default(), input)
+    }
+
+    fn session_with(options: Options, input: &str) -> String {
+        let mut out = Vec::new();
+        Repl::with_options(options).run_with(input.as_bytes(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
     #[test]
     fn test_load_parse_eval() {
-        test_template("test.sch", Value::Integer(4));
+        test_template("example/test.sch", Value::from(4));
     }
 
     #[test]
     fn test_load_parse_eval_lambda() {
-        test_template("lambda.sch", Value::Integer(1));
+        test_template("example/lambda.sch", Value::from(1));
     }
 
     #[test]
     fn test_load_parse_eval_if() {
-        test_template("if.sch", Value::Integer(2));
+        test_template("example/if.sch", Value::from(2));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_session_keeps_state() {
+        let out = session("(define (sq x) (* x x))\n(sq 3)\n\n(list \"a\" #\\b 'c)\nquit\n(sq 4)\n");
+        assert_eq!(out, "start the repl\nscheme_rs> #procedure\nscheme_rs> 9\nscheme_rs> scheme_rs> (\"a\" #\\b c)\nscheme_rs> quitting the sch_rs\n");
+    }
+
+    #[test]
+    fn test_session_continues_after_errors() {
+        let out = session("(define x 2)\n(car x)\n(+ x \"a\")\n(* x 10))\n(* x 10)\n");
+        let errors: Vec<&str> = out.lines().filter(|l| l.contains("Error")).collect();
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].ends_with("<repl>:1:1: Runtime Error: car expects a pair but got: 2"));
+        assert!(errors[1].contains("<repl>:1:1: Runtime Error"));
+        assert!(errors[2].contains("<repl>:1:9: Parse Error: No matching open paren!"));
+        assert!(out.ends_with("scheme_rs> 20\nscheme_rs> \n"));
+    }
+
+    #[test]
+    fn test_session_load() {
+        let out = session("load example/test.sch\n(* x y)\nload missing.sch\n");
+        assert!(out.contains("scheme_rs> 4\nscheme_rs> 4\nscheme_rs> I/O Error"));
+    }
+
+    #[test]
+    fn test_session_redefines() {
+        // * a toplevel define of a bound name replaces it, a file can be loaded again
+        let out = session("(define (f x) (* x 2))\n(f 5)\n(define (f x) (+ x 2))\n(f 5)\nload example/test.sch\nload example/test.sch\n");
+        assert_eq!(out, "start the repl\nscheme_rs> #procedure\nscheme_rs> 10\nscheme_rs> #procedure\nscheme_rs> 7\nscheme_rs> 4\nscheme_rs> 4\nscheme_rs> \n");
+    }
+
+    #[test]
+    fn test_session_multi_line() {
+        let out = session("(define (f x)\n  (* x\n     2))\n(f 21) (f 1)\n\"two\nlines\"\n'\nsym #\\(\n");
+        assert_eq!(out, "start the repl\nscheme_rs>       ...>       ...> #procedure\nscheme_rs> 42\n2\nscheme_rs>       ...> \"two\\nlines\"\nscheme_rs>       ...> sym\n#\\(\nscheme_rs> \n");
+    }
+
+    #[test]
+    fn test_commands() {
+        let out = session(",describe sq\n(define (sq x)\n  (* x x))\n(define y '(1))\n,describe sq\n,describe y\n,describe car\n,describe let\n,describe\n,reset\n,describe sq\n");
+        assert_eq!(out, "start the repl\nscheme_rs> sq is not bound\nscheme_rs>       ...> #procedure\nscheme_rs> (1)\nscheme_rs> sq is a procedure of 1 argument: (x)\nits body is at <repl>:2:3\n  |\n2 |   (* x x))\n  |   ^^^^^^^\nscheme_rs> y is bound to (1)\nscheme_rs> car is a native procedure\nscheme_rs> let is syntax\nscheme_rs> usage: ,describe <sym>\nscheme_rs> the interpreter is reset\nscheme_rs> sq is not bound\nscheme_rs> \n");
+    }
+
+    #[test]
+    fn test_commands_eval() {
+        // * without the prelude no identifier has been renamed before the session starts
+        let bare = Options { prelude: false, ..Options::default() };
+        let out = session_with(bare, ",expand (let ((a 1))\n  (car a))\n,time (+ 1 2) (car 1)\n,env\n,help\nquit now\n");
+        assert!(out.contains("scheme_rs>       ...> (let ((a.1 1)) (car a.1))\nscheme_rs> 3\n; took "));
+        assert!(out.contains("<repl>:1:15: Runtime Error: car expects a pair"));
+        assert!(out.contains("\ncar = #procedure\n"));
+        assert!(out.contains(",expand <expr>      show expr after macro expansion and desugaring\n"));
+        assert!(out.ends_with("scheme_rs> usage: quit \nscheme_rs> \n"));
+        assert!(session(",expand (lambda)\n").contains("<repl>:1:9: Runtime Error: bad syntax"));
+        assert!(session(",describe iota\n").contains("iota is a procedure of at least 1 argument: (count . opts)\nits body is at <prelude>:"));
+        assert!(session("(define (g . xs) xs)\n,describe g\n").contains("g is a procedure of at least 0 arguments: xs\n"));
+
+        // * the engine outlives a reset
+        let mut repl = Repl::new();
+        repl.set_engine(Engine::Vm);
+        let mut out = Vec::new();
+        repl.run_with(",reset\n(define (add a b)\n  (+ a b))\n,describe add\n".as_bytes(), &mut out).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("add is a compiled procedure of 2 arguments: (a b)\nits body is at <repl>:2:3\n"));
+    }
+
+    #[test]
+    fn test_complete() {
+        let mut repl = Repl::new();
+        repl.run_with("(define (square x) (* x x))\n(define-syntax sq-twice (syntax-rules () ((_ x) (square (square x)))))\n".as_bytes(), io::sink()).unwrap();
+        assert_eq!(repl.complete("(sq"), (1, vec!["sq-twice".to_string(), "square".to_string()]));
+        assert_eq!(repl.complete("(car (vector-r"), (6, vec!["vector-ref".to_string()]));
+        assert_eq!(repl.complete("(letr"), (1, vec!["letrec-syntax".to_string()]));
+        assert_eq!(repl.complete("(zzz"), (1, vec![]));
+        assert_eq!(repl.complete("load exa"), (5, vec!["example/".to_string()]));
+        assert_eq!(repl.complete("load example/t"), (5, vec!["example/test.sch".to_string()]));
+        assert_eq!(repl.complete("load  example/"), (6, vec!["example/if.sch".to_string(), "example/lambda.sch".to_string(), "example/test.sch".to_string()]));
+        assert_eq!(repl.complete("(load ./"), (6, vec![]));
+        assert_eq!(repl.complete(" ,e"), (1, vec![",env".to_string(), ",expand".to_string()]));
+    }
+
+    #[test]
+    fn test_session_eof() {
+        // * the forms before an error are still printed, an unbalanced input at the end is reported
+        let out = session("(+ 1 1) (car 1) (+ 2 2)\n(+ 1\n");
+        assert!(out.starts_with("start the repl\nscheme_rs> 2\n<repl>:1:9: Runtime Error"));
+        assert!(out.ends_with("scheme_rs>       ...> <repl>:1:1: Parse Error: Unexpected end of input, this paren is never closed\n  |\n1 | (+ 1\n  | ^\n\n"));
+        assert_eq!(session(""), "start the repl\nscheme_rs> \n");
+    }
+}
@@ -0,0 +1,235 @@
+use std::fs;
+use std::path::Path;
+use std::io::{self, Read, Write};
+use crate::interpreter::{eval::{Engine, Evalator, Value}, system};
+use crate::Error;
+
+pub const USAGE: &str = "usage: sch_rs [option]                      start the repl, or run the program on stdin when it is not a terminal
+       sch_rs [option] <file> [arg ...]      run the program in file
+       sch_rs [option] -e <expr> [arg ...]   evaluate expr and print its value
+       sch_rs [option] - [arg ...]           run the program read from stdin
+       sch_rs -h                             show this help
+option: --engine tree|vm                     walk the forms (the default) or compile them to bytecode
+        --bare                               start without the procedures of the prelude, like map or filter";
+
+/**
+ * * where the program to run comes from
+ */
+#[derive(Debug, PartialEq)]
+pub enum Program {
+    File(String),
+    Expr(String),
+    Stdin,
+}
+
+/**
+ * * how the evalator of the repl or of a script is made
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Options {
+    pub engine: Engine,
+    pub prelude: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options { engine: Engine::Tree, prelude: true }
+    }
+}
+
+impl Options {
+    /**
+     * * a new evalator, the prelude is loaded with the engine so the vm runs it compiled
+     */
+    pub fn evalator(&self) -> Evalator {
+        let mut evalator = Evalator::bare();
+        evalator.set_engine(self.engine);
+        if self.prelude {
+            evalator.load_prelude().expect("the prelude evaluates");
+        }
+        evalator
+    }
+}
+
+/**
+ * * what the command line asks the binary to do
+ */
+#[derive(Debug, PartialEq)]
+pub enum Mode {
+    Repl(Options),
+    Help,
+    Script(Script),
+}
+
+impl Mode {
+    /**
+     * * the mode for the arguments after the binary name, Err with a message for a bad command line
+     * * without arguments the program is read from stdin, unless stdin is a terminal where the repl starts
+     */
+    pub fn from_args(args: Vec<String>, interactive: bool) -> Result<Mode, String> {
+        let mut args = args.into_iter().peekable();
+        let mut options = Options::default();
+        while let Some(option) = args.next_if(|arg| arg == "--engine" || arg == "--bare") {
+            match option.as_str() {
+                "--bare" => options.prelude = false,
+                _ => options.engine = match args.next() {
+                    Some(name) => Engine::from_name(&name).ok_or(format!("unknown engine: {}", name))?,
+                    None => return Err("--engine expects tree or vm".to_string()),
+                },
+            }
+        }
+
+        let program = match args.next().as_deref() {
+            None if interactive => return Ok(Mode::Repl(options)),
+            None | Some("-") => Program::Stdin,
+            Some("-h") | Some("--help") => return Ok(Mode::Help),
+            Some("-e") => match args.next() {
+                Some(expr) => Program::Expr(expr),
+                None => return Err("-e expects an expression".to_string()),
+            },
+            Some(opt) if opt.starts_with('-') => return Err(format!("unknown option: {}", opt)),
+            Some(path) => Program::File(path.to_string()),
+        };
+        let mut script = Script::new(program, args.collect());
+        script.options = options;
+        Ok(Mode::Script(script))
+    }
+}
+
+/**
+ * * a program run once without the repl, with the arguments (command-line) returns after its name
+ */
+#[derive(Debug, PartialEq)]
+pub struct Script {
+    program: Program,
+    args: Vec<String>,
+    options: Options,
+}
+
+impl Script {
+    pub fn new(program: Program, args: Vec<String>) -> Script {
+        Script { program, args, options: Options::default() }
+    }
+
+    // * the program name (command-line) starts with
+    fn name(&self) -> &str {
+        match &self.program {
+            Program::File(path) => path,
+            Program::Expr(_) => "-e",
+            Program::Stdin => "-",
+        }
+    }
+
+    pub fn run(&self) -> Result<(), Error> {
+        let stdin = io::stdin();
+        self.run_with(stdin.lock(), io::stdout())
+    }
+
+    /**
+     * * evaluate the program, the value of an -e expression is written to out
+     * ! the first error which no handler catches stops the program and is returned
+     */
+    pub fn run_with<R: Read, W: Write>(&self, mut input: R, mut out: W) -> Result<(), Error> {
+        let (source, text) = match &self.program {
+            Program::File(path) => (path.as_str(), fs::read_to_string(path)?),
+            Program::Expr(expr) => ("<expr>", expr.clone()),
+            Program::Stdin => {
+                let mut text = String::new();
+                input.read_to_string(&mut text)?;
+                ("<stdin>", text)
+            },
+        };
+
+        let mut command_line = vec![self.name().to_string()];
+        command_line.extend(self.args.iter().cloned());
+        system::set_command_line(command_line);
+
+        let mut evalator = self.options.evalator();
+        // * the libraries next to a script are found wherever it is run from
+        if let Program::File(path) = &self.program {
+            if let Some(dir) = Path::new(path).parent() {
+                evalator.add_library_path(dir);
+            }
+        }
+        let v = evalator.eval_source(source, skip_shebang(&text))?;
+        // * only the value of an expression is printed, the way the repl prints it
+        if matches!(self.program, Program::Expr(_)) && v != Value::Unit {
+            writeln!(out, "{:?}", v)?;
+        }
+        Ok(())
+    }
+}
+
+/**
+ * * the text without a `#!` first line, the newline is kept so the lines after it keep their numbers
+ */
+fn skip_shebang(text: &str) -> &str {
+    match text.starts_with("#!") {
+        true => &text[text.find('\n').unwrap_or(text.len())..],
+        false => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn run_on(engine: Engine, program: Program, input: &str) -> Result<String, Error> {
+        let mut out = Vec::new();
+        let script = Script { program, args: args(&["a", "b"]), options: Options { engine, prelude: true } };
+        script.run_with(input.as_bytes(), &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    fn run(program: Program, input: &str) -> Result<String, Error> {
+        run_on(Engine::Tree, program, input)
+    }
+
+    #[test]
+    fn script_modes() {
+        assert_eq!(Mode::from_args(args(&[]), true), Ok(Mode::Repl(Options::default())));
+        assert_eq!(Mode::from_args(args(&["--engine", "vm"]), true), Ok(Mode::Repl(Options { engine: Engine::Vm, prelude: true })));
+        assert_eq!(Mode::from_args(args(&["--bare", "--engine", "vm"]), true), Ok(Mode::Repl(Options { engine: Engine::Vm, prelude: false })));
+        let script = Script { program: Program::File("s.sch".to_string()), args: args(&["--engine"]), options: Options { engine: Engine::Vm, prelude: true } };
+        assert_eq!(Mode::from_args(args(&["--engine", "tree", "--engine", "vm", "s.sch", "--engine"]), true), Ok(Mode::Script(script)));
+        assert!(Mode::from_args(args(&["--engine", "jit"]), true).is_err());
+        assert!(Mode::from_args(args(&["--engine"]), true).is_err());
+        assert_eq!(Mode::from_args(args(&[]), false), Ok(Mode::Script(Script::new(Program::Stdin, vec![]))));
+        assert_eq!(Mode::from_args(args(&["-", "x"]), true), Ok(Mode::Script(Script::new(Program::Stdin, args(&["x"])))));
+        assert_eq!(Mode::from_args(args(&["-e", "(+ 1 2)", "-e"]), true), Ok(Mode::Script(Script::new(Program::Expr("(+ 1 2)".to_string()), args(&["-e"])))));
+        assert_eq!(Mode::from_args(args(&["s.sch", "-v", "1"]), true), Ok(Mode::Script(Script::new(Program::File("s.sch".to_string()), args(&["-v", "1"])))));
+        assert_eq!(Mode::from_args(args(&["--help"]), true), Ok(Mode::Help));
+        assert!(Mode::from_args(args(&["-e"]), true).is_err());
+        assert!(Mode::from_args(args(&["-x", "s.sch"]), true).is_err());
+    }
+
+    #[test]
+    fn script_run() {
+        assert_eq!(run(Program::Expr("(define x 2) (* x 21)".to_string()), "").unwrap(), "42\n");
+        assert_eq!(run(Program::Expr("(command-line)".to_string()), "").unwrap(), "(\"-e\" \"a\" \"b\")\n");
+        assert_eq!(run(Program::Expr("(display \"\")".to_string()), "").unwrap(), "");
+        // * the value of a file or stdin program is not printed
+        assert_eq!(run(Program::File("example/test.sch".to_string()), "").unwrap(), "");
+        assert_eq!(run(Program::Stdin, "#!/usr/bin/env sch_rs\n(car (cdr (command-line)))").unwrap(), "");
+    }
+
+    #[test]
+    fn script_errors() {
+        let err = run(Program::Stdin, "#!/usr/bin/env sch_rs\n(define x 1)\n(car x)").unwrap_err();
+        assert!(format!("{}", err).starts_with("<stdin>:3:1: Runtime Error: car expects a pair"));
+        assert!(matches!(run(Program::File("example/missing.sch".to_string()), ""), Err(Error::Io(_))));
+        assert!(matches!(run(Program::Expr("(car".to_string()), ""), Err(Error::Parse(_))));
+        assert_eq!(run(Program::Expr("(guard (e (#t 'caught)) (raise 1))".to_string()), "").unwrap(), "caught\n");
+        assert_eq!(skip_shebang("#!"), "");
+
+        // * the vm reports errors where the tree-walker does
+        let err = run_on(Engine::Vm, Program::Stdin, "(define (f x)\n  (car x))\n(f 1)").unwrap_err();
+        assert!(format!("{}", err).starts_with("<stdin>:2:3: Runtime Error: car expects a pair"));
+        assert_eq!(run_on(Engine::Vm, Program::Expr("(define (f) (guard (e (#t 'caught)) (raise 1))) (f)".to_string()), "").unwrap(), "caught\n");
+    }
+}
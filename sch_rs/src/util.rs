@@ -1,9 +1,18 @@
 use crate::interpreter::lex::{lexer, Token};
 use crate::interpreter::parser::*;
+use crate::interpreter::span::{Source, Span, Spanned};
+
+/**
+ * * give hand written tokens a position, one column each
+ */
+pub fn spanned(tokens: Vec<Token>) -> Vec<Spanned<Token>> {
+    let source = Source::new("<test>", "");
+    tokens.into_iter().enumerate().map(|(i, item)| Spanned { item, span: Span::new(source.clone(), 1, i + 1, 1) }).collect()
+}
 
 pub fn parse_test_template(input: Vec<Token>, exp: Vec<Node>) {
 
-    let act = Parser::parse(&input).unwrap();
+    let act = Parser::parse(&spanned(input)).unwrap();
     assert_eq!(act, exp);
 
 }